@@ -1,8 +1,10 @@
 mod mobile_network_core_event;
+pub mod notification_signature;
 
 pub use crate::mobile_network_core_event::{
-    AccuracyFulfillmentIndicator, CivicAddress, Event, EventKind, EventKind::LocationReporting,
-    EventKind::PdnConnectionEvent, GeographicArea, InterfaceIndication, LdrType, LocationInfo,
-    MinorLocationQoS, MobileNetworkCoreEvent, PdnConnectionInformation, PdnConnectionStatus,
-    PdnType, PositioningMethod,
+    AccuracyFulfillmentIndicator, ApplicationMigrationInformation, AreaOfInterestInformation,
+    CivicAddress, Event, EventKind, EventKind::LocationReporting, EventKind::PdnConnectionEvent,
+    GeographicArea, InterfaceIndication, LdrType, LocationInfo, MigrationStatus, MinorLocationQoS,
+    MobileNetworkCoreEvent, PdnConnectionInformation, PdnConnectionStatus, PdnType,
+    PositioningMethod, QosMonitoringInformation, UpPathChangeInformation,
 };
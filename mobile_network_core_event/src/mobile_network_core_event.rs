@@ -1,7 +1,7 @@
 use std::{
     hash::{Hash, Hasher},
     net::{Ipv4Addr, Ipv6Addr},
-    time::{SystemTime, UNIX_EPOCH, Duration},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use geo::{Point, Polygon};
@@ -11,12 +11,20 @@ use serde::{Deserialize, Serialize};
 pub enum EventKind {
     PdnConnectionEvent,
     LocationReporting,
+    UpPathChange,
+    QosMonitoring,
+    AreaOfInterest,
+    ApplicationMigration,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Event {
     PdnConnectionEvent(PdnConnectionInformation),
     LocationReporting(LocationInfo),
+    UpPathChange(UpPathChangeInformation),
+    QosMonitoring(QosMonitoringInformation),
+    AreaOfInterest(AreaOfInterestInformation),
+    ApplicationMigration(ApplicationMigrationInformation),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -60,6 +68,7 @@ impl Hash for LocationInfo {
 }
 
 impl LocationInfo {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         age_of_location_info: u64,
         e_node_b_id: String,
@@ -264,12 +273,142 @@ impl PdnConnectionInformation {
     }
 }
 
+/// The user plane path of an application's traffic moved to another edge data center.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UpPathChangeInformation {
+    pub application_id: u32,
+    pub source_edge_data_center_id: u32,
+    pub target_edge_data_center_id: u32,
+    pub ipv4_addr: Ipv4Addr,
+}
+
+impl UpPathChangeInformation {
+    pub fn new(
+        application_id: u32,
+        source_edge_data_center_id: u32,
+        target_edge_data_center_id: u32,
+        ipv4_addr: Ipv4Addr,
+    ) -> Self {
+        Self {
+            application_id,
+            source_edge_data_center_id,
+            target_edge_data_center_id,
+            ipv4_addr,
+        }
+    }
+}
+
+/// The measured delay of a QoS flow exceeded its packet delay budget.
+/// Delays are given in milliseconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QosMonitoringInformation {
+    pub qos_reference: String,
+    pub application_id: u32,
+    pub ipv4_addr: Ipv4Addr,
+    pub packet_delay_budget: u32,
+    pub measured_delay: u64,
+}
+
+impl QosMonitoringInformation {
+    pub fn new(
+        qos_reference: String,
+        application_id: u32,
+        ipv4_addr: Ipv4Addr,
+        packet_delay_budget: u32,
+        measured_delay: u64,
+    ) -> Self {
+        Self {
+            qos_reference,
+            application_id,
+            ipv4_addr,
+            packet_delay_budget,
+            measured_delay,
+        }
+    }
+}
+
+/// A UE entered, left or is inside an area of interest.
+/// `number_of_ues` counts all UEs inside the area after the move.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AreaOfInterestInformation {
+    pub area_id: u32,
+    pub ldr_type: LdrType,
+    pub geographic_area: GeographicArea,
+    pub number_of_ues: u32,
+}
+
+impl AreaOfInterestInformation {
+    pub fn new(area_id: u32, ldr_type: LdrType, position: Point, number_of_ues: u32) -> Self {
+        Self {
+            area_id,
+            ldr_type,
+            geographic_area: GeographicArea::Point(position),
+            number_of_ues,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MigrationStatus {
+    /// The state of the application is copied while the source keeps serving.
+    Transferring,
+    /// Neither edge data center serves the application while it switches over.
+    Interrupted,
+    Completed,
+    /// The target could not take the application, it stays at the source.
+    Failed,
+}
+
+/// An application moved, or is moving, from one edge data center to another.
+/// Migration events are not about a UE, their user id is 0.
+/// Sizes are given in MB and times in milliseconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApplicationMigrationInformation {
+    pub migration_id: u32,
+    pub application_id: u32,
+    pub source_edge_data_center_id: u32,
+    pub target_edge_data_center_id: u32,
+    pub status: MigrationStatus,
+    pub state_size: u64,
+    pub transfer_time: u64,
+    pub downtime: u64,
+}
+
+impl ApplicationMigrationInformation {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        migration_id: u32,
+        application_id: u32,
+        source_edge_data_center_id: u32,
+        target_edge_data_center_id: u32,
+        status: MigrationStatus,
+        state_size: u64,
+        transfer_time: u64,
+        downtime: u64,
+    ) -> Self {
+        Self {
+            migration_id,
+            application_id,
+            source_edge_data_center_id,
+            target_edge_data_center_id,
+            status,
+            state_size,
+            transfer_time,
+            downtime,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
 pub struct MobileNetworkCoreEvent {
     event: Event,
     kind: EventKind,
     timestamp: Duration,
     user_id: u32,
+    /// Position of the event in the order the core produced events, starting at 1.
+    /// 0 means the event has not been stored yet.
+    #[serde(default)]
+    sequence_number: u64,
 }
 
 impl MobileNetworkCoreEvent {
@@ -279,9 +418,18 @@ impl MobileNetworkCoreEvent {
             kind,
             timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap(),
             user_id,
+            sequence_number: 0,
         }
     }
 
+    pub fn set_sequence_number(&mut self, sequence_number: u64) {
+        self.sequence_number = sequence_number;
+    }
+
+    pub fn get_sequence_number(&self) -> u64 {
+        self.sequence_number
+    }
+
     pub fn get_event(&self) -> &Event {
         &self.event
     }
@@ -0,0 +1,147 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Header carrying the unix time in seconds at which a notification was signed.
+pub const TIMESTAMP_HEADER: &str = "X-Notification-Timestamp";
+/// Header carrying `sha256=` followed by the hex encoded HMAC of `{timestamp}.{body}`.
+pub const SIGNATURE_HEADER: &str = "X-Notification-Signature";
+/// How far a notification's timestamp may be from the receiver's clock by default.
+pub const DEFAULT_TOLERANCE: Duration = Duration::from_secs(300);
+
+#[derive(Debug)]
+pub struct SignatureError {
+    message: String,
+}
+
+impl SignatureError {
+    fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for SignatureError {}
+
+fn mac(secret: &str, timestamp: u64, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// The value of the signature header for `body` sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        hex::encode(mac(secret, timestamp, body).finalize().into_bytes())
+    )
+}
+
+/// Checks the timestamp and signature headers of a received notification.
+/// Notifications signed more than `tolerance` away from now are rejected so they cannot be replayed later.
+pub fn verify(
+    secret: &str,
+    timestamp: &str,
+    signature: &str,
+    body: &[u8],
+    tolerance: Duration,
+) -> Result<(), SignatureError> {
+    let timestamp: u64 = timestamp
+        .trim()
+        .parse()
+        .map_err(|_| SignatureError::new("timestamp is not a unix time"))?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    if now.abs_diff(Duration::from_secs(timestamp)) > tolerance {
+        return Err(SignatureError::new("timestamp is outside the tolerance"));
+    }
+    let signature = signature
+        .trim()
+        .strip_prefix("sha256=")
+        .and_then(|signature| hex::decode(signature).ok())
+        .ok_or_else(|| SignatureError::new("signature is not a sha256 HMAC"))?;
+    mac(secret, timestamp, body)
+        .verify_slice(&signature)
+        .map_err(|_| SignatureError::new("signature does not match"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let body = br#"{"id":1}"#;
+        let timestamp = now();
+        let signature = sign("secret", timestamp, body);
+
+        assert!(verify(
+            "secret",
+            &timestamp.to_string(),
+            &signature,
+            body,
+            DEFAULT_TOLERANCE
+        )
+        .is_ok());
+        assert!(verify(
+            "other",
+            &timestamp.to_string(),
+            &signature,
+            body,
+            DEFAULT_TOLERANCE
+        )
+        .is_err());
+        assert!(verify(
+            "secret",
+            &timestamp.to_string(),
+            &signature,
+            br#"{"id":2}"#,
+            DEFAULT_TOLERANCE
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn reject_replay() {
+        let body = b"[]";
+        let timestamp = now() - 600;
+        let signature = sign("secret", timestamp, body);
+
+        assert!(verify(
+            "secret",
+            &timestamp.to_string(),
+            &signature,
+            body,
+            DEFAULT_TOLERANCE
+        )
+        .is_err());
+        assert!(verify(
+            "secret",
+            &timestamp.to_string(),
+            &signature,
+            body,
+            Duration::from_secs(900)
+        )
+        .is_ok());
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use geo::{EuclideanDistance, Point};
+use mobile_network_core_event::{Event, GeographicArea, MobileNetworkCoreEvent};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::northbound::resolve_user_id;
+
+/// Number of trajectory points reported per UE, the most recent ones are kept.
+const MAX_TRAJECTORY_LENGTH: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AnalyticsEvent {
+    UeMobility,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetUeId {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    any_ue_ind: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gpsi: Option<String>,
+}
+
+impl TargetUeId {
+    fn is_any_ue(&self) -> bool {
+        self.any_ue_ind == Some(true)
+    }
+
+    fn user_id(&self) -> Option<u32> {
+        self.gpsi.as_deref().and_then(resolve_user_id)
+    }
+
+    fn targets(&self, user_id: u32) -> bool {
+        self.is_any_ue() || self.user_id() == Some(user_id)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.is_any_ue() || self.user_id().is_some() {
+            Ok(())
+        } else {
+            Err("tgtUe must contain anyUeInd or a gpsi identifying a UE".to_string())
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsEventSubs {
+    analy_event: AnalyticsEvent,
+    #[serde(default)]
+    tgt_ue: TargetUeId,
+}
+
+/// The AnalyticsExposureSubsc resource of TS 29.522.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsExposureSubscription {
+    #[serde(rename = "self", skip_serializing_if = "Option::is_none")]
+    self_link: Option<String>,
+    analy_events_subs: Vec<AnalyticsEventSubs>,
+    notif_uri: String,
+    notif_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supp_feat: Option<String>,
+}
+
+impl AnalyticsExposureSubscription {
+    pub fn validate(&self) -> Result<(), String> {
+        if Url::parse(&self.notif_uri).is_err() {
+            return Err(format!("notifUri {} is not a valid URL", self.notif_uri));
+        }
+        if self.analy_events_subs.is_empty() {
+            return Err("analyEventsSubs must not be empty".to_string());
+        }
+        self.analy_events_subs
+            .iter()
+            .try_for_each(|analy_event_subs| analy_event_subs.tgt_ue.validate())
+    }
+
+    pub fn set_self_link(&mut self, self_link: String) {
+        self.self_link = Some(self_link);
+    }
+
+    pub fn get_notif_uri(&self) -> Url {
+        Url::parse(&self.notif_uri).unwrap()
+    }
+
+    /// Whether `event` is a location report of a UE the subscription targets.
+    pub fn matches(&self, event: &MobileNetworkCoreEvent) -> bool {
+        matches!(event.get_event(), Event::LocationReporting(_))
+            && self
+                .analy_events_subs
+                .iter()
+                .any(|analy_event_subs| analy_event_subs.tgt_ue.targets(event.get_user_id()))
+    }
+
+    /// Computes the subscribed analytics from `events`, `None` if there is nothing to report.
+    pub fn notification(&self, events: &[MobileNetworkCoreEvent]) -> Option<AnalyticsEventNotif> {
+        let analy_event_notifs: Vec<AnalyticsEventNotification> = self
+            .analy_events_subs
+            .iter()
+            .map(|analy_event_subs| analytics(&analy_event_subs.tgt_ue, events))
+            .filter(|notification| !notification.ue_mobility_infos.is_empty())
+            .collect();
+        if analy_event_notifs.is_empty() {
+            return None;
+        }
+        Some(AnalyticsEventNotif {
+            notif_id: self.notif_id.clone(),
+            analy_event_notifs,
+        })
+    }
+}
+
+/// On-demand analytics request, the body of the `fetch` operation of TS 29.522.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsRequest {
+    analy_event: AnalyticsEvent,
+    #[serde(default)]
+    tgt_ue: TargetUeId,
+}
+
+impl AnalyticsRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        self.tgt_ue.validate()
+    }
+
+    pub fn analytics(&self, events: &[MobileNetworkCoreEvent]) -> AnalyticsEventNotification {
+        analytics(&self.tgt_ue, events)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VisitedCell {
+    cell_id: String,
+    visits: u32,
+    /// Total time spent in the cell, in seconds.
+    dwell_time: f64,
+    /// Share of the observed time spent in the cell.
+    ratio: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrajectoryPoint {
+    ts: DateTime<Utc>,
+    cell_id: String,
+    x: f64,
+    y: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrajectoryStatistics {
+    /// Observed time span, in seconds.
+    duration: f64,
+    distance: f64,
+    average_speed: f64,
+    max_speed: f64,
+    cell_changes: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UeMobilityInfo {
+    gpsi: String,
+    visited_cells: Vec<VisitedCell>,
+    ue_trajs: Vec<TrajectoryPoint>,
+    trajectory_statistics: TrajectoryStatistics,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsEventNotification {
+    analy_event: AnalyticsEvent,
+    time_stamp: DateTime<Utc>,
+    ue_mobility_infos: Vec<UeMobilityInfo>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsEventNotif {
+    notif_id: String,
+    analy_event_notifs: Vec<AnalyticsEventNotification>,
+}
+
+struct LocationSample {
+    timestamp: f64,
+    cell_id: String,
+    position: Point,
+}
+
+fn analytics(target: &TargetUeId, events: &[MobileNetworkCoreEvent]) -> AnalyticsEventNotification {
+    let mut samples: BTreeMap<u32, Vec<LocationSample>> = BTreeMap::new();
+    for event in events {
+        if !target.targets(event.get_user_id()) {
+            continue;
+        }
+        if let Event::LocationReporting(location_info) = event.get_event() {
+            if let GeographicArea::Point(position) = location_info.geographic_area {
+                samples
+                    .entry(event.get_user_id())
+                    .or_default()
+                    .push(LocationSample {
+                        timestamp: event.get_timestamp().as_secs_f64(),
+                        cell_id: location_info.e_node_b_id.clone(),
+                        position,
+                    });
+            }
+        }
+    }
+
+    AnalyticsEventNotification {
+        analy_event: AnalyticsEvent::UeMobility,
+        time_stamp: Utc::now(),
+        ue_mobility_infos: samples
+            .into_iter()
+            .map(|(user_id, samples)| ue_mobility(user_id, samples))
+            .collect(),
+    }
+}
+
+/// Mobility analytics for a single UE based on its location samples.
+fn ue_mobility(user_id: u32, mut samples: Vec<LocationSample>) -> UeMobilityInfo {
+    samples.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+
+    let mut visited_cells: Vec<VisitedCell> = Vec::new();
+    let mut distance = 0.0;
+    let mut max_speed: f64 = 0.0;
+    let mut cell_changes = 0;
+    for (i, sample) in samples.iter().enumerate() {
+        let previous = if i > 0 { samples.get(i - 1) } else { None };
+        let new_visit = match previous {
+            Some(previous) => previous.cell_id != sample.cell_id,
+            None => true,
+        };
+        if new_visit && previous.is_some() {
+            cell_changes += 1;
+        }
+        let dwell_time = match samples.get(i + 1) {
+            Some(next) => next.timestamp - sample.timestamp,
+            None => 0.0,
+        };
+        match visited_cells
+            .iter_mut()
+            .find(|cell| cell.cell_id == sample.cell_id)
+        {
+            Some(cell) => {
+                cell.dwell_time += dwell_time;
+                if new_visit {
+                    cell.visits += 1;
+                }
+            }
+            None => visited_cells.push(VisitedCell {
+                cell_id: sample.cell_id.clone(),
+                visits: 1,
+                dwell_time,
+                ratio: 0.0,
+            }),
+        }
+        if let Some(previous) = previous {
+            let step = previous.position.euclidean_distance(&sample.position);
+            distance += step;
+            let elapsed = sample.timestamp - previous.timestamp;
+            if elapsed > 0.0 {
+                max_speed = max_speed.max(step / elapsed);
+            }
+        }
+    }
+
+    let duration = match (samples.first(), samples.last()) {
+        (Some(first), Some(last)) => last.timestamp - first.timestamp,
+        _ => 0.0,
+    };
+    for cell in visited_cells.iter_mut() {
+        cell.ratio = if duration > 0.0 {
+            cell.dwell_time / duration
+        } else {
+            1.0 / samples.len() as f64
+        };
+    }
+    visited_cells.sort_by(|a, b| b.dwell_time.total_cmp(&a.dwell_time));
+
+    let ue_trajs = samples
+        .iter()
+        .skip(samples.len().saturating_sub(MAX_TRAJECTORY_LENGTH))
+        .map(|sample| TrajectoryPoint {
+            ts: DateTime::from_timestamp(
+                sample.timestamp.trunc() as i64,
+                (sample.timestamp.fract() * 1e9) as u32,
+            )
+            .unwrap_or_default(),
+            cell_id: sample.cell_id.clone(),
+            x: sample.position.x(),
+            y: sample.position.y(),
+        })
+        .collect();
+
+    UeMobilityInfo {
+        gpsi: format!("msisdn-{}", user_id),
+        visited_cells,
+        ue_trajs,
+        trajectory_statistics: TrajectoryStatistics {
+            duration,
+            distance,
+            average_speed: if duration > 0.0 {
+                distance / duration
+            } else {
+                0.0
+            },
+            max_speed,
+            cell_changes,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mobile_network_core_event::{
+        AccuracyFulfillmentIndicator, CivicAddress, EventKind, LdrType, LocationInfo,
+        MinorLocationQoS, PositioningMethod,
+    };
+
+    use super::*;
+
+    fn sample(timestamp: f64, cell_id: &str, x: f64) -> LocationSample {
+        LocationSample {
+            timestamp,
+            cell_id: cell_id.to_string(),
+            position: Point::new(x, 0.0),
+        }
+    }
+
+    #[test]
+    fn ue_mobility_dwell_times_and_statistics() {
+        let samples = vec![
+            sample(10.0, "1", 0.0),
+            sample(0.0, "0", 0.0),
+            sample(5.0, "0", 5.0),
+            sample(20.0, "0", 10.0),
+        ];
+
+        let info = ue_mobility(7, samples);
+
+        assert_eq!(info.gpsi, "msisdn-7");
+        assert_eq!(
+            info.visited_cells,
+            vec![
+                VisitedCell {
+                    cell_id: "0".to_string(),
+                    visits: 2,
+                    dwell_time: 10.0,
+                    ratio: 0.5
+                },
+                VisitedCell {
+                    cell_id: "1".to_string(),
+                    visits: 1,
+                    dwell_time: 10.0,
+                    ratio: 0.5
+                },
+            ]
+        );
+        let statistics = info.trajectory_statistics;
+        assert_eq!(statistics.duration, 20.0);
+        assert_eq!(statistics.distance, 20.0);
+        assert_eq!(statistics.average_speed, 1.0);
+        assert_eq!(statistics.max_speed, 1.0);
+        assert_eq!(statistics.cell_changes, 2);
+        assert_eq!(info.ue_trajs.len(), 4);
+        assert_eq!(info.ue_trajs[0].ts, DateTime::from_timestamp(0, 0).unwrap());
+    }
+
+    #[test]
+    fn subscription_targets_single_ue() {
+        let subscription: AnalyticsExposureSubscription =
+            serde_json::from_value(serde_json::json!({
+                "analyEventsSubs": [{
+                    "analyEvent": "UE_MOBILITY",
+                    "tgtUe": { "gpsi": "msisdn-1" },
+                    "suppFeat": "*"
+                }],
+                "notifUri": "http://localhost:8789/",
+                "notifId": "test"
+            }))
+            .unwrap();
+        assert!(subscription.validate().is_ok());
+        assert!(!subscription.matches(&location_event(0)));
+        assert!(subscription.matches(&location_event(1)));
+
+        let events = vec![location_event(0), location_event(1), location_event(1)];
+        let notification = subscription.notification(&events).unwrap();
+
+        assert_eq!(notification.notif_id, "test");
+        let infos = &notification.analy_event_notifs[0].ue_mobility_infos;
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].gpsi, "msisdn-1");
+        assert_eq!(infos[0].ue_trajs.len(), 2);
+
+        assert!(subscription.notification(&events[..1]).is_none());
+    }
+
+    fn location_event(user_id: u32) -> MobileNetworkCoreEvent {
+        MobileNetworkCoreEvent::new(
+            Event::LocationReporting(LocationInfo::new(
+                0,
+                "0".to_string(),
+                GeographicArea::Point(Point::new(0.0, 0.0)),
+                CivicAddress {},
+                vec![PositioningMethod::CellId],
+                AccuracyFulfillmentIndicator::RequestedAccuracyFulfilled,
+                1.0,
+                LdrType::Motion,
+                MinorLocationQoS::new(1.0, 1.0),
+            )),
+            EventKind::LocationReporting,
+            user_id,
+        )
+    }
+}
@@ -0,0 +1,206 @@
+use actix_web::{
+    delete, get, post, put,
+    web::{Data, Json, Path},
+    HttpRequest, HttpResponse,
+};
+
+use mongodb::Database;
+
+use crate::{
+    analytics_exposure::{AnalyticsExposureSubscription, AnalyticsRequest},
+    mobile_network_exposure::Subscription,
+    mobile_network_exposure_endpoints::MobileNetworkExposureWrapper,
+    northbound::{request_url, resource_url, ProblemDetails},
+};
+
+fn analytics_exposure_subscription<'a>(
+    subscription: &'a Subscription,
+    af_id: &str,
+) -> Option<&'a AnalyticsExposureSubscription> {
+    match subscription {
+        Subscription::AnalyticsExposure {
+            af_id: owner,
+            subscription,
+        } if owner == af_id => Some(subscription),
+        _ => None,
+    }
+}
+
+#[get("/3gpp-analyticsexposure/v1/{af_id}/subscriptions")]
+pub async fn get_analytics_exposure_subscriptions(
+    af_id: Path<String>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+) -> HttpResponse {
+    let subscriptions: Vec<AnalyticsExposureSubscription> = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .read()
+        .await
+        .get_subscribers()
+        .into_iter()
+        .filter_map(|subscriber| {
+            analytics_exposure_subscription(subscriber.get_subscription(), &af_id)
+        })
+        .cloned()
+        .collect();
+    HttpResponse::Ok().json(subscriptions)
+}
+
+#[post("/3gpp-analyticsexposure/v1/{af_id}/subscriptions")]
+pub async fn post_analytics_exposure_subscription(
+    request: HttpRequest,
+    af_id: Path<String>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    database: Data<Database>,
+    subscription: Json<AnalyticsExposureSubscription>,
+) -> HttpResponse {
+    let mut subscription = subscription.into_inner();
+    if let Err(err) = subscription.validate() {
+        return ProblemDetails::bad_request(&err);
+    }
+
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    let id = mobile_network_exposure.add_subscription(Subscription::AnalyticsExposure {
+        af_id: af_id.clone(),
+        subscription: subscription.clone(),
+    });
+    let location = resource_url(&request, id);
+    subscription.set_self_link(location.clone());
+    mobile_network_exposure
+        .update_subscription(
+            id,
+            Subscription::AnalyticsExposure {
+                af_id: af_id.into_inner(),
+                subscription: subscription.clone(),
+            },
+        )
+        .unwrap();
+
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::Created()
+        .insert_header(("Location", location))
+        .json(subscription)
+}
+
+#[get("/3gpp-analyticsexposure/v1/{af_id}/subscriptions/{subscription_id}")]
+pub async fn get_analytics_exposure_subscription(
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+) -> HttpResponse {
+    let (af_id, subscription_id) = path.into_inner();
+    match mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .read()
+        .await
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            analytics_exposure_subscription(subscriber.get_subscription(), &af_id)
+        }) {
+        Some(subscription) => HttpResponse::Ok().json(subscription),
+        None => ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        )),
+    }
+}
+
+#[put("/3gpp-analyticsexposure/v1/{af_id}/subscriptions/{subscription_id}")]
+pub async fn put_analytics_exposure_subscription(
+    request: HttpRequest,
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    database: Data<Database>,
+    subscription: Json<AnalyticsExposureSubscription>,
+) -> HttpResponse {
+    let (af_id, subscription_id) = path.into_inner();
+    let mut subscription = subscription.into_inner();
+    if let Err(err) = subscription.validate() {
+        return ProblemDetails::bad_request(&err);
+    }
+
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    if mobile_network_exposure
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            analytics_exposure_subscription(subscriber.get_subscription(), &af_id)
+        })
+        .is_none()
+    {
+        return ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        ));
+    }
+    subscription.set_self_link(request_url(&request));
+    mobile_network_exposure
+        .update_subscription(
+            subscription_id,
+            Subscription::AnalyticsExposure {
+                af_id,
+                subscription: subscription.clone(),
+            },
+        )
+        .unwrap();
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::Ok().json(subscription)
+}
+
+#[delete("/3gpp-analyticsexposure/v1/{af_id}/subscriptions/{subscription_id}")]
+pub async fn delete_analytics_exposure_subscription(
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    database: Data<Database>,
+) -> HttpResponse {
+    let (af_id, subscription_id) = path.into_inner();
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    if mobile_network_exposure
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            analytics_exposure_subscription(subscriber.get_subscription(), &af_id)
+        })
+        .is_none()
+    {
+        return ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        ));
+    }
+    mobile_network_exposure
+        .remove_subscription(subscription_id)
+        .unwrap();
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::NoContent().finish()
+}
+
+/// Computes the requested analytics right away instead of subscribing to them.
+#[post("/3gpp-analyticsexposure/v1/{af_id}/fetch")]
+pub async fn fetch_analytics(
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    database: Data<Database>,
+    analytics_request: Json<AnalyticsRequest>,
+) -> HttpResponse {
+    if let Err(err) = analytics_request.validate() {
+        return ProblemDetails::bad_request(&err);
+    }
+    let events = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .read()
+        .await
+        .get_analytics_history(&database)
+        .await;
+    HttpResponse::Ok().json(analytics_request.analytics(&events))
+}
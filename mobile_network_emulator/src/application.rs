@@ -1,54 +1,119 @@
 use std::net::IpAddr;
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::vec;
-use std::{collections::HashMap, time::Duration};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
 
+use crate::resources::Resources;
+use crate::usage::{Usage, UsageHistory};
+
+/// What an application needs when no requirements are given for it.
+pub const DEFAULT_REQUIREMENTS: Resources = Resources::new(1.0, 512);
+
+/// Where a deployed application is in its lifecycle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeploymentState {
+    /// Deployed, waiting for the edge data center to start it.
+    #[default]
+    Pending,
+    /// Cold starting, requests wait until it is running.
+    Starting,
+    Running,
+    /// Takes no new requests and its resources are free for a replacement.
+    /// Requests are served within the tick they arrive, so it terminates at the next update.
+    Draining,
+    /// Stopped, it is removed at the next update.
+    Terminated,
+}
+
+impl DeploymentState {
+    /// Whether the application takes requests or is on its way to, i.e. neither draining nor terminated.
+    pub fn is_active(self) -> bool {
+        !matches!(
+            self,
+            DeploymentState::Draining | DeploymentState::Terminated
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Application {
     id: u32,
-    accesses: HashMap<IpAddr, Vec<Duration>>,
+    #[serde(default)]
+    requirements: Resources,
+    #[serde(default)]
+    state: DeploymentState,
+    /// When a starting application is running.
+    #[serde(skip)]
+    ready_at: Option<Instant>,
+    /// Serialized as counters, `GET .../usage` returns the uses themselves.
+    #[serde(skip_deserializing)]
+    usage: UsageHistory,
 }
 
 impl Application {
-    pub fn new(id: u32) -> Self {
+    pub fn new(id: u32, requirements: Resources) -> Self {
         Application {
             id,
-            accesses: HashMap::new(),
+            requirements,
+            state: DeploymentState::default(),
+            ready_at: None,
+            usage: UsageHistory::default(),
         }
     }
 
+    pub fn get_state(&self) -> DeploymentState {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: DeploymentState) {
+        self.state = state;
+    }
+
+    /// Starts the application, it is running from `ready_at`.
+    pub fn start(&mut self, ready_at: Instant) {
+        self.state = DeploymentState::Starting;
+        self.ready_at = Some(ready_at);
+    }
+
+    pub fn get_ready_at(&self) -> Option<Instant> {
+        self.ready_at
+    }
+
     pub fn id(&self) -> u32 {
         self.id
     }
 
-    pub fn add_use(&mut self, ip_addr: IpAddr) {
-        match self.accesses.get_mut(&ip_addr) {
-            Some(durations) => {
-                durations.push(SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
-            }
-            None => {
-                let durations = vec![SystemTime::now().duration_since(UNIX_EPOCH).unwrap()];
-                self.accesses.insert(ip_addr, durations);
-            }
-        };
+    pub fn get_requirements(&self) -> &Resources {
+        &self.requirements
     }
 
-    pub fn get_use(&self, ip_addr: &IpAddr) -> Vec<Duration> {
-        match self.accesses.get(ip_addr) {
-            Some(durations) => durations.clone(),
-            None => Vec::new(),
-        }
+    /// Records a use by `ip_addr` connected to the RAN `ran_id`.
+    pub fn add_use(&mut self, ip_addr: IpAddr, ran_id: u32) {
+        self.usage.add(
+            ip_addr,
+            ran_id,
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap(),
+        );
+    }
+
+    /// Drops the uses that fell out of the retention window.
+    pub fn expire_uses(&mut self) {
+        self.usage
+            .expire(SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
+    }
+
+    /// The uses by `ip_addr` within the retention window.
+    pub fn get_use(&self, ip_addr: &IpAddr) -> u32 {
+        self.usage.get_uses_by(ip_addr)
+    }
+
+    /// The uses still held from `since`, since the UNIX epoch, on.
+    pub fn get_usage_since(&self, since: Duration) -> Usage {
+        self.usage.since(since)
     }
 
     pub fn get_total_usage(&self) -> u32 {
-        self.accesses
-            .values()
-            .map(|durations| durations.len())
-            .sum::<usize>()
-            .try_into()
-            .unwrap()
+        self.usage.get_total()
     }
 }
 
@@ -60,7 +125,7 @@ mod tests {
 
     #[test]
     fn new() {
-        let application = Application::new(0);
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
         let id = application.id();
 
         assert_eq!(id, 0);
@@ -68,26 +133,26 @@ mod tests {
 
     #[test]
     fn add_get_use() {
-        let mut application = Application::new(0);
+        let mut application = Application::new(0, DEFAULT_REQUIREMENTS);
         let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
-        application.add_use(ip_addr);
+        application.add_use(ip_addr, 0);
         let use_count = application.get_use(&ip_addr);
 
-        assert_eq!(use_count.len(), 1);
+        assert_eq!(use_count, 1);
 
-        application.add_use(ip_addr);
+        application.add_use(ip_addr, 0);
 
         let use_count = application.get_use(&ip_addr);
 
-        assert_eq!(use_count.len(), 2);
+        assert_eq!(use_count, 2);
     }
 
     #[test]
     fn get_total_usage() {
-        let mut application = Application::new(0);
+        let mut application = Application::new(0, DEFAULT_REQUIREMENTS);
         let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
-        application.add_use(ip_addr);
-        application.add_use(ip_addr);
+        application.add_use(ip_addr, 0);
+        application.add_use(ip_addr, 1);
 
         let total_usage = application.get_total_usage();
 
@@ -0,0 +1,253 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    application::DEFAULT_REQUIREMENTS, migration::DEFAULT_STATE_SIZE_MB, resources::Resources,
+};
+
+/// Size of a request in kB when the profile does not give one.
+pub const DEFAULT_REQUEST_SIZE_KB: u64 = 16;
+
+/// Size of a response in kB when the profile does not give one.
+pub const DEFAULT_RESPONSE_SIZE_KB: u64 = 64;
+
+/// Mean time in milliseconds a server takes to process a request when the profile does not give one.
+pub const DEFAULT_COMPUTE_PER_REQUEST_MS: f64 = 20.0;
+
+#[derive(Debug)]
+pub struct ApplicationCatalogueError {
+    message: String,
+}
+
+impl ApplicationCatalogueError {
+    pub fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl Display for ApplicationCatalogueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ApplicationCatalogueError {}
+
+/// What an application is and what serving it takes, the body of `PUT /network/applications/{id}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApplicationProfile {
+    /// Given by the path when the profile is put in the catalogue.
+    #[serde(default)]
+    id: u32,
+    name: String,
+    /// The container image the application is deployed from.
+    image: String,
+    /// What a deployment of the application takes from an edge data center.
+    #[serde(default = "default_requirements")]
+    requirements: Resources,
+    /// In kB.
+    #[serde(default = "default_request_size")]
+    request_size: u64,
+    /// In kB.
+    #[serde(default = "default_response_size")]
+    response_size: u64,
+    /// Mean time in milliseconds a server takes to process a request.
+    #[serde(default = "default_compute_per_request")]
+    compute_per_request: f64,
+    /// In milliseconds, the delay requests to the application should stay within.
+    #[serde(default)]
+    latency_slo: Option<u64>,
+    /// In MB, the state carried over when the application is migrated.
+    #[serde(default = "default_state_size")]
+    state_size: u64,
+}
+
+fn default_requirements() -> Resources {
+    DEFAULT_REQUIREMENTS
+}
+
+fn default_request_size() -> u64 {
+    DEFAULT_REQUEST_SIZE_KB
+}
+
+fn default_response_size() -> u64 {
+    DEFAULT_RESPONSE_SIZE_KB
+}
+
+fn default_compute_per_request() -> f64 {
+    DEFAULT_COMPUTE_PER_REQUEST_MS
+}
+
+fn default_state_size() -> u64 {
+    DEFAULT_STATE_SIZE_MB
+}
+
+impl ApplicationProfile {
+    /// A profile with the default characteristics.
+    pub fn new(id: u32, name: &str, image: &str) -> Self {
+        ApplicationProfile {
+            id,
+            name: name.to_string(),
+            image: image.to_string(),
+            requirements: DEFAULT_REQUIREMENTS,
+            request_size: DEFAULT_REQUEST_SIZE_KB,
+            response_size: DEFAULT_RESPONSE_SIZE_KB,
+            compute_per_request: DEFAULT_COMPUTE_PER_REQUEST_MS,
+            latency_slo: None,
+            state_size: DEFAULT_STATE_SIZE_MB,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn get_requirements(&self) -> &Resources {
+        &self.requirements
+    }
+
+    /// The kB sent over the network to serve a request, the request and its response.
+    pub fn get_payload_size(&self) -> u64 {
+        self.request_size + self.response_size
+    }
+
+    pub fn get_compute_per_request(&self) -> Duration {
+        Duration::from_secs_f64(self.compute_per_request / 1000.0)
+    }
+
+    pub fn get_latency_slo(&self) -> Option<Duration> {
+        self.latency_slo.map(Duration::from_millis)
+    }
+
+    pub fn get_state_size(&self) -> u64 {
+        self.state_size
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        if !(self.compute_per_request.is_finite() && self.compute_per_request >= 0.0) {
+            return Err(format!(
+                "compute_per_request {} must be a non-negative number",
+                self.compute_per_request
+            ));
+        }
+        self.requirements.validate()
+    }
+}
+
+/// The applications that can be deployed, with what serving each of them takes.
+#[derive(Debug, Clone, Default)]
+pub struct ApplicationCatalogue {
+    profiles: Vec<ApplicationProfile>,
+}
+
+impl ApplicationCatalogue {
+    pub fn new(profiles: Vec<ApplicationProfile>) -> Self {
+        let mut catalogue = ApplicationCatalogue::default();
+        for profile in profiles {
+            catalogue.put(profile.id, profile);
+        }
+        catalogue
+    }
+
+    /// Adds the profile of application `id`, replacing the one it had.
+    /// Returns whether the application is new to the catalogue.
+    pub fn put(&mut self, id: u32, mut profile: ApplicationProfile) -> bool {
+        profile.id = id;
+        match self.profiles.iter_mut().find(|profile| profile.id == id) {
+            Some(existing) => {
+                *existing = profile;
+                false
+            }
+            None => {
+                self.profiles.push(profile);
+                self.profiles.sort_by_key(|profile| profile.id);
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<ApplicationProfile, ApplicationCatalogueError> {
+        match self.profiles.iter().position(|profile| profile.id == id) {
+            Some(index) => Ok(self.profiles.remove(index)),
+            None => Err(ApplicationCatalogueError::new(format!(
+                "Application with id {} is not in the catalogue",
+                id
+            ))),
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&ApplicationProfile> {
+        self.profiles.iter().find(|profile| profile.id == id)
+    }
+
+    pub fn get_profiles(&self) -> &[ApplicationProfile] {
+        &self.profiles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_with_defaults() {
+        let profile: ApplicationProfile = serde_json::from_value(serde_json::json!({
+            "name": "video-analytics",
+            "image": "registry.local/video-analytics:1.2",
+            "compute_per_request": 45.0,
+            "latency_slo": 100
+        }))
+        .unwrap();
+
+        assert_eq!(profile.get_requirements(), &DEFAULT_REQUIREMENTS);
+        assert_eq!(
+            profile.get_payload_size(),
+            DEFAULT_REQUEST_SIZE_KB + DEFAULT_RESPONSE_SIZE_KB
+        );
+        assert_eq!(profile.get_compute_per_request(), Duration::from_millis(45));
+        assert_eq!(profile.get_latency_slo(), Some(Duration::from_millis(100)));
+        assert_eq!(profile.get_state_size(), DEFAULT_STATE_SIZE_MB);
+        assert!(profile.validate().is_ok());
+    }
+
+    #[test]
+    fn validate() {
+        assert!(ApplicationProfile::new(0, "", "image").validate().is_err());
+        let mut profile = ApplicationProfile::new(0, "application", "image");
+        profile.compute_per_request = -1.0;
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn put_replaces_profile() {
+        let mut catalogue = ApplicationCatalogue::new(vec![
+            ApplicationProfile::new(2, "second", "image"),
+            ApplicationProfile::new(0, "first", "image"),
+        ]);
+
+        assert!(!catalogue.put(2, ApplicationProfile::new(7, "replaced", "image")));
+        assert!(catalogue.put(1, ApplicationProfile::new(1, "new", "image")));
+
+        let ids: Vec<u32> = catalogue.get_profiles().iter().map(|p| p.id()).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert_eq!(catalogue.get(2).unwrap().name, "replaced");
+    }
+
+    #[test]
+    fn remove() {
+        let mut catalogue =
+            ApplicationCatalogue::new(vec![ApplicationProfile::new(0, "application", "image")]);
+
+        assert!(catalogue.remove(0).is_ok());
+        assert!(catalogue.get(0).is_none());
+        assert!(catalogue.remove(0).is_err());
+    }
+}
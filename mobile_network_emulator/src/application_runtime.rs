@@ -2,9 +2,16 @@ use std::{
     error::Error,
     fmt::{Display, Formatter},
     net::IpAddr,
+    time::{Duration, Instant},
 };
 
-use crate::application::Application;
+use crate::{
+    application::{Application, DeploymentState},
+    resources::Resources,
+};
+
+/// How long an application takes to start when no cold start time is configured.
+pub const DEFAULT_COLD_START: Duration = Duration::from_secs(2);
 
 #[derive(Debug)]
 pub struct ApplicationRuntimeError {
@@ -25,18 +32,72 @@ impl Display for ApplicationRuntimeError {
 
 impl Error for ApplicationRuntimeError {}
 
+/// Runs the applications deployed to an edge data center.
+/// Applications go through their lifecycle when the runtime is updated, once per tick:
+/// pending applications start and are running after the cold start time,
+/// draining applications terminate and terminated applications are removed.
+/// Draining lasts exactly one tick, the requests of an application are served within the tick they arrive.
+/// An application may be deployed again while its draining instance is still around.
 #[derive(Debug, Clone)]
 pub struct ApplicationRuntime {
     applications: Vec<Application>,
+    capacity: Resources,
+    cold_start: Duration,
 }
 
 impl ApplicationRuntime {
-    pub fn new() -> Self {
+    pub fn new(capacity: Resources) -> Self {
         ApplicationRuntime {
             applications: Vec::new(),
+            capacity,
+            cold_start: DEFAULT_COLD_START,
+        }
+    }
+
+    pub fn set_cold_start(&mut self, cold_start: Duration) {
+        self.cold_start = cold_start;
+    }
+
+    /// Advances the lifecycle of the applications to `now`.
+    pub fn update(&mut self, now: Instant) {
+        self.applications
+            .retain(|application| application.get_state() != DeploymentState::Terminated);
+        for application in self.applications.iter_mut() {
+            application.expire_uses();
+            match application.get_state() {
+                DeploymentState::Pending => application.start(now + self.cold_start),
+                DeploymentState::Draining => application.set_state(DeploymentState::Terminated),
+                _ => (),
+            }
+            if application.get_state() == DeploymentState::Starting
+                && application
+                    .get_ready_at()
+                    .is_none_or(|ready_at| ready_at <= now)
+            {
+                application.set_state(DeploymentState::Running);
+            }
         }
     }
 
+    /// How long a request arriving at `now` waits for the application to run,
+    /// `None` if it is not deployed or on its way out.
+    pub fn get_readiness(&self, id: u32, now: Instant) -> Option<Duration> {
+        let application = self.get_application(id).ok()?;
+        match application.get_state() {
+            DeploymentState::Pending => Some(self.cold_start),
+            DeploymentState::Starting => Some(
+                application
+                    .get_ready_at()
+                    .map_or(Duration::ZERO, |ready_at| {
+                        ready_at.saturating_duration_since(now)
+                    }),
+            ),
+            DeploymentState::Running => Some(Duration::ZERO),
+            DeploymentState::Draining | DeploymentState::Terminated => None,
+        }
+    }
+
+    /// Deploys `application` if the resources it requires are still free.
     pub fn add_application(
         &mut self,
         application: Application,
@@ -46,34 +107,80 @@ impl ApplicationRuntime {
                 "Application already exists".to_string(),
             ));
         }
+        if !self.fits(application.get_requirements()) {
+            let free = self.capacity - self.get_used_resources();
+            return Err(ApplicationRuntimeError::new(format!(
+                "Application {} requires {} but only {} of {} are free",
+                application.id(),
+                application.get_requirements(),
+                free,
+                self.capacity
+            )));
+        }
         self.applications.push(application);
         Ok(())
     }
 
-    pub fn remove_application(
-        &mut self,
-        application: &Application,
-    ) -> Result<(), ApplicationRuntimeError> {
-        for (i, current_application) in self.applications.iter_mut().enumerate() {
-            if current_application.id() == application.id() {
-                self.applications.remove(i);
-                return Ok(());
+    pub fn get_capacity(&self) -> &Resources {
+        &self.capacity
+    }
+
+    /// The resources required by the active applications, draining ones no longer count.
+    pub fn get_used_resources(&self) -> Resources {
+        self.applications
+            .iter()
+            .filter(|application| application.get_state().is_active())
+            .fold(Resources::default(), |used, application| {
+                used + *application.get_requirements()
+            })
+    }
+
+    /// Stops the application from taking new requests, it terminates at the next update.
+    pub fn drain_application(&mut self, id: u32) -> Result<(), ApplicationRuntimeError> {
+        match self
+            .applications
+            .iter_mut()
+            .find(|application| application.id() == id && application.get_state().is_active())
+        {
+            Some(application) => {
+                application.set_state(DeploymentState::Draining);
+                Ok(())
             }
+            None => Err(ApplicationRuntimeError::new(
+                "Application does not exist".to_string(),
+            )),
         }
-        Err(ApplicationRuntimeError::new(
-            "Application does not exist".to_string(),
-        ))
     }
 
+    /// Stops the application and hands it over with its usage history.
+    pub fn take_application(&mut self, id: u32) -> Result<Application, ApplicationRuntimeError> {
+        match self.position(id) {
+            Some(index) => Ok(self.applications.remove(index)),
+            None => Err(ApplicationRuntimeError::new(
+                "Application does not exist".to_string(),
+            )),
+        }
+    }
+
+    /// Whether an application requiring `requirements` would fit next to the running ones.
+    pub fn fits(&self, requirements: &Resources) -> bool {
+        requirements.fits_in(&(self.capacity - self.get_used_resources()))
+    }
+
+    /// Records a use of `application` by `ip_addr` connected to the RAN `ran_id`,
+    /// returns the uses by `ip_addr` within the retention window.
     pub fn use_application(
         &mut self,
         ip_addr: IpAddr,
+        ran_id: u32,
         application: &Application,
-    ) -> Result<usize, ApplicationRuntimeError> {
+    ) -> Result<u32, ApplicationRuntimeError> {
         for current_application in self.applications.iter_mut() {
-            if current_application.id() == application.id() {
-                current_application.add_use(ip_addr);
-                return Ok(current_application.get_use(&ip_addr).len());
+            if current_application.id() == application.id()
+                && current_application.get_state().is_active()
+            {
+                current_application.add_use(ip_addr, ran_id);
+                return Ok(current_application.get_use(&ip_addr));
             }
         }
         Err(ApplicationRuntimeError::new(
@@ -81,96 +188,210 @@ impl ApplicationRuntime {
         ))
     }
 
+    /// Whether the application is deployed and neither draining nor terminated.
     pub fn contains_application(&self, id: &u32) -> bool {
         self.applications
             .iter()
-            .any(|application| application.id() == *id)
+            .any(|application| application.id() == *id && application.get_state().is_active())
     }
 
     pub fn get_applications(&self) -> Vec<&Application> {
         self.applications.iter().collect()
     }
 
+    #[allow(dead_code)]
     pub fn num_applications(&self) -> usize {
         self.applications.len()
     }
 
     pub fn get_application(&self, id: u32) -> Result<&Application, ApplicationRuntimeError> {
-        match self
-            .applications
-            .iter()
-            .find(|application| application.id() == id)
-        {
-            Some(application) => Ok(application),
+        match self.position(id) {
+            Some(index) => Ok(&self.applications[index]),
             None => Err(ApplicationRuntimeError::new(
                 "Application does not exist".to_string(),
             )),
         }
     }
+
+    /// Where the application is, its active instance first if it is also still draining.
+    fn position(&self, id: u32) -> Option<usize> {
+        self.applications
+            .iter()
+            .position(|application| application.id() == id && application.get_state().is_active())
+            .or_else(|| {
+                self.applications
+                    .iter()
+                    .position(|application| application.id() == id)
+            })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::net::Ipv4Addr;
 
+    use crate::application::DEFAULT_REQUIREMENTS;
+
     use super::*;
 
     #[test]
     fn add_application() {
-        let mut application_runtime = ApplicationRuntime::new();
-        let application = Application::new(0);
+        let mut application_runtime = ApplicationRuntime::new(Resources::new(4.0, 4096));
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
         application_runtime.add_application(application).unwrap();
         assert_eq!(application_runtime.applications.len(), 1);
     }
 
     #[test]
     fn add_same_application_two_times_should_fail() {
-        let mut application_runtime = ApplicationRuntime::new();
-        let application = Application::new(0);
+        let mut application_runtime = ApplicationRuntime::new(Resources::new(4.0, 4096));
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
         application_runtime.add_application(application).unwrap();
         assert_eq!(application_runtime.applications.len(), 1);
 
-        let application = Application::new(0);
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
         let res = application_runtime.add_application(application);
         assert!(res.is_err());
         assert_eq!(application_runtime.applications.len(), 1);
     }
 
     #[test]
-    fn remove_application() {
-        let mut application_runtime = ApplicationRuntime::new();
-        let application = Application::new(0);
+    fn add_application_beyond_capacity_should_fail() {
+        let mut application_runtime = ApplicationRuntime::new(Resources::new(4.0, 4096));
+        application_runtime
+            .add_application(Application::new(0, Resources::new(3.0, 1024)))
+            .unwrap();
+        let res =
+            application_runtime.add_application(Application::new(1, Resources::new(2.0, 1024)));
+        assert!(res.is_err());
+        let res =
+            application_runtime.add_application(Application::new(1, Resources::new(1.0, 4096)));
+        assert!(res.is_err());
+        application_runtime
+            .add_application(Application::new(1, Resources::new(1.0, 3072)))
+            .unwrap();
+        assert_eq!(
+            application_runtime.get_used_resources(),
+            Resources::new(4.0, 4096)
+        );
+    }
+
+    #[test]
+    fn drain_application() {
+        let mut application_runtime = ApplicationRuntime::new(Resources::new(4.0, 4096));
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
         application_runtime
             .add_application(application.clone())
             .unwrap();
         assert_eq!(application_runtime.applications.len(), 1);
 
-        let res = application_runtime.remove_application(&application);
+        let res = application_runtime.drain_application(0);
         assert!(res.is_ok());
+        assert!(application_runtime
+            .get_readiness(0, Instant::now())
+            .is_none());
+        assert!(application_runtime.drain_application(0).is_err());
+        assert!(!application_runtime.contains_application(&0));
+        assert_eq!(
+            application_runtime.get_used_resources(),
+            Resources::default()
+        );
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        assert!(application_runtime
+            .use_application(ip_addr, 0, &application)
+            .is_err());
+
+        application_runtime.update(Instant::now());
+        assert_eq!(
+            application_runtime.get_applications()[0].get_state(),
+            DeploymentState::Terminated
+        );
+        application_runtime.update(Instant::now());
         assert_eq!(application_runtime.applications.len(), 0);
+        assert!(application_runtime.drain_application(0).is_err());
     }
 
     #[test]
-    fn remove_application_two_times() {
-        let mut application_runtime = ApplicationRuntime::new();
-        let application = Application::new(0);
+    fn redeploy_draining_application() {
+        let mut application_runtime = ApplicationRuntime::new(Resources::new(1.0, 512));
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
         application_runtime
             .add_application(application.clone())
             .unwrap();
+        application_runtime.drain_application(0).unwrap();
+
+        application_runtime
+            .add_application(application.clone())
+            .unwrap();
+        assert!(application_runtime.contains_application(&0));
+        assert_eq!(
+            application_runtime.get_application(0).unwrap().get_state(),
+            DeploymentState::Pending
+        );
+
+        application_runtime.update(Instant::now());
+        application_runtime.update(Instant::now());
         assert_eq!(application_runtime.applications.len(), 1);
+        assert!(application_runtime.contains_application(&0));
+    }
 
+    #[test]
+    fn applications_cold_start() {
+        let mut application_runtime = ApplicationRuntime::new(Resources::new(4.0, 4096));
+        application_runtime.set_cold_start(Duration::from_secs(3));
         application_runtime
-            .remove_application(&application)
+            .add_application(Application::new(0, DEFAULT_REQUIREMENTS))
             .unwrap();
-        assert_eq!(application_runtime.applications.len(), 0);
-        let res = application_runtime.remove_application(&application);
-        assert!(res.is_err());
+        let now = Instant::now();
+        assert_eq!(
+            application_runtime.get_readiness(0, now),
+            Some(Duration::from_secs(3))
+        );
+
+        application_runtime.update(now);
+        assert_eq!(
+            application_runtime.get_applications()[0].get_state(),
+            DeploymentState::Starting
+        );
+        assert_eq!(
+            application_runtime.get_readiness(0, now + Duration::from_secs(1)),
+            Some(Duration::from_secs(2))
+        );
+
+        application_runtime.update(now + Duration::from_secs(3));
+        assert_eq!(
+            application_runtime.get_applications()[0].get_state(),
+            DeploymentState::Running
+        );
+        assert_eq!(
+            application_runtime.get_readiness(0, now + Duration::from_secs(3)),
+            Some(Duration::ZERO)
+        );
+        assert!(application_runtime.get_readiness(1, now).is_none());
+    }
+
+    #[test]
+    fn take_application_keeps_its_usage() {
+        let mut application_runtime = ApplicationRuntime::new(Resources::new(4.0, 4096));
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+        application_runtime
+            .add_application(application.clone())
+            .unwrap();
+        application_runtime
+            .use_application(ip_addr, 0, &application)
+            .unwrap();
+
+        let taken = application_runtime.take_application(0).unwrap();
+        assert_eq!(taken.get_total_usage(), 1);
+        assert_eq!(application_runtime.num_applications(), 0);
+        assert!(application_runtime.take_application(0).is_err());
     }
 
     #[test]
     fn use_application() {
-        let mut application_runtime = ApplicationRuntime::new();
-        let application = Application::new(0);
+        let mut application_runtime = ApplicationRuntime::new(Resources::new(4.0, 4096));
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
         application_runtime
             .add_application(application.clone())
             .unwrap();
@@ -180,27 +401,27 @@ mod tests {
         let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 
         application_runtime
-            .use_application(ip_addr, &application)
+            .use_application(ip_addr, 0, &application)
             .unwrap();
         let application_use = application_runtime.get_applications()[0].get_use(&ip_addr);
-        assert_eq!(application_use.len(), 1);
+        assert_eq!(application_use, 1);
     }
 
     #[test]
     fn use_application_when_application_does_not_exsist() {
-        let mut application_runtime = ApplicationRuntime::new();
-        let application = Application::new(0);
+        let mut application_runtime = ApplicationRuntime::new(Resources::new(4.0, 4096));
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
 
         let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 
-        let res = application_runtime.use_application(ip_addr, &application);
+        let res = application_runtime.use_application(ip_addr, 0, &application);
         assert!(res.is_err());
     }
 
     #[test]
     fn num_applications() {
-        let mut application_runtime = ApplicationRuntime::new();
-        let application = Application::new(0);
+        let mut application_runtime = ApplicationRuntime::new(Resources::new(4.0, 4096));
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
 
         assert_eq!(application_runtime.applications.len(), 0);
         assert_eq!(application_runtime.num_applications(), 0);
@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+
+use geo::{Contains, EuclideanDistance, LineString, Point, Polygon};
+use mobile_network_core_event::LdrType;
+use serde::{Deserialize, Serialize};
+
+/// An area in the coordinates of the emulated network.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Area {
+    Circle { center: Point, radius: f64 },
+    Polygon { points: Vec<Point> },
+}
+
+impl Area {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Area::Circle { radius, .. } if !(radius.is_finite() && *radius > 0.0) => {
+                Err(format!("radius {} must be a positive number", radius))
+            }
+            Area::Polygon { points } if points.len() < 3 => {
+                Err("a polygon needs at least 3 points".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn contains(&self, position: &Point) -> bool {
+        match self {
+            Area::Circle { center, radius } => center.euclidean_distance(position) <= *radius,
+            Area::Polygon { points } => {
+                Polygon::new(LineString::from(points.clone()), vec![]).contains(position)
+            }
+        }
+    }
+}
+
+/// An area the core watches for UEs entering, leaving and staying inside.
+#[derive(Clone, Debug)]
+pub struct AreaOfInterest {
+    id: u32,
+    area: Area,
+    inside: HashSet<u32>,
+}
+
+impl AreaOfInterest {
+    pub fn new(id: u32, area: Area) -> Self {
+        Self {
+            id,
+            area,
+            inside: HashSet::new(),
+        }
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    /// Moves the UEs in `users` to their new positions and returns what happened to every UE
+    /// inside the area now or before, together with the number of UEs inside now.
+    pub fn evaluate(&mut self, users: &[(u32, Point)]) -> (Vec<(u32, Point, LdrType)>, u32) {
+        let mut inside = HashSet::new();
+        let mut changes = Vec::new();
+        for (user_id, position) in users {
+            let was_inside = self.inside.contains(user_id);
+            if self.area.contains(position) {
+                inside.insert(*user_id);
+                let ldr_type = if was_inside {
+                    LdrType::BeingInsideArea
+                } else {
+                    LdrType::EnteringIntoArea
+                };
+                changes.push((*user_id, *position, ldr_type));
+            } else if was_inside {
+                changes.push((*user_id, *position, LdrType::LeavingFromArea));
+            }
+        }
+        self.inside = inside;
+        (changes, self.inside.len() as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn circle_and_polygon() {
+        let circle = Area::Circle {
+            center: Point::new(0.0, 0.0),
+            radius: 10.0,
+        };
+        assert!(circle.contains(&Point::new(6.0, 8.0)));
+        assert!(!circle.contains(&Point::new(8.0, 8.0)));
+
+        let polygon = Area::Polygon {
+            points: vec![
+                Point::new(0.0, 0.0),
+                Point::new(10.0, 0.0),
+                Point::new(10.0, 10.0),
+            ],
+        };
+        assert!(polygon.contains(&Point::new(8.0, 2.0)));
+        assert!(!polygon.contains(&Point::new(2.0, 8.0)));
+
+        assert!(Area::Polygon { points: vec![] }.validate().is_err());
+        assert!(Area::Circle {
+            center: Point::new(0.0, 0.0),
+            radius: -1.0
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn enter_stay_leave() {
+        let mut area_of_interest = AreaOfInterest::new(
+            0,
+            Area::Circle {
+                center: Point::new(0.0, 0.0),
+                radius: 10.0,
+            },
+        );
+        let ldr_types = |changes: Vec<(u32, Point, LdrType)>| -> Vec<(u32, LdrType)> {
+            changes
+                .into_iter()
+                .map(|(user_id, _, ldr_type)| (user_id, ldr_type))
+                .collect()
+        };
+
+        let (changes, number_of_ues) =
+            area_of_interest.evaluate(&[(1, Point::new(1.0, 1.0)), (2, Point::new(50.0, 0.0))]);
+        assert_eq!(ldr_types(changes), vec![(1, LdrType::EnteringIntoArea)]);
+        assert_eq!(number_of_ues, 1);
+
+        let (changes, number_of_ues) =
+            area_of_interest.evaluate(&[(1, Point::new(2.0, 1.0)), (2, Point::new(5.0, 0.0))]);
+        assert_eq!(
+            ldr_types(changes),
+            vec![
+                (1, LdrType::BeingInsideArea),
+                (2, LdrType::EnteringIntoArea)
+            ]
+        );
+        assert_eq!(number_of_ues, 2);
+
+        let (changes, number_of_ues) =
+            area_of_interest.evaluate(&[(1, Point::new(20.0, 1.0)), (2, Point::new(5.0, 0.0))]);
+        assert_eq!(
+            ldr_types(changes),
+            vec![(1, LdrType::LeavingFromArea), (2, LdrType::BeingInsideArea)]
+        );
+        assert_eq!(number_of_ues, 1);
+    }
+}
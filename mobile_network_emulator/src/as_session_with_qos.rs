@@ -0,0 +1,215 @@
+use std::net::Ipv4Addr;
+
+use mobile_network_core_event::{Event, MobileNetworkCoreEvent};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::qos::QosFlow;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum UserPlaneEvent {
+    QosMonitoring,
+}
+
+/// Identifies the traffic a QoS flow carries, `flowId` is the id of the application.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowInfo {
+    flow_id: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    flow_descriptions: Vec<String>,
+}
+
+/// The AsSessionWithQoSSubscription resource of TS 29.122.
+/// `qosReference` names a standardized 5QI as `5qi-{5QI}`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AsSessionWithQosSubscription {
+    #[serde(rename = "self", skip_serializing_if = "Option::is_none")]
+    self_link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supported_features: Option<String>,
+    notification_destination: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    flow_info: Vec<FlowInfo>,
+    qos_reference: String,
+    ue_ipv4_addr: Ipv4Addr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gbr_ul: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_bit_rate_ul: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    events: Vec<UserPlaneEvent>,
+}
+
+impl AsSessionWithQosSubscription {
+    pub fn validate(&self) -> Result<(), String> {
+        if Url::parse(&self.notification_destination).is_err() {
+            return Err(format!(
+                "notificationDestination {} is not a valid URL",
+                self.notification_destination
+            ));
+        }
+        match self.qos_flow(0) {
+            Some(_) => Ok(()),
+            None => Err(format!("qosReference {} is unknown", self.qos_reference)),
+        }
+    }
+
+    /// The QoS flow this subscription asks for, `None` if the QoS reference is unknown.
+    pub fn qos_flow(&self, id: u32) -> Option<QosFlow> {
+        QosFlow::new(
+            id,
+            &self.qos_reference,
+            self.flow_info.iter().map(|flow| flow.flow_id).collect(),
+            self.gbr_ul,
+            self.max_bit_rate_ul,
+        )
+    }
+
+    pub fn get_ue_ipv4_addr(&self) -> Ipv4Addr {
+        self.ue_ipv4_addr
+    }
+
+    pub fn set_self_link(&mut self, self_link: String) {
+        self.self_link = Some(self_link);
+    }
+
+    pub fn get_notification_destination(&self) -> Url {
+        Url::parse(&self.notification_destination).unwrap()
+    }
+
+    /// Whether `event` monitors this subscription's flow, on the UE with `ue_id` if it is known
+    /// and otherwise on the PDU session holding `ueIpv4Addr`.
+    pub fn matches(&self, event: &MobileNetworkCoreEvent, ue_id: Option<u32>) -> bool {
+        if !self.events.contains(&UserPlaneEvent::QosMonitoring) {
+            return false;
+        }
+        match event.get_event() {
+            Event::QosMonitoring(qos_monitoring) => {
+                ue_id.map_or(qos_monitoring.ipv4_addr == self.ue_ipv4_addr, |ue_id| {
+                    event.get_user_id() == ue_id
+                }) && qos_monitoring.qos_reference == self.qos_reference
+                    && (self.flow_info.is_empty()
+                        || self
+                            .flow_info
+                            .iter()
+                            .any(|flow| flow.flow_id == qos_monitoring.application_id))
+            }
+            _ => false,
+        }
+    }
+
+    pub fn notification(&self, events: &[MobileNetworkCoreEvent]) -> UserPlaneNotificationData {
+        UserPlaneNotificationData {
+            transaction: self.self_link.clone().unwrap_or_default(),
+            event_reports: events
+                .iter()
+                .filter_map(|event| match event.get_event() {
+                    Event::QosMonitoring(qos_monitoring) => Some(UserPlaneEventReport {
+                        event: UserPlaneEvent::QosMonitoring,
+                        flow_ids: vec![qos_monitoring.application_id],
+                        qos_mon_reports: vec![QosMonitoringReport {
+                            ul_delays: vec![qos_monitoring.measured_delay],
+                            dl_delays: vec![qos_monitoring.measured_delay],
+                            rt_delays: vec![qos_monitoring.measured_delay * 2],
+                        }],
+                    }),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Packet delays in milliseconds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QosMonitoringReport {
+    ul_delays: Vec<u64>,
+    dl_delays: Vec<u64>,
+    rt_delays: Vec<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserPlaneEventReport {
+    event: UserPlaneEvent,
+    flow_ids: Vec<u32>,
+    qos_mon_reports: Vec<QosMonitoringReport>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserPlaneNotificationData {
+    transaction: String,
+    event_reports: Vec<UserPlaneEventReport>,
+}
+
+#[cfg(test)]
+mod tests {
+    use mobile_network_core_event::{EventKind, QosMonitoringInformation};
+    use serde_json::json;
+
+    use super::*;
+
+    fn subscription() -> AsSessionWithQosSubscription {
+        serde_json::from_value(json!({
+            "notificationDestination": "http://localhost:8789/",
+            "flowInfo": [{"flowId": 2, "flowDescriptions": ["permit out ip from any to any"]}],
+            "qosReference": "5qi-82",
+            "ueIpv4Addr": "10.0.0.1",
+            "events": ["QOS_MONITORING"]
+        }))
+        .unwrap()
+    }
+
+    fn qos_monitoring_event(qos_reference: &str) -> MobileNetworkCoreEvent {
+        MobileNetworkCoreEvent::new(
+            Event::QosMonitoring(QosMonitoringInformation::new(
+                qos_reference.to_string(),
+                2,
+                Ipv4Addr::new(10, 0, 0, 1),
+                10,
+                42,
+            )),
+            EventKind::QosMonitoring,
+            1,
+        )
+    }
+
+    #[test]
+    fn validate() {
+        let mut subscription = subscription();
+        assert!(subscription.validate().is_ok());
+        assert!(subscription.qos_flow(3).unwrap().carries(2));
+
+        subscription.qos_reference = "platinum".to_string();
+        assert!(subscription.validate().is_err());
+    }
+
+    #[test]
+    fn qos_monitoring_notification() {
+        let mut subscription = subscription();
+        subscription.set_self_link("http://localhost/subscriptions/0".to_string());
+
+        assert!(subscription.matches(&qos_monitoring_event("5qi-82"), None));
+        assert!(!subscription.matches(&qos_monitoring_event("5qi-9"), None));
+        assert!(subscription.matches(&qos_monitoring_event("5qi-82"), Some(1)));
+        assert!(!subscription.matches(&qos_monitoring_event("5qi-82"), Some(2)));
+
+        let notification =
+            serde_json::to_value(subscription.notification(&[qos_monitoring_event("5qi-82")]))
+                .unwrap();
+        assert_eq!(
+            notification["transaction"],
+            "http://localhost/subscriptions/0"
+        );
+        assert_eq!(notification["eventReports"][0]["event"], "QOS_MONITORING");
+        assert_eq!(
+            notification["eventReports"][0]["qosMonReports"][0]["ulDelays"][0],
+            42
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use std::net::IpAddr;
+
+use actix_web::{
+    delete, get, post, put,
+    web::{Data, Json, Path},
+    HttpRequest, HttpResponse,
+};
+use mongodb::Database;
+
+use crate::{
+    as_session_with_qos::AsSessionWithQosSubscription,
+    mobile_network_core_endpoints::MobileNetworkCoreWrapper,
+    mobile_network_exposure::Subscription,
+    mobile_network_exposure_endpoints::MobileNetworkExposureWrapper,
+    northbound::{request_url, resource_url, ProblemDetails},
+};
+
+fn as_session_with_qos_subscription<'a>(
+    subscription: &'a Subscription,
+    scs_as_id: &str,
+) -> Option<&'a AsSessionWithQosSubscription> {
+    match subscription {
+        Subscription::AsSessionWithQos {
+            scs_as_id: owner,
+            subscription,
+            ..
+        } if owner == scs_as_id => Some(subscription),
+        _ => None,
+    }
+}
+
+#[get("/3gpp-as-session-with-qos/v1/{scs_as_id}/subscriptions")]
+pub async fn get_as_session_with_qos_subscriptions(
+    scs_as_id: Path<String>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+) -> HttpResponse {
+    let subscriptions: Vec<AsSessionWithQosSubscription> = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .read()
+        .await
+        .get_subscribers()
+        .into_iter()
+        .filter_map(|subscriber| {
+            as_session_with_qos_subscription(subscriber.get_subscription(), &scs_as_id)
+        })
+        .cloned()
+        .collect();
+    HttpResponse::Ok().json(subscriptions)
+}
+
+/// Creates an AS session with QoS and sets up the requested QoS flow on the UE's PDU session.
+#[post("/3gpp-as-session-with-qos/v1/{scs_as_id}/subscriptions")]
+pub async fn post_as_session_with_qos_subscription(
+    request: HttpRequest,
+    scs_as_id: Path<String>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    mobile_network_core_wrapper: Data<MobileNetworkCoreWrapper>,
+    database: Data<Database>,
+    subscription: Json<AsSessionWithQosSubscription>,
+) -> HttpResponse {
+    let mut subscription = subscription.into_inner();
+    if let Err(err) = subscription.validate() {
+        return ProblemDetails::bad_request(&err);
+    }
+
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    let id = mobile_network_exposure.add_subscription(Subscription::AsSessionWithQos {
+        scs_as_id: scs_as_id.clone(),
+        subscription: subscription.clone(),
+        ue_id: None,
+    });
+    let ue_id = match mobile_network_core_wrapper
+        .mobile_network_core
+        .write()
+        .await
+        .add_qos_flow(
+            &IpAddr::V4(subscription.get_ue_ipv4_addr()),
+            subscription.qos_flow(id).unwrap(),
+        ) {
+        Ok(ue_id) => ue_id,
+        Err(err) => {
+            mobile_network_exposure.remove_subscription(id).unwrap();
+            return ProblemDetails::bad_request(&err.to_string());
+        }
+    };
+    let location = resource_url(&request, id);
+    subscription.set_self_link(location.clone());
+    mobile_network_exposure
+        .update_subscription(
+            id,
+            Subscription::AsSessionWithQos {
+                scs_as_id: scs_as_id.into_inner(),
+                subscription: subscription.clone(),
+                ue_id: Some(ue_id),
+            },
+        )
+        .unwrap();
+
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::Created()
+        .insert_header(("Location", location))
+        .json(subscription)
+}
+
+#[get("/3gpp-as-session-with-qos/v1/{scs_as_id}/subscriptions/{subscription_id}")]
+pub async fn get_as_session_with_qos_subscription(
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+) -> HttpResponse {
+    let (scs_as_id, subscription_id) = path.into_inner();
+    match mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .read()
+        .await
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            as_session_with_qos_subscription(subscriber.get_subscription(), &scs_as_id)
+        }) {
+        Some(subscription) => HttpResponse::Ok().json(subscription),
+        None => ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        )),
+    }
+}
+
+#[put("/3gpp-as-session-with-qos/v1/{scs_as_id}/subscriptions/{subscription_id}")]
+pub async fn put_as_session_with_qos_subscription(
+    request: HttpRequest,
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    mobile_network_core_wrapper: Data<MobileNetworkCoreWrapper>,
+    database: Data<Database>,
+    subscription: Json<AsSessionWithQosSubscription>,
+) -> HttpResponse {
+    let (scs_as_id, subscription_id) = path.into_inner();
+    let mut subscription = subscription.into_inner();
+    if let Err(err) = subscription.validate() {
+        return ProblemDetails::bad_request(&err);
+    }
+
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    if mobile_network_exposure
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            as_session_with_qos_subscription(subscriber.get_subscription(), &scs_as_id)
+        })
+        .is_none()
+    {
+        return ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        ));
+    }
+    let mut mobile_network_core = mobile_network_core_wrapper
+        .mobile_network_core
+        .write()
+        .await;
+    let previous_qos_flow = mobile_network_core.remove_qos_flow(subscription_id);
+    let ue_id = match mobile_network_core.add_qos_flow(
+        &IpAddr::V4(subscription.get_ue_ipv4_addr()),
+        subscription.qos_flow(subscription_id).unwrap(),
+    ) {
+        Ok(ue_id) => ue_id,
+        Err(err) => {
+            let previous_ue_id = mobile_network_exposure
+                .get_subscription(subscription_id)
+                .and_then(|subscriber| match subscriber.get_subscription() {
+                    Subscription::AsSessionWithQos { ue_id, .. } => *ue_id,
+                    _ => None,
+                });
+            if let (Some(previous_qos_flow), Some(previous_ue_id)) =
+                (previous_qos_flow, previous_ue_id)
+            {
+                mobile_network_core.add_user_qos_flow(previous_ue_id, previous_qos_flow);
+            }
+            return ProblemDetails::bad_request(&err.to_string());
+        }
+    };
+    subscription.set_self_link(request_url(&request));
+    mobile_network_exposure
+        .update_subscription(
+            subscription_id,
+            Subscription::AsSessionWithQos {
+                scs_as_id,
+                subscription: subscription.clone(),
+                ue_id: Some(ue_id),
+            },
+        )
+        .unwrap();
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::Ok().json(subscription)
+}
+
+#[delete("/3gpp-as-session-with-qos/v1/{scs_as_id}/subscriptions/{subscription_id}")]
+pub async fn delete_as_session_with_qos_subscription(
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    mobile_network_core_wrapper: Data<MobileNetworkCoreWrapper>,
+    database: Data<Database>,
+) -> HttpResponse {
+    let (scs_as_id, subscription_id) = path.into_inner();
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    if mobile_network_exposure
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            as_session_with_qos_subscription(subscriber.get_subscription(), &scs_as_id)
+        })
+        .is_none()
+    {
+        return ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        ));
+    }
+    mobile_network_exposure
+        .remove_subscription(subscription_id)
+        .unwrap();
+    // The flow is already gone if the UE released its PDU session.
+    mobile_network_core_wrapper
+        .mobile_network_core
+        .write()
+        .await
+        .remove_qos_flow(subscription_id);
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::NoContent().finish()
+}
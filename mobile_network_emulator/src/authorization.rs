@@ -0,0 +1,246 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+const ISSUER: &str = "mobile_network_emulator";
+const TOKEN_LIFETIME: Duration = Duration::from_secs(3600);
+
+#[derive(Debug)]
+pub struct AuthorizationError {
+    message: String,
+}
+
+impl AuthorizationError {
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for AuthorizationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for AuthorizationError {}
+
+/// An AF allowed to request access tokens, its client id is the AF id used in the 3GPP APIs.
+/// Operator clients may also use the endpoints that concern every AF.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AfClient {
+    client_id: String,
+    client_secret: String,
+    #[serde(default)]
+    operator: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    iss: String,
+    sub: String,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    typ: String,
+}
+
+/// Issues and checks HS256 signed JWTs for the configured AF clients.
+/// The signing key is generated at startup, so tokens do not outlive the emulator.
+pub struct Authorization {
+    clients: Vec<AfClient>,
+    signing_key: [u8; 32],
+}
+
+impl Authorization {
+    pub fn new(clients: Vec<AfClient>) -> Self {
+        let mut signing_key = [0; 32];
+        rand::thread_rng().fill_bytes(&mut signing_key);
+        Self {
+            clients,
+            signing_key,
+        }
+    }
+
+    /// Without configured clients the exposure API stays open.
+    pub fn is_enabled(&self) -> bool {
+        !self.clients.is_empty()
+    }
+
+    pub fn get_number_of_clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn is_operator(&self, client_id: &str) -> bool {
+        self.clients
+            .iter()
+            .any(|client| client.client_id == client_id && client.operator)
+    }
+
+    pub fn get_token_lifetime(&self) -> Duration {
+        TOKEN_LIFETIME
+    }
+
+    fn mac(&self, signing_input: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.signing_key).unwrap();
+        mac.update(signing_input.as_bytes());
+        mac
+    }
+
+    /// Issues an access token for the client if its credentials are valid.
+    pub fn issue_token(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+    ) -> Result<String, AuthorizationError> {
+        if !self
+            .clients
+            .iter()
+            .any(|client| client.client_id == client_id && client.client_secret == client_secret)
+        {
+            return Err(AuthorizationError::new("invalid client credentials"));
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let header = Header {
+            alg: "HS256".to_string(),
+            typ: "JWT".to_string(),
+        };
+        let claims = Claims {
+            iss: ISSUER.to_string(),
+            sub: client_id.to_string(),
+            iat: now,
+            exp: now + TOKEN_LIFETIME.as_secs(),
+        };
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header).unwrap()),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap())
+        );
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&signing_input).finalize().into_bytes());
+        Ok(format!("{}.{}", signing_input, signature))
+    }
+
+    /// Checks an access token and returns the client id it was issued to.
+    pub fn verify_token(&self, token: &str) -> Result<String, AuthorizationError> {
+        let invalid = || AuthorizationError::new("invalid access token");
+        let (signing_input, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+        let (header, claims) = signing_input.split_once('.').ok_or_else(invalid)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+        self.mac(signing_input)
+            .verify_slice(&signature)
+            .map_err(|_| invalid())?;
+
+        let header: Header = URL_SAFE_NO_PAD
+            .decode(header)
+            .ok()
+            .and_then(|header| serde_json::from_slice(&header).ok())
+            .ok_or_else(invalid)?;
+        let claims: Claims = URL_SAFE_NO_PAD
+            .decode(claims)
+            .ok()
+            .and_then(|claims| serde_json::from_slice(&claims).ok())
+            .ok_or_else(invalid)?;
+        if header.alg != "HS256" || claims.iss != ISSUER {
+            return Err(invalid());
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        if claims.exp <= now {
+            return Err(AuthorizationError::new("access token expired"));
+        }
+        if !self
+            .clients
+            .iter()
+            .any(|client| client.client_id == claims.sub)
+        {
+            return Err(AuthorizationError::new("unknown client"));
+        }
+        Ok(claims.sub)
+    }
+}
+
+/// The AF id in the path of a 3GPP northbound request, e.g. `af` in `/3gpp-monitoring-event/v1/af/subscriptions`.
+/// `path` is relative to the exposure API.
+pub fn northbound_af_id(path: &str) -> Option<&str> {
+    let mut segments = path.trim_start_matches('/').split('/');
+    if !segments.next()?.starts_with("3gpp-") {
+        return None;
+    }
+    segments.next()?;
+    segments.next().filter(|af_id| !af_id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authorization() -> Authorization {
+        Authorization::new(vec![
+            AfClient {
+                client_id: "af-1".to_string(),
+                client_secret: "secret".to_string(),
+                operator: false,
+            },
+            AfClient {
+                client_id: "operator".to_string(),
+                client_secret: "secret".to_string(),
+                operator: true,
+            },
+        ])
+    }
+
+    #[test]
+    fn issue_and_verify_token() {
+        let authorization = authorization();
+        assert!(authorization.issue_token("af-1", "wrong").is_err());
+        assert!(authorization.issue_token("af-2", "secret").is_err());
+
+        let token = authorization.issue_token("af-1", "secret").unwrap();
+        assert_eq!(authorization.verify_token(&token).unwrap(), "af-1");
+
+        let mut tampered = token.clone();
+        tampered.pop();
+        assert!(authorization.verify_token(&tampered).is_err());
+        assert!(Authorization::new(vec![]).verify_token(&token).is_err());
+    }
+
+    #[test]
+    fn operator_clients() {
+        let authorization = authorization();
+        assert!(!authorization.is_operator("af-1"));
+        assert!(authorization.is_operator("operator"));
+        assert!(!authorization.is_operator("af-2"));
+    }
+
+    #[test]
+    fn af_id_from_path() {
+        assert_eq!(
+            northbound_af_id("/3gpp-monitoring-event/v1/af-1/subscriptions/3"),
+            Some("af-1")
+        );
+        assert_eq!(
+            northbound_af_id("/3gpp-analyticsexposure/v1/af-1/fetch"),
+            Some("af-1")
+        );
+        assert_eq!(northbound_af_id("/subscribers/3"), None);
+        assert_eq!(northbound_af_id("/3gpp-monitoring-event/v1/"), None);
+    }
+}
@@ -0,0 +1,260 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::{header, StatusCode},
+    middleware::Next,
+    post,
+    web::{Data, Form, ReqData},
+    HttpMessage, HttpRequest, HttpResponse,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    authorization::{northbound_af_id, Authorization},
+    northbound::ProblemDetails,
+};
+
+/// The AF a request to the exposure API was authorized for.
+#[derive(Clone, Debug)]
+pub struct AfId(pub String);
+
+/// Marks a request to the exposure API authorized for an operator client.
+#[derive(Clone, Debug)]
+pub struct Operator;
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    grant_type: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: &'static str,
+    expires_in: u64,
+}
+
+#[derive(Serialize)]
+struct TokenError {
+    error: &'static str,
+    error_description: String,
+}
+
+fn token_error(status: StatusCode, error: &'static str, description: &str) -> HttpResponse {
+    let mut response = HttpResponse::build(status);
+    if status == StatusCode::UNAUTHORIZED {
+        response.insert_header((header::WWW_AUTHENTICATE, "Basic"));
+    }
+    response.json(TokenError {
+        error,
+        error_description: description.to_string(),
+    })
+}
+
+/// Percent-decodes a form encoded value, `+` standing for a space.
+/// Unlike parsing a form, `=` and `&` are kept as part of the value.
+fn form_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+        match (bytes[index], hex) {
+            (b'%', Some(hex)) => {
+                // Both are ASCII hex digits.
+                let hex = std::str::from_utf8(hex).unwrap();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                index += 2;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Client id and secret from HTTP Basic authentication, form encoded as RFC 6749 asks for.
+fn basic_credentials(request: &HttpRequest) -> Option<(String, String)> {
+    let credentials = request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let credentials = String::from_utf8(STANDARD.decode(credentials.trim()).ok()?).ok()?;
+    let (client_id, client_secret) = credentials.split_once(':')?;
+    Some((form_decode(client_id), form_decode(client_secret)))
+}
+
+/// OAuth2 token endpoint for the client credentials grant.
+/// Credentials are accepted with HTTP Basic authentication or in the form body.
+#[post("/token")]
+pub async fn post_token(
+    request: HttpRequest,
+    authorization: Data<Authorization>,
+    token_request: Form<TokenRequest>,
+) -> HttpResponse {
+    let token_request = token_request.into_inner();
+    if token_request.grant_type != "client_credentials" {
+        return token_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+            "only client_credentials is supported",
+        );
+    }
+    let (client_id, client_secret) = match basic_credentials(&request)
+        .or(token_request.client_id.zip(token_request.client_secret))
+    {
+        Some(credentials) => credentials,
+        None => {
+            return token_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+                "client credentials are missing",
+            )
+        }
+    };
+    match authorization.issue_token(&client_id, &client_secret) {
+        Ok(access_token) => HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .json(TokenResponse {
+                access_token,
+                token_type: "Bearer",
+                expires_in: authorization.get_token_lifetime().as_secs(),
+            }),
+        Err(err) => token_error(StatusCode::UNAUTHORIZED, "invalid_client", &err.to_string()),
+    }
+}
+
+fn rejection(status: StatusCode, detail: &str) -> actix_web::Error {
+    let mut response = ProblemDetails::new(status, detail).into_response();
+    if status == StatusCode::UNAUTHORIZED {
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static("Bearer"),
+        );
+    }
+    InternalError::from_response(detail.to_string(), response).into()
+}
+
+/// Requires a valid bearer token on the exposure API when authorization is enabled.
+/// 3GPP requests may only use the AF id the token was issued to,
+/// the AF id is handed to the endpoints as `AfId` and operator clients are marked with `Operator`.
+pub async fn authenticate(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let authorization = request.app_data::<Data<Authorization>>().unwrap().clone();
+    if authorization.is_enabled() {
+        let token = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| rejection(StatusCode::UNAUTHORIZED, "a bearer token is required"))?;
+        let af_id = authorization
+            .verify_token(token.trim())
+            .map_err(|err| rejection(StatusCode::UNAUTHORIZED, &err.to_string()))?;
+        let path = request.match_info().unprocessed();
+        if let Some(path_af_id) = northbound_af_id(path) {
+            if path_af_id != af_id {
+                return Err(rejection(
+                    StatusCode::FORBIDDEN,
+                    &format!("the access token was not issued to {}", path_af_id),
+                ));
+            }
+        }
+        if authorization.is_operator(&af_id) {
+            request.extensions_mut().insert(Operator);
+        }
+        request.extensions_mut().insert(AfId(af_id));
+    }
+    next.call(request).await
+}
+
+/// Refuses requests authorized for an AF unless it is an operator client,
+/// the endpoint is not scoped to one AF.
+pub fn operator_only(
+    af_id: Option<ReqData<AfId>>,
+    operator: Option<ReqData<Operator>>,
+) -> Result<(), actix_web::Error> {
+    match (af_id, operator) {
+        (Some(af_id), None) => Err(rejection(
+            StatusCode::FORBIDDEN,
+            &format!("{} may only access its own subscriptions", af_id.0),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        middleware::from_fn,
+        test::{init_service, try_call_service, TestRequest},
+        web, App,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn form_decode_keeps_separators() {
+        assert_eq!(form_decode("a=b&c"), "a=b&c");
+        assert_eq!(form_decode("s%3Dcret%26+x"), "s=cret& x");
+        assert_eq!(form_decode("100%"), "100%");
+        assert_eq!(form_decode("%zz%C3%A9"), "%zzé");
+    }
+
+    #[actix_web::test]
+    async fn operator_only_with_authorization() {
+        let authorization = Authorization::new(
+            serde_json::from_value(json!([
+                { "client_id": "af-1", "client_secret": "secret" },
+                { "client_id": "operator", "client_secret": "secret", "operator": true }
+            ]))
+            .unwrap(),
+        );
+        let af_token = authorization.issue_token("af-1", "secret").unwrap();
+        let operator_token = authorization.issue_token("operator", "secret").unwrap();
+        let app = init_service(
+            App::new().app_data(Data::new(authorization)).service(
+                web::scope("/mobile_network_exposure")
+                    .wrap(from_fn(authenticate))
+                    .route(
+                        "/events",
+                        web::get().to(
+                            |af_id: Option<ReqData<AfId>>, operator: Option<ReqData<Operator>>| async {
+                                operator_only(af_id, operator).map(|_| HttpResponse::Ok())
+                            },
+                        ),
+                    ),
+            ),
+        )
+        .await;
+
+        for (token, expected) in [
+            (None, StatusCode::UNAUTHORIZED),
+            (Some(af_token), StatusCode::FORBIDDEN),
+            (Some(operator_token), StatusCode::OK),
+        ] {
+            let mut request = TestRequest::get().uri("/mobile_network_exposure/events");
+            if let Some(token) = token {
+                request =
+                    request.insert_header((header::AUTHORIZATION, format!("Bearer {}", token)));
+            }
+            let status = match try_call_service(&app, request.to_request()).await {
+                Ok(response) => response.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            assert_eq!(status, expected);
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use log::{error, warn};
+use mobile_network_core_event::notification_signature::{sign, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use reqwest::{header::CONTENT_TYPE, Client, RequestBuilder};
+use serde::Serialize;
+use tokio::time::Instant;
+use url::Url;
+
+use crate::metrics::Metrics;
+
+/// Number of dead letters kept, the oldest ones are dropped first.
+const MAX_DEAD_LETTERS: usize = 1000;
+
+#[derive(Clone, Debug)]
+pub struct DeliveryConfig {
+    pub timeout: Duration,
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            max_attempts: 8,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl DeliveryConfig {
+    /// The wait before retrying after `attempts` failed attempts.
+    fn backoff(&self, attempts: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Delivery {
+    endpoint: Url,
+    body: serde_json::Value,
+    secret: Option<String>,
+    attempts: u32,
+    next_attempt: Instant,
+    /// Taken out to be sent, it stays at the head of its queue until the result is recorded.
+    in_flight: bool,
+}
+
+/// Delivery statistics of one subscription.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DeliveryStatus {
+    subscription_id: u32,
+    pending: usize,
+    delivered: u64,
+    failed_attempts: u64,
+    dead_lettered: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_success: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_failure: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_error: Option<String>,
+}
+
+impl DeliveryStatus {
+    pub fn get_subscription_id(&self) -> u32 {
+        self.subscription_id
+    }
+}
+
+/// A notification given up on after the maximum number of attempts.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeadLetter {
+    subscription_id: u32,
+    endpoint: String,
+    body: serde_json::Value,
+    attempts: u32,
+    last_error: String,
+    time: DateTime<Utc>,
+}
+
+/// Delivers notifications with one FIFO queue per subscription.
+/// Only the head of a queue is attempted so a subscriber receives its notifications in order,
+/// failed attempts are retried with exponential backoff until they are dead-lettered.
+pub struct Deliveries {
+    config: DeliveryConfig,
+    http_client: Client,
+    queues: BTreeMap<u32, VecDeque<Delivery>>,
+    statuses: BTreeMap<u32, DeliveryStatus>,
+    dead_letters: VecDeque<DeadLetter>,
+    metrics: Metrics,
+}
+
+/// Notifications taken out of their queues to be sent.
+pub struct DueDeliveries {
+    requests: Vec<(u32, RequestBuilder)>,
+}
+
+impl DueDeliveries {
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Posts the notifications, different subscribers concurrently.
+    pub async fn send(self) -> DeliveryResults {
+        let (subscription_ids, requests): (Vec<u32>, Vec<RequestBuilder>) =
+            self.requests.into_iter().unzip();
+        let results = join_all(requests.into_iter().map(|request| request.send())).await;
+        DeliveryResults {
+            results: subscription_ids
+                .into_iter()
+                .zip(results)
+                .map(|(subscription_id, result)| {
+                    let result = result
+                        .and_then(|response| response.error_for_status())
+                        .map(|_| ())
+                        .map_err(|err| err.to_string());
+                    (subscription_id, result)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// How sending [`DueDeliveries`] went, per subscription.
+pub struct DeliveryResults {
+    results: Vec<(u32, Result<(), String>)>,
+}
+
+impl DeadLetter {
+    pub fn get_subscription_id(&self) -> u32 {
+        self.subscription_id
+    }
+}
+
+impl Deliveries {
+    pub fn new(config: DeliveryConfig, metrics: Metrics) -> Self {
+        Self {
+            http_client: Client::builder().timeout(config.timeout).build().unwrap(),
+            config,
+            queues: BTreeMap::new(),
+            statuses: BTreeMap::new(),
+            dead_letters: VecDeque::new(),
+            metrics,
+        }
+    }
+
+    /// Queues `body` for the subscription, signed with `secret` if there is one.
+    pub fn enqueue(
+        &mut self,
+        subscription_id: u32,
+        endpoint: Url,
+        body: serde_json::Value,
+        secret: Option<String>,
+    ) {
+        self.queues
+            .entry(subscription_id)
+            .or_default()
+            .push_back(Delivery {
+                endpoint,
+                body,
+                secret,
+                attempts: 0,
+                next_attempt: Instant::now(),
+                in_flight: false,
+            });
+        self.status_mut(subscription_id).pending += 1;
+    }
+
+    /// Takes out the head of every queue that is due to be sent without holding on to the deliveries,
+    /// the results are handed back with [`Deliveries::record`].
+    pub fn take_due(&mut self) -> DueDeliveries {
+        let now = Instant::now();
+        let mut requests = Vec::new();
+        for (subscription_id, queue) in self.queues.iter_mut() {
+            if let Some(delivery) = queue
+                .front_mut()
+                .filter(|delivery| !delivery.in_flight && delivery.next_attempt <= now)
+            {
+                delivery.in_flight = true;
+                requests.push((*subscription_id, Self::request(&self.http_client, delivery)));
+            }
+        }
+        DueDeliveries { requests }
+    }
+
+    /// Records the results of sending deliveries taken with [`Deliveries::take_due`],
+    /// those of subscriptions removed in the meantime are dropped.
+    pub fn record(&mut self, results: DeliveryResults) {
+        for (subscription_id, result) in results.results {
+            self.metrics.record_webhook_delivery(result.is_ok());
+            let in_flight = self
+                .queues
+                .get(&subscription_id)
+                .and_then(|queue| queue.front())
+                .is_some_and(|delivery| delivery.in_flight);
+            if !in_flight {
+                continue;
+            }
+            match result {
+                Ok(()) => self.delivered(subscription_id),
+                Err(err) => self.failed(subscription_id, err),
+            }
+        }
+        self.queues.retain(|_, queue| !queue.is_empty());
+    }
+
+    /// Drops the queued deliveries of a removed subscription.
+    pub fn remove(&mut self, subscription_id: u32) {
+        self.queues.remove(&subscription_id);
+        self.statuses.remove(&subscription_id);
+    }
+
+    /// Every attempt is signed anew so retries carry a fresh timestamp.
+    fn request(http_client: &Client, delivery: &Delivery) -> RequestBuilder {
+        let body = serde_json::to_vec(&delivery.body).unwrap();
+        let mut request = http_client
+            .post(delivery.endpoint.clone())
+            .header(CONTENT_TYPE, "application/json");
+        if let Some(secret) = &delivery.secret {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            request = request
+                .header(TIMESTAMP_HEADER, timestamp)
+                .header(SIGNATURE_HEADER, sign(secret, timestamp, &body));
+        }
+        request.body(body)
+    }
+
+    fn delivered(&mut self, subscription_id: u32) {
+        self.pop(subscription_id);
+        let status = self.status_mut(subscription_id);
+        status.delivered += 1;
+        status.last_success = Some(Utc::now());
+    }
+
+    fn failed(&mut self, subscription_id: u32, err: String) {
+        let config = self.config.clone();
+        let delivery = match self
+            .queues
+            .get_mut(&subscription_id)
+            .and_then(|queue| queue.front_mut())
+        {
+            Some(delivery) => delivery,
+            None => return,
+        };
+        delivery.in_flight = false;
+        delivery.attempts += 1;
+        delivery.next_attempt = Instant::now() + config.backoff(delivery.attempts);
+        let attempts = delivery.attempts;
+        let endpoint = delivery.endpoint.clone();
+        {
+            let status = self.status_mut(subscription_id);
+            status.failed_attempts += 1;
+            status.last_failure = Some(Utc::now());
+            status.last_error = Some(err.clone());
+        }
+        if attempts < config.max_attempts {
+            warn!(
+                "attempt {} to notify {} failed: {}",
+                attempts, endpoint, err
+            );
+            return;
+        }
+
+        error!(
+            "giving up on notifying {} after {} attempts: {}",
+            endpoint, attempts, err
+        );
+        let delivery = self.pop(subscription_id).unwrap();
+        self.status_mut(subscription_id).dead_lettered += 1;
+        if self.dead_letters.len() == MAX_DEAD_LETTERS {
+            self.dead_letters.pop_front();
+        }
+        self.dead_letters.push_back(DeadLetter {
+            subscription_id,
+            endpoint: delivery.endpoint.to_string(),
+            body: delivery.body,
+            attempts,
+            last_error: err,
+            time: Utc::now(),
+        });
+    }
+
+    fn pop(&mut self, subscription_id: u32) -> Option<Delivery> {
+        let delivery = self.queues.get_mut(&subscription_id)?.pop_front()?;
+        self.status_mut(subscription_id).pending -= 1;
+        Some(delivery)
+    }
+
+    fn status_mut(&mut self, subscription_id: u32) -> &mut DeliveryStatus {
+        self.statuses
+            .entry(subscription_id)
+            .or_insert_with(|| DeliveryStatus {
+                subscription_id,
+                ..Default::default()
+            })
+    }
+
+    pub fn get_statuses(&self) -> Vec<&DeliveryStatus> {
+        self.statuses.values().collect()
+    }
+
+    pub fn get_status(&self, subscription_id: u32) -> Option<&DeliveryStatus> {
+        self.statuses.get(&subscription_id)
+    }
+
+    pub fn get_dead_letters(&self) -> Vec<&DeadLetter> {
+        self.dead_letters.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use mobile_network_core_event::notification_signature::{verify, DEFAULT_TOLERANCE};
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Stand-in subscriber answering the first `failures` requests with 500 and all others with 204.
+    /// Returns the raw requests it received.
+    async fn subscriber(failures: usize) -> (Url, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_lowercase();
+                    if let Some(header_end) = text.find("\r\n\r\n") {
+                        let content_length: usize = text
+                            .lines()
+                            .find_map(|line| line.strip_prefix("content-length:"))
+                            .map(|length| length.trim().parse().unwrap())
+                            .unwrap_or(0);
+                        if request.len() >= header_end + 4 + content_length {
+                            break;
+                        }
+                    }
+                }
+                let fail = {
+                    let mut received = received.lock().unwrap();
+                    received.push(String::from_utf8_lossy(&request).to_string());
+                    received.len() <= failures
+                };
+                let status = if fail {
+                    "500 Internal Server Error"
+                } else {
+                    "204 No Content"
+                };
+                stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                            status
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    fn deliveries() -> Deliveries {
+        Deliveries::new(
+            DeliveryConfig {
+                timeout: Duration::from_secs(1),
+                max_attempts: 3,
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(40),
+            },
+            Metrics::new(),
+        )
+    }
+
+    async fn deliver_due(deliveries: &mut Deliveries) {
+        let results = deliveries.take_due().send().await;
+        deliveries.record(results);
+    }
+
+    async fn drain(deliveries: &mut Deliveries) {
+        for _ in 0..50 {
+            deliver_due(deliveries).await;
+            if deliveries.queues.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[test]
+    fn backoff() {
+        let config = DeliveryConfig {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            ..Default::default()
+        };
+
+        assert_eq!(config.backoff(1), Duration::from_millis(100));
+        assert_eq!(config.backoff(3), Duration::from_millis(400));
+        assert_eq!(config.backoff(4), Duration::from_millis(500));
+        assert_eq!(config.backoff(64), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn retry_until_delivered() {
+        let (url, requests) = subscriber(2).await;
+        let mut deliveries = deliveries();
+
+        deliveries.enqueue(0, url.clone(), json!({"report": 1}), None);
+        deliveries.enqueue(0, url, json!({"report": 2}), None);
+        drain(&mut deliveries).await;
+
+        let status = deliveries.get_status(0).unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 4);
+        assert_eq!(status.delivered, 2);
+        assert_eq!(status.failed_attempts, 2);
+        assert_eq!(status.dead_lettered, 0);
+        assert!(deliveries.get_dead_letters().is_empty());
+    }
+
+    #[tokio::test]
+    async fn dead_letter_after_max_attempts() {
+        let (url, requests) = subscriber(usize::MAX).await;
+        let mut deliveries = deliveries();
+
+        deliveries.enqueue(7, url, json!({"report": 1}), None);
+        drain(&mut deliveries).await;
+
+        let status = deliveries.get_status(7).unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        assert_eq!(status.delivered, 0);
+        assert_eq!(status.dead_lettered, 1);
+        assert_eq!(deliveries.get_dead_letters()[0].attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn remove_drops_queued_deliveries() {
+        let (url, requests) = subscriber(usize::MAX).await;
+        let mut deliveries = deliveries();
+
+        deliveries.enqueue(3, url.clone(), json!({"report": 1}), None);
+        deliveries.enqueue(3, url, json!({"report": 2}), None);
+        let due = deliveries.take_due();
+        assert!(deliveries.take_due().is_empty());
+        deliveries.remove(3);
+        deliveries.record(due.send().await);
+
+        assert!(deliveries.queues.is_empty());
+        assert!(deliveries.get_status(3).is_none());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn signed_delivery() {
+        let (url, requests) = subscriber(0).await;
+        let mut deliveries = deliveries();
+
+        deliveries.enqueue(1, url, json!({"report": 1}), Some("secret".to_string()));
+        drain(&mut deliveries).await;
+
+        let request = requests.lock().unwrap()[0].clone();
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        let header = |name: &str| {
+            head.lines()
+                .find_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    key.eq_ignore_ascii_case(name)
+                        .then(|| value.trim().to_string())
+                })
+                .unwrap()
+        };
+        let timestamp = header(TIMESTAMP_HEADER);
+        let signature = header(SIGNATURE_HEADER);
+        assert!(verify(
+            "secret",
+            &timestamp,
+            &signature,
+            body.as_bytes(),
+            DEFAULT_TOLERANCE
+        )
+        .is_ok());
+        assert!(verify(
+            "other",
+            &timestamp,
+            &signature,
+            body.as_bytes(),
+            DEFAULT_TOLERANCE
+        )
+        .is_err());
+    }
+}
@@ -2,12 +2,21 @@ use std::{
     error::Error,
     fmt::{Display, Formatter},
     net::IpAddr,
+    time::{Duration, Instant},
 };
 
 use geo::Point;
+use rand::Rng;
+use rand_distr::Exp1;
 use serde::{ser::SerializeStruct, Serialize};
 
-use crate::{application::Application, application_runtime::ApplicationRuntime};
+use crate::{
+    application::{Application, DeploymentState},
+    application_runtime::ApplicationRuntime,
+    processing_queue::ProcessingQueue,
+    resources::Resources,
+    usage::Usage,
+};
 
 #[derive(Debug)]
 pub struct EdgeDataCenterError {
@@ -31,15 +40,18 @@ impl Error for EdgeDataCenterError {}
 #[derive(Debug, Clone)]
 pub struct EdgeDataCenter {
     application_runtime: ApplicationRuntime,
+    /// One server per whole CPU core of the capacity.
+    processing_queue: ProcessingQueue,
     id: u32,
     name: String,
     position: Point,
 }
 
 impl EdgeDataCenter {
-    pub fn new(id: u32, name: &str, position: Point) -> Self {
+    pub fn new(id: u32, name: &str, position: Point, capacity: Resources) -> Self {
         EdgeDataCenter {
-            application_runtime: ApplicationRuntime::new(),
+            application_runtime: ApplicationRuntime::new(capacity),
+            processing_queue: ProcessingQueue::new(capacity.get_cpu() as usize),
             id,
             name: name.to_string(),
             position,
@@ -50,8 +62,13 @@ impl EdgeDataCenter {
         self.id
     }
 
-    pub fn add_application(&mut self, application_id: u32) -> Result<u32, EdgeDataCenterError> {
-        let application = Application::new(application_id);
+    /// Deploys the application, failing if the edge data center lacks the resources it requires.
+    pub fn add_application(
+        &mut self,
+        application_id: u32,
+        requirements: Resources,
+    ) -> Result<u32, EdgeDataCenterError> {
+        let application = Application::new(application_id, requirements);
         match self
             .application_runtime
             .add_application(application.clone())
@@ -62,28 +79,83 @@ impl EdgeDataCenter {
         }
     }
 
+    /// Drains the application, it stops taking requests and terminates at the next tick.
     pub fn remove_application(&mut self, application_id: u32) -> Result<(), EdgeDataCenterError> {
-        let application = Application::new(application_id);
-        match self
-            .application_runtime
-            .remove_application(&application)
+        self.application_runtime
+            .drain_application(application_id)
             .map_err(|err| EdgeDataCenterError::new(format!("{}", err)))
-        {
-            Ok(_) => Ok(()),
-            Err(err) => Err(err),
-        }
+    }
+
+    pub fn set_cold_start(&mut self, cold_start: Duration) {
+        self.application_runtime.set_cold_start(cold_start);
+    }
+
+    /// Advances the lifecycle of the applications to `now`.
+    pub fn update_applications(&mut self, now: Instant) {
+        self.application_runtime.update(now);
+    }
+
+    /// How long a request arriving at `now` waits for the application to run,
+    /// `None` if the edge data center does not serve it.
+    pub fn get_readiness(&self, application_id: u32, now: Instant) -> Option<Duration> {
+        self.application_runtime.get_readiness(application_id, now)
+    }
+
+    /// Removes the application and hands it over with its usage history, e.g. to migrate it.
+    pub fn take_application(
+        &mut self,
+        application_id: u32,
+    ) -> Result<Application, EdgeDataCenterError> {
+        self.application_runtime
+            .take_application(application_id)
+            .map_err(|err| EdgeDataCenterError::new(format!("{}", err)))
+    }
+
+    /// Deploys an application that runs right away, e.g. one migrated from another edge data center.
+    pub fn put_application(
+        &mut self,
+        mut application: Application,
+    ) -> Result<(), EdgeDataCenterError> {
+        application.set_state(DeploymentState::Running);
+        self.application_runtime
+            .add_application(application)
+            .map_err(|err| EdgeDataCenterError::new(format!("{}", err)))
+    }
+
+    /// Whether an application requiring `requirements` can still be deployed.
+    pub fn fits(&self, requirements: &Resources) -> bool {
+        self.application_runtime.fits(requirements)
     }
 
     pub fn use_application(
         &mut self,
         ip_addr: IpAddr,
+        ran_id: u32,
         application: &Application,
-    ) -> Result<usize, EdgeDataCenterError> {
+    ) -> Result<u32, EdgeDataCenterError> {
         self.application_runtime
-            .use_application(ip_addr, application)
+            .use_application(ip_addr, ran_id, application)
             .map_err(|err| EdgeDataCenterError::new(format!("{}", err)))
     }
 
+    /// Queues a request arriving `arrival` after the start of the tick, the M/M/c way:
+    /// service times are exponentially distributed around `mean_service_time` and there is a server per core.
+    /// Returns the time the request spends waiting and being processed.
+    pub fn process_request(&mut self, arrival: Duration, mean_service_time: Duration) -> Duration {
+        let service_time = mean_service_time.mul_f64(rand::thread_rng().sample(Exp1));
+        self.processing_queue.process(arrival, service_time)
+    }
+
+    /// How long the servers are busy with the requests of this tick on average.
+    pub fn get_backlog(&self) -> Duration {
+        self.processing_queue.get_backlog()
+    }
+
+    /// Starts a new tick with no requests queued.
+    pub fn reset_processing_queue(&mut self) {
+        self.processing_queue.reset();
+    }
+
     pub fn contains_application(&self, id: &u32) -> bool {
         self.application_runtime.contains_application(id)
     }
@@ -105,6 +177,18 @@ impl EdgeDataCenter {
             Err(e) => Err(EdgeDataCenterError::new(format!("{}", e))),
         }
     }
+
+    /// The uses of an application from `since`, since the UNIX epoch, on.
+    pub fn get_usage_of_application(
+        &self,
+        application_id: u32,
+        since: Duration,
+    ) -> Result<Usage, EdgeDataCenterError> {
+        self.application_runtime
+            .get_application(application_id)
+            .map(|application| application.get_usage_since(since))
+            .map_err(|err| EdgeDataCenterError::new(format!("{}", err)))
+    }
 }
 
 impl Serialize for EdgeDataCenter {
@@ -112,11 +196,17 @@ impl Serialize for EdgeDataCenter {
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("EdgeDataCenter", 4)?;
+        let capacity = self.application_runtime.get_capacity();
+        let used = self.application_runtime.get_used_resources();
+        let mut state = serializer.serialize_struct("EdgeDataCenter", 8)?;
         state.serialize_field("id", &self.id)?;
         state.serialize_field("name", &self.name)?;
         state.serialize_field("x", &self.position.x())?;
         state.serialize_field("y", &self.position.y())?;
+        state.serialize_field("capacity", capacity)?;
+        state.serialize_field("used", &used)?;
+        state.serialize_field("utilisation", &used.utilisation(capacity))?;
+        state.serialize_field("servers", &self.processing_queue.get_servers())?;
         state.end()
     }
 }
@@ -125,91 +215,176 @@ impl Serialize for EdgeDataCenter {
 mod tests {
     use std::net::Ipv4Addr;
 
+    use crate::application::DEFAULT_REQUIREMENTS;
+
     use super::*;
 
+    fn capacity() -> Resources {
+        Resources::new(2.0, 1024)
+    }
+
     #[test]
     fn create_edge_datacenter() {
-        let eds = EdgeDataCenter::new(0, "Fredrik's EdgeDataCenter", Point::new(0., 0.));
+        let eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
         assert_eq!(eds.name, "Fredrik's EdgeDataCenter");
         assert_eq!(eds.position, Point::new(0., 0.));
     }
 
     #[test]
     fn add_application() {
-        let mut eds = EdgeDataCenter::new(0, "Fredrik's EdgeDataCenter", Point::new(0., 0.));
-        let res = eds.add_application(0);
+        let mut eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
+        let res = eds.add_application(0, DEFAULT_REQUIREMENTS);
         assert!(res.is_ok());
         assert_eq!(eds.application_runtime.num_applications(), 1);
     }
 
     #[test]
     fn add_application_already_present_should_fail() {
-        let mut eds = EdgeDataCenter::new(0, "Fredrik's EdgeDataCenter", Point::new(0., 0.));
-        let mut res = eds.add_application(0);
+        let mut eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
+        let mut res = eds.add_application(0, DEFAULT_REQUIREMENTS);
         assert!(res.is_ok());
         assert_eq!(eds.application_runtime.num_applications(), 1);
-        res = eds.add_application(0);
+        res = eds.add_application(0, DEFAULT_REQUIREMENTS);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn add_application_beyond_capacity_should_fail() {
+        let mut eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
+        assert!(eds.add_application(0, Resources::new(2.0, 512)).is_ok());
+        let res = eds.add_application(1, Resources::new(0.5, 512));
         assert!(res.is_err());
+        assert_eq!(eds.application_runtime.num_applications(), 1);
+
+        let json = serde_json::to_value(&eds).unwrap();
+        assert_eq!(json["used"]["cpu"], 2.0);
+        assert_eq!(json["utilisation"]["memory"], 0.5);
+    }
+
+    #[test]
+    fn processing_delay_grows_with_load() {
+        let mut eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
+        let mean_delay = |eds: &mut EdgeDataCenter, requests: u32| {
+            eds.reset_processing_queue();
+            (0..requests)
+                .map(|_| eds.process_request(Duration::ZERO, Duration::from_millis(20)))
+                .sum::<Duration>()
+                / requests
+        };
+        let light = (0..100).map(|_| mean_delay(&mut eds, 2)).sum::<Duration>() / 100;
+        let heavy = (0..100).map(|_| mean_delay(&mut eds, 40)).sum::<Duration>() / 100;
+        assert!(heavy > light * 4);
     }
 
     #[test]
     fn remove_application() {
-        let mut eds = EdgeDataCenter::new(0, "Fredrik's EdgeDataCenter", Point::new(0., 0.));
-        let res = eds.add_application(0);
+        let mut eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
+        let res = eds.add_application(0, DEFAULT_REQUIREMENTS);
         assert!(res.is_ok());
         assert_eq!(eds.application_runtime.num_applications(), 1);
 
         let res = eds.remove_application(0);
         assert!(res.is_ok());
+        assert!(eds.get_readiness(0, Instant::now()).is_none());
+        eds.update_applications(Instant::now());
+        eds.update_applications(Instant::now());
         assert_eq!(eds.application_runtime.num_applications(), 0);
     }
 
     #[test]
     fn remove_application_two_times() {
-        let mut eds = EdgeDataCenter::new(0, "Fredrik's EdgeDataCenter", Point::new(0., 0.));
-        let res = eds.add_application(0);
+        let mut eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
+        let res = eds.add_application(0, DEFAULT_REQUIREMENTS);
         assert!(res.is_ok());
         assert_eq!(eds.application_runtime.num_applications(), 1);
 
         let res = eds.remove_application(0);
         assert!(res.is_ok());
-        assert_eq!(eds.application_runtime.num_applications(), 0);
 
         let res = eds.remove_application(0);
         assert!(res.is_err());
-        assert_eq!(eds.application_runtime.num_applications(), 0);
     }
 
     #[test]
     fn use_application() {
-        let mut eds = EdgeDataCenter::new(0, "Fredrik's EdgeDataCenter", Point::new(0., 0.));
-        let application = Application::new(0);
-        let res = eds.add_application(0);
+        let mut eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+        let res = eds.add_application(0, DEFAULT_REQUIREMENTS);
 
         assert!(res.is_ok());
         assert_eq!(eds.application_runtime.num_applications(), 1);
 
         let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
-        let res = eds.use_application(ip_addr, &application);
+        let res = eds.use_application(ip_addr, 0, &application);
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), 1);
     }
 
     #[test]
     fn use_application_no_application_should_fail() {
-        let mut eds = EdgeDataCenter::new(0, "Fredrik's EdgeDataCenter", Point::new(0., 0.));
-        let application = Application::new(0);
+        let mut eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
         let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 
-        let res = eds.use_application(ip_addr, &application);
+        let res = eds.use_application(ip_addr, 0, &application);
         assert!(res.is_err());
     }
 
     #[test]
     fn contains_application() {
-        let mut eds = EdgeDataCenter::new(0, "Fredrik's EdgeDataCenter", Point::new(0., 0.));
-        let _application = Application::new(0);
-        let res = eds.add_application(0);
+        let mut eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
+        let _application = Application::new(0, DEFAULT_REQUIREMENTS);
+        let res = eds.add_application(0, DEFAULT_REQUIREMENTS);
 
         assert!(res.is_ok());
         assert_eq!(eds.application_runtime.num_applications(), 1);
@@ -220,7 +395,12 @@ mod tests {
 
     #[test]
     fn not_contain_application() {
-        let eds = EdgeDataCenter::new(0, "Fredrik's EdgeDataCenter", Point::new(0., 0.));
+        let eds = EdgeDataCenter::new(
+            0,
+            "Fredrik's EdgeDataCenter",
+            Point::new(0., 0.),
+            capacity(),
+        );
 
         let res = eds.contains_application(&0);
         assert!(!res);
@@ -0,0 +1,391 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+use crate::{mobile_network_core::MobileNetworkCore, network::Network};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct UserState {
+    x: f64,
+    y: f64,
+    ran_id: Option<u32>,
+}
+
+/// The parts of the emulator state that are streamed to observers, captured after a tick.
+#[derive(Debug, Clone, Default)]
+pub struct StateSnapshot {
+    users: HashMap<u32, UserState>,
+    placements: HashSet<(u32, u32)>,
+    edge_data_center_usage: HashMap<u32, u32>,
+}
+
+impl StateSnapshot {
+    pub fn new(mobile_network_core: &MobileNetworkCore, network: &Network) -> Self {
+        let mut users: HashMap<u32, UserState> = mobile_network_core
+            .get_all_users()
+            .into_iter()
+            .map(|user| {
+                let position = user.current_pos();
+                (
+                    user.get_id(),
+                    UserState {
+                        x: position.x(),
+                        y: position.y(),
+                        ran_id: None,
+                    },
+                )
+            })
+            .collect();
+        for ran in mobile_network_core.get_rans() {
+            for pdu_session in ran.get_current_connected_users() {
+                if let Some(user) = users.get_mut(&pdu_session.user().get_id()) {
+                    user.ran_id = Some(ran.get_id());
+                }
+            }
+        }
+
+        let mut placements = HashSet::new();
+        let mut edge_data_center_usage = HashMap::new();
+        for edge_data_center in network.get_edge_data_centers() {
+            let applications = edge_data_center.get_applications();
+            for application in applications.iter() {
+                placements.insert((edge_data_center.get_id(), application.id()));
+            }
+            edge_data_center_usage.insert(
+                edge_data_center.get_id(),
+                applications
+                    .iter()
+                    .map(|application| application.get_total_usage())
+                    .sum(),
+            );
+        }
+
+        Self {
+            users,
+            placements,
+            edge_data_center_usage,
+        }
+    }
+
+    /// Computes what changed going from `previous` to `self`.
+    pub fn delta(&self, previous: &StateSnapshot, tick: u64) -> StateDelta {
+        let mut delta = StateDelta::new(tick);
+
+        for (id, user) in self.users.iter() {
+            let previous_user = previous.users.get(id);
+            if previous_user.map(|p| (p.x, p.y)) != Some((user.x, user.y)) {
+                delta.user_positions.push(UserPosition {
+                    user_id: *id,
+                    x: user.x,
+                    y: user.y,
+                });
+            }
+            let previous_ran_id = previous_user.and_then(|p| p.ran_id);
+            match (previous_ran_id, user.ran_id) {
+                (None, Some(ran_id)) => delta.attached.push(UserAttachment {
+                    user_id: *id,
+                    ran_id,
+                }),
+                (Some(ran_id), None) => delta.detached.push(UserAttachment {
+                    user_id: *id,
+                    ran_id,
+                }),
+                (Some(source_ran_id), Some(target_ran_id)) if source_ran_id != target_ran_id => {
+                    delta.handovers.push(Handover {
+                        user_id: *id,
+                        source_ran_id,
+                        target_ran_id,
+                    })
+                }
+                _ => (),
+            }
+        }
+
+        for (edge_data_center_id, application_id) in
+            self.placements.difference(&previous.placements)
+        {
+            delta.placed_applications.push(ApplicationPlacement {
+                edge_data_center_id: *edge_data_center_id,
+                application_id: *application_id,
+            });
+        }
+        for (edge_data_center_id, application_id) in
+            previous.placements.difference(&self.placements)
+        {
+            delta.removed_applications.push(ApplicationPlacement {
+                edge_data_center_id: *edge_data_center_id,
+                application_id: *application_id,
+            });
+        }
+
+        for (edge_data_center_id, usage) in self.edge_data_center_usage.iter() {
+            let previous_usage = previous
+                .edge_data_center_usage
+                .get(edge_data_center_id)
+                .copied()
+                .unwrap_or(0);
+            delta.edge_data_center_load.push(EdgeDataCenterLoad {
+                edge_data_center_id: *edge_data_center_id,
+                requests: usage.saturating_sub(previous_usage),
+                applications: self
+                    .placements
+                    .iter()
+                    .filter(|(id, _)| id == edge_data_center_id)
+                    .count() as u32,
+            });
+        }
+
+        delta.sort();
+        delta
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UserPosition {
+    user_id: u32,
+    x: f64,
+    y: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UserAttachment {
+    user_id: u32,
+    ran_id: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Handover {
+    user_id: u32,
+    source_ran_id: u32,
+    target_ran_id: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApplicationPlacement {
+    edge_data_center_id: u32,
+    application_id: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EdgeDataCenterLoad {
+    edge_data_center_id: u32,
+    requests: u32,
+    applications: u32,
+}
+
+/// Everything that changed during a single tick of the emulator.
+#[derive(Debug, Clone, Serialize)]
+pub struct StateDelta {
+    tick: u64,
+    timestamp: u64,
+    user_positions: Vec<UserPosition>,
+    attached: Vec<UserAttachment>,
+    detached: Vec<UserAttachment>,
+    handovers: Vec<Handover>,
+    placed_applications: Vec<ApplicationPlacement>,
+    removed_applications: Vec<ApplicationPlacement>,
+    edge_data_center_load: Vec<EdgeDataCenterLoad>,
+}
+
+impl StateDelta {
+    fn new(tick: u64) -> Self {
+        Self {
+            tick,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            user_positions: Vec::new(),
+            attached: Vec::new(),
+            detached: Vec::new(),
+            handovers: Vec::new(),
+            placed_applications: Vec::new(),
+            removed_applications: Vec::new(),
+            edge_data_center_load: Vec::new(),
+        }
+    }
+
+    fn sort(&mut self) {
+        self.user_positions.sort_by_key(|user| user.user_id);
+        self.attached.sort_by_key(|user| user.user_id);
+        self.detached.sort_by_key(|user| user.user_id);
+        self.handovers.sort_by_key(|handover| handover.user_id);
+        self.placed_applications
+            .sort_by_key(|p| (p.edge_data_center_id, p.application_id));
+        self.removed_applications
+            .sort_by_key(|p| (p.edge_data_center_id, p.application_id));
+        self.edge_data_center_load
+            .sort_by_key(|load| load.edge_data_center_id);
+    }
+
+    pub fn get_attached(&self) -> &[UserAttachment] {
+        &self.attached
+    }
+
+    pub fn get_detached(&self) -> &[UserAttachment] {
+        &self.detached
+    }
+
+    pub fn get_handovers(&self) -> &[Handover] {
+        &self.handovers
+    }
+}
+
+/// Keeps the last published snapshot and fans out per-tick deltas to every listener.
+pub struct LiveState {
+    sender: Sender<StateDelta>,
+    snapshot: StateSnapshot,
+    tick: u64,
+}
+
+impl LiveState {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _receiver) = broadcast::channel(capacity);
+        Self {
+            sender,
+            snapshot: StateSnapshot::default(),
+            tick: 0,
+        }
+    }
+
+    pub fn subscribe(&self) -> Receiver<StateDelta> {
+        self.sender.subscribe()
+    }
+
+    /// The full current state, expressed as a delta from an empty emulator.
+    pub fn current_state(&self) -> StateDelta {
+        self.snapshot.delta(&StateSnapshot::default(), self.tick)
+    }
+
+    /// Diffs `snapshot` against the previous one and pushes the result to all listeners.
+    pub fn publish(&mut self, snapshot: StateSnapshot) -> StateDelta {
+        self.tick += 1;
+        let delta = snapshot.delta(&self.snapshot, self.tick);
+        self.snapshot = snapshot;
+        // Sending only fails when nobody is listening, which is fine.
+        let _ = self.sender.send(delta.clone());
+        delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(
+        users: &[(u32, f64, Option<u32>)],
+        placements: &[(u32, u32, u32)],
+    ) -> StateSnapshot {
+        StateSnapshot {
+            users: users
+                .iter()
+                .map(|(id, x, ran_id)| {
+                    (
+                        *id,
+                        UserState {
+                            x: *x,
+                            y: 0.0,
+                            ran_id: *ran_id,
+                        },
+                    )
+                })
+                .collect(),
+            placements: placements
+                .iter()
+                .map(|(edc_id, application_id, _)| (*edc_id, *application_id))
+                .collect(),
+            edge_data_center_usage: placements
+                .iter()
+                .map(|(edc_id, _, usage)| (*edc_id, *usage))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn delta_attach_detach_handover() {
+        let previous = snapshot(&[(0, 0.0, None), (1, 0.0, Some(1)), (2, 0.0, Some(1))], &[]);
+        let current = snapshot(&[(0, 0.0, Some(1)), (1, 0.0, None), (2, 0.0, Some(2))], &[]);
+
+        let delta = current.delta(&previous, 1);
+
+        assert_eq!(
+            delta.attached,
+            vec![UserAttachment {
+                user_id: 0,
+                ran_id: 1
+            }]
+        );
+        assert_eq!(
+            delta.detached,
+            vec![UserAttachment {
+                user_id: 1,
+                ran_id: 1
+            }]
+        );
+        assert_eq!(
+            delta.handovers,
+            vec![Handover {
+                user_id: 2,
+                source_ran_id: 1,
+                target_ran_id: 2
+            }]
+        );
+        assert!(delta.user_positions.is_empty());
+    }
+
+    #[test]
+    fn delta_positions_placements_and_load() {
+        let previous = snapshot(&[(0, 0.0, None)], &[(0, 0, 10)]);
+        let current = snapshot(&[(0, 1.0, None)], &[(1, 0, 4)]);
+
+        let delta = current.delta(&previous, 1);
+
+        assert_eq!(
+            delta.user_positions,
+            vec![UserPosition {
+                user_id: 0,
+                x: 1.0,
+                y: 0.0
+            }]
+        );
+        assert_eq!(
+            delta.placed_applications,
+            vec![ApplicationPlacement {
+                edge_data_center_id: 1,
+                application_id: 0
+            }]
+        );
+        assert_eq!(
+            delta.removed_applications,
+            vec![ApplicationPlacement {
+                edge_data_center_id: 0,
+                application_id: 0
+            }]
+        );
+        assert_eq!(
+            delta.edge_data_center_load,
+            vec![EdgeDataCenterLoad {
+                edge_data_center_id: 1,
+                requests: 4,
+                applications: 1
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_reaches_subscribers() {
+        let mut live_state = LiveState::new(8);
+        let mut receiver = live_state.subscribe();
+
+        live_state.publish(snapshot(&[(0, 1.0, Some(0))], &[]));
+
+        let delta = receiver.recv().await.unwrap();
+        assert_eq!(delta.tick, 1);
+        assert_eq!(delta.attached.len(), 1);
+        assert_eq!(live_state.current_state().user_positions.len(), 1);
+    }
+}
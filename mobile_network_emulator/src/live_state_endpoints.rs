@@ -0,0 +1,74 @@
+use actix_web::{
+    get,
+    web::{Bytes, Data, Json},
+    HttpResponse, Responder,
+};
+use futures::{stream, StreamExt};
+use log::warn;
+use serde::Serialize;
+use tokio::sync::{broadcast::error::RecvError, RwLock};
+
+use crate::live_state::LiveState;
+
+pub struct LiveStateWrapper {
+    pub live_state: RwLock<LiveState>,
+}
+
+impl LiveStateWrapper {
+    pub fn new(live_state: LiveState) -> Self {
+        LiveStateWrapper {
+            live_state: RwLock::new(live_state),
+        }
+    }
+}
+
+fn server_sent_event<T: Serialize>(event: &str, data: &T) -> Bytes {
+    Bytes::from(format!(
+        "event: {}\ndata: {}\n\n",
+        event,
+        serde_json::to_string(data).unwrap()
+    ))
+}
+
+#[get("/state")]
+pub async fn get_state(live_state_wrapper: Data<LiveStateWrapper>) -> impl Responder {
+    Json(live_state_wrapper.live_state.read().await.current_state())
+}
+
+/// Server-Sent Events stream of per-tick state deltas.
+/// The first message is the full current state, every following message is a delta.
+#[get("/state/stream")]
+pub async fn get_state_stream(live_state_wrapper: Data<LiveStateWrapper>) -> HttpResponse {
+    let (initial_state, receiver) = {
+        let live_state = live_state_wrapper.live_state.read().await;
+        (live_state.current_state(), live_state.subscribe())
+    };
+
+    let initial = stream::once(async move {
+        Ok::<_, actix_web::Error>(server_sent_event("state", &initial_state))
+    });
+    let deltas = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(delta) => {
+                    return Some((
+                        Ok::<_, actix_web::Error>(server_sent_event("delta", &delta)),
+                        receiver,
+                    ))
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "live state listener lagged behind, skipped {} deltas",
+                        skipped
+                    )
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(initial.chain(deltas))
+}
@@ -1,45 +1,112 @@
+mod analytics_exposure;
+mod analytics_exposure_endpoints;
 mod application;
+mod application_catalogue;
 mod application_runtime;
+mod area_of_interest;
+mod as_session_with_qos;
+mod as_session_with_qos_endpoints;
+mod authorization;
+mod authorization_endpoints;
+mod delivery;
 mod edge_data_center;
+mod live_state;
+mod live_state_endpoints;
+mod metrics;
+mod metrics_endpoints;
+mod migration;
 mod mobile_network_core;
 mod mobile_network_core_endpoints;
 mod mobile_network_exposure;
 mod mobile_network_exposure_endpoints;
+mod monitoring_event;
+mod monitoring_event_endpoints;
 mod network;
 mod network_endpoints;
+mod northbound;
 mod pdu_session;
+mod processing_queue;
+mod qos;
 mod ran;
+mod resources;
+mod scenario;
+mod topology;
+mod traffic_influence;
+mod traffic_influence_endpoints;
+mod usage;
 mod user;
 use std::{
+    fs,
     iter::repeat_with,
     net::{IpAddr, Ipv4Addr},
     ops::Range,
+    path::PathBuf,
+    time::Duration,
 };
 
 use actix_web::{
+    middleware::from_fn,
     web::{self, Data},
     App, HttpServer,
 };
+use analytics_exposure_endpoints::{
+    delete_analytics_exposure_subscription, fetch_analytics, get_analytics_exposure_subscription,
+    get_analytics_exposure_subscriptions, post_analytics_exposure_subscription,
+    put_analytics_exposure_subscription,
+};
+use application::Application;
+use application_catalogue::{ApplicationCatalogue, ApplicationProfile};
+use area_of_interest::AreaOfInterest;
+use as_session_with_qos_endpoints::{
+    delete_as_session_with_qos_subscription, get_as_session_with_qos_subscription,
+    get_as_session_with_qos_subscriptions, post_as_session_with_qos_subscription,
+    put_as_session_with_qos_subscription,
+};
+use authorization::Authorization;
+use authorization_endpoints::{authenticate, post_token};
 use edge_data_center::EdgeDataCenter;
 use geo::Point;
+use live_state::LiveState;
+use live_state_endpoints::{get_state, get_state_stream, LiveStateWrapper};
+use log::{error, info, warn};
+use metrics::Metrics;
+use metrics_endpoints::get_metrics;
 use mobile_network_core::MobileNetworkCore;
 use mobile_network_core_endpoints::{
     get_connected_users, get_rans, get_users, update_user_positions, MobileNetworkCoreWrapper,
 };
-use mobile_network_exposure::MobileNetworkExposure;
+use mobile_network_exposure::{MobileNetworkExposure, Subscription};
 use mobile_network_exposure_endpoints::{
-    get_events, get_subscribers, post_subscribers, publish_events, MobileNetworkExposureWrapper,
+    delete_subscriber, dispatch_events, get_dead_letters, get_delivery_status,
+    get_delivery_statuses, get_events, get_subscriber, get_subscriber_stream,
+    get_subscriber_websocket, get_subscribers, patch_subscriber, post_subscribers, publish_events,
+    retry_deliveries, MobileNetworkExposureWrapper,
+};
+use monitoring_event_endpoints::{
+    delete_monitoring_event_subscription, get_monitoring_event_subscription,
+    get_monitoring_event_subscriptions, post_monitoring_event_subscription,
+    put_monitoring_event_subscription,
 };
 use network::Network;
 use network_endpoints::{
-    add_application, delete_application, get_applications, get_edge_data_centers,
-    get_total_application_usage, NetworkWrapper,
+    add_application, delete_application, delete_application_profile, get_application_profile,
+    get_application_profiles, get_application_replicas, get_application_usage, get_applications,
+    get_cloud, get_edge_data_centers, get_migrations, get_topology, get_total_application_usage,
+    migrate_application, put_application_profile, NetworkWrapper,
 };
 use poisson_diskus::bridson;
 use ran::Ran;
 use rand::prelude::*;
+use resources::Resources;
+use scenario::Scenario;
 use simple_logger::SimpleLogger;
 use structopt::StructOpt;
+use topology::{Topology, DEFAULT_CLOUD_LATENCY};
+use traffic_influence_endpoints::{
+    delete_traffic_influence_subscription, get_traffic_influence_subscription,
+    get_traffic_influence_subscriptions, post_traffic_influence_subscription,
+    put_traffic_influence_subscription,
+};
 use user::User;
 
 fn random_point(rng: &mut ThreadRng, range: &Range<f64>) -> Point {
@@ -57,6 +124,47 @@ fn poisson_points(range: &Range<f64>, rmin: f32) -> Vec<Point> {
         .collect()
 }
 
+/// Sets up what restored subscriptions need in the core and the network again.
+fn reinstall_subscriptions(
+    mobile_network_exposure: &MobileNetworkExposure,
+    mobile_network_core: &mut MobileNetworkCore,
+    network: &mut Network,
+) {
+    for subscriber in mobile_network_exposure.get_subscribers() {
+        let id = subscriber.get_id();
+        match subscriber.get_subscription() {
+            Subscription::Event(event_subscriber) => {
+                if let Some(area) = event_subscriber.get_area() {
+                    mobile_network_core.add_area_of_interest(AreaOfInterest::new(id, area.clone()));
+                }
+            }
+            Subscription::TrafficInfluence { subscription, .. } => match subscription.rule(id) {
+                Ok(rule) => network.add_traffic_influence_rule(rule),
+                Err(err) => warn!("traffic influence subscription {} not routed: {}", id, err),
+            },
+            // The UEs get new PDU sessions, so the flow follows the UE rather than its old address.
+            Subscription::AsSessionWithQos {
+                subscription,
+                ue_id,
+                ..
+            } => match (subscription.qos_flow(id), ue_id) {
+                (Some(qos_flow), Some(ue_id)) => {
+                    mobile_network_core.add_user_qos_flow(*ue_id, qos_flow)
+                }
+                (None, _) => error!(
+                    "QoS flow of subscription {} not set up again: unknown QoS reference",
+                    id
+                ),
+                (_, None) => warn!(
+                    "QoS flow of subscription {} not set up again: unknown UE",
+                    id
+                ),
+            },
+            _ => (),
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "mn_system", about = "Backend for mobile_network_system")]
 struct Opt {
@@ -68,6 +176,17 @@ struct Opt {
 
     #[structopt(short, long, default_value = "mongodb://localhost:27017/")]
     db_connection_string: String,
+
+    /// JSON file listing the AF clients as `[{"client_id": ..., "client_secret": ...}]`,
+    /// operator clients with `"operator": true`. The exposure API requires access tokens only if it is given.
+    #[structopt(long, parse(from_os_str))]
+    af_clients: Option<PathBuf>,
+
+    /// JSON file describing the scenario, e.g. the transport network as `{"topology": {"links": [...]}}`
+    /// the cloud as `{"cloud": {"latency_ms": ..., "application_ids": [...]}}`
+    /// or the application catalogue as `{"applications": [{"id": ..., "name": ..., "image": ...}]}`.
+    #[structopt(long, parse(from_os_str))]
+    scenario: Option<PathBuf>,
 }
 
 #[tokio::main]
@@ -85,12 +204,35 @@ async fn main() -> std::io::Result<()> {
 
     let db_client_data = Data::new(database);
 
+    let af_clients = match &opts.af_clients {
+        Some(path) => serde_json::from_str(&fs::read_to_string(path)?)?,
+        None => Vec::new(),
+    };
+    let authorization = Authorization::new(af_clients);
+    if authorization.is_enabled() {
+        info!(
+            "exposure API requires access tokens, {} AF clients configured",
+            authorization.get_number_of_clients()
+        );
+    } else {
+        info!("exposure API is open, no AF clients configured");
+    }
+    let authorization_data = Data::new(authorization);
+
+    let mut scenario: Scenario = match &opts.scenario {
+        Some(path) => serde_json::from_str(&fs::read_to_string(path)?)?,
+        None => Scenario::default(),
+    };
+
     let bounds = -500.0..500.;
     let num_users = 128;
     let user_velocdity = 1.5;
     let _num_rans = 16;
-    let num_edge_data_centers = 8;
+    let _num_edge_data_centers = 8;
     let num_applications = 8;
+    let edge_data_center_capacity = Resources::new(8.0, 16384);
+    let cloud_capacity = Resources::new(256.0, 1 << 20);
+    let latency_per_distance = Duration::from_millis(1500);
 
     let mut rng = rand::thread_rng();
 
@@ -105,7 +247,7 @@ async fn main() -> std::io::Result<()> {
     //     .map(|id| (id, random_point(&mut rng, &bounds)))
     //     .map(|(id, point)| Ran::new(id, point, 100.0))
     //     .collect();
-    let rans = poisson_points(&(1000.0..1000.0), 150.0)
+    let rans: Vec<Ran> = poisson_points(&(1000.0..1000.0), 150.0)
         .into_iter()
         .enumerate()
         .map(|(id, point)| {
@@ -113,6 +255,10 @@ async fn main() -> std::io::Result<()> {
             Ran::new(id as u32, p, 100.0)
         })
         .collect();
+    let ran_positions: Vec<(u32, Point)> = rans
+        .iter()
+        .map(|ran| (ran.get_id(), ran.get_position()))
+        .collect();
 
     //TODO: Make sure that we only get unique ip addresses
     let ip_addresses = repeat_with(|| (rng.gen(), rng.gen(), rng.gen(), rng.gen()))
@@ -122,13 +268,15 @@ async fn main() -> std::io::Result<()> {
         })
         .collect();
 
-    let mnc = MobileNetworkCore::new(rans, users, ip_addresses);
-    let mnc_wrapper = MobileNetworkCoreWrapper::new(mnc);
-    let mnc_wrapper_data = Data::new(mnc_wrapper);
+    let mut mnc = MobileNetworkCore::new(rans, users, ip_addresses);
+    mnc.resume_sequence_numbers(&db_client_data).await;
+    let core_events = mnc.subscribe_events();
 
-    let mnce = MobileNetworkExposure::new();
-    let mnce_wrapper = MobileNetworkExposureWrapper::new(mnce);
-    let mnce_wrapper_data = Data::new(mnce_wrapper);
+    let metrics = Metrics::new();
+    let metrics_data = Data::new(metrics.clone());
+
+    let mut mnce = MobileNetworkExposure::new(metrics);
+    mnce.restore_subscriptions(&db_client_data).await;
 
     // let mut edge_data_centers: Vec<EdgeDataCenter> = (0u32..)
     //     .take(num_edge_data_centers)
@@ -142,26 +290,135 @@ async fn main() -> std::io::Result<()> {
         .enumerate()
         .map(|(id, point)| {
             let p = Point::new(point.x() - 500.0, point.y() - 500.0);
-            EdgeDataCenter::new(id as u32, &format!("edc: {}", id), p)
+            EdgeDataCenter::new(
+                id as u32,
+                &format!("edc: {}", id),
+                p,
+                edge_data_center_capacity,
+            )
         })
         .collect();
 
-    for id in 0..num_applications {
-        edge_data_centers[0].add_application(id).unwrap();
+    if let Some(cold_start) = scenario.get_cold_start() {
+        for edge_data_center in edge_data_centers.iter_mut() {
+            edge_data_center.set_cold_start(cold_start);
+        }
     }
 
-    let network = Network::new(edge_data_centers);
+    let catalogue = ApplicationCatalogue::new(scenario.take_applications().unwrap_or_else(|| {
+        (0..num_applications)
+            .map(|id| {
+                ApplicationProfile::new(
+                    id,
+                    &format!("application-{}", id),
+                    &format!("mn-system/application-{}:latest", id),
+                )
+            })
+            .collect()
+    }));
+    for profile in catalogue.get_profiles() {
+        profile.validate().map_err(std::io::Error::other)?;
+    }
+
+    // The initial placement is running when the emulation starts.
+    for profile in catalogue.get_profiles() {
+        edge_data_centers[0]
+            .put_application(Application::new(profile.id(), *profile.get_requirements()))
+            .map_err(std::io::Error::other)?;
+    }
+
+    let topology = match scenario.take_links() {
+        Some(links) => {
+            let topology = Topology::new(links).map_err(std::io::Error::other)?;
+            let ran_ids: Vec<u32> = ran_positions.iter().map(|(id, _)| *id).collect();
+            let edge_data_center_ids: Vec<u32> = edge_data_centers
+                .iter()
+                .map(|edge_data_center| edge_data_center.get_id())
+                .collect();
+            topology
+                .validate(&ran_ids, &edge_data_center_ids)
+                .map_err(std::io::Error::other)?;
+            topology
+        }
+        None => {
+            let edge_data_center_positions: Vec<(u32, Point)> = edge_data_centers
+                .iter()
+                .map(|edge_data_center| {
+                    (edge_data_center.get_id(), *edge_data_center.get_position())
+                })
+                .collect();
+            let cloud_latency = scenario
+                .get_cloud_latency()
+                .unwrap_or(DEFAULT_CLOUD_LATENCY);
+            Topology::full_mesh(
+                &ran_positions,
+                &edge_data_center_positions,
+                latency_per_distance,
+                cloud_latency,
+            )
+        }
+    };
+
+    let cloud_application_ids = scenario.take_cloud_application_ids();
+    let mut cloud = EdgeDataCenter::new(u32::MAX, "cloud", Point::new(0.0, 0.0), cloud_capacity);
+    for profile in catalogue.get_profiles().iter().filter(|profile| {
+        cloud_application_ids
+            .as_ref()
+            .is_none_or(|ids| ids.contains(&profile.id()))
+    }) {
+        cloud
+            .put_application(Application::new(profile.id(), *profile.get_requirements()))
+            .map_err(std::io::Error::other)?;
+    }
+
+    let mut network = Network::new(edge_data_centers, topology);
+    network.set_catalogue(catalogue);
+    network.set_cloud(cloud, cloud_application_ids);
+    network.set_request_timeout(scenario.get_request_timeout());
+    network.set_replica_selection(scenario.get_replica_selection());
+    reinstall_subscriptions(&mnce, &mut mnc, &mut network);
+    // Catch up on events stored after the last notification before the restart.
+    mnce.publish_events(&db_client_data).await;
+
+    let mnc_wrapper = MobileNetworkCoreWrapper::new(mnc);
+    let mnc_wrapper_data = Data::new(mnc_wrapper);
+
+    let mnce_wrapper = MobileNetworkExposureWrapper::new(mnce);
+    let mnce_wrapper_data = Data::new(mnce_wrapper);
+    tokio::spawn(dispatch_events(
+        mnce_wrapper_data.clone(),
+        mnc_wrapper_data.clone(),
+        core_events,
+        db_client_data.clone(),
+    ));
+    tokio::spawn(retry_deliveries(mnce_wrapper_data.clone()));
+
     let network_wrapper = NetworkWrapper::new(network);
     let network_wrapper_data = Data::new(network_wrapper);
 
+    let live_state_wrapper = LiveStateWrapper::new(LiveState::new(64));
+    let live_state_wrapper_data = Data::new(live_state_wrapper);
+
     HttpServer::new(move || {
         let cors = actix_cors::Cors::permissive();
         App::new()
+            .service(get_metrics)
+            .service(web::scope("/oauth2").service(post_token))
             .service(
                 web::scope("/network")
                     .service(get_edge_data_centers)
+                    .service(get_topology)
+                    .service(get_cloud)
                     .service(get_applications)
+                    .service(get_application_profiles)
+                    .service(get_application_profile)
+                    .service(put_application_profile)
+                    .service(delete_application_profile)
+                    .service(get_application_replicas)
+                    .service(migrate_application)
+                    .service(get_migrations)
                     .service(get_total_application_usage)
+                    .service(get_application_usage)
                     .service(add_application)
                     .service(delete_application),
             )
@@ -172,17 +429,55 @@ async fn main() -> std::io::Result<()> {
                     .service(get_rans)
                     .service(update_user_positions),
             )
+            .service(
+                web::scope("/live")
+                    .service(get_state)
+                    .service(get_state_stream),
+            )
             .service(
                 web::scope("/mobile_network_exposure")
+                    .wrap(from_fn(authenticate))
                     .service(get_events)
                     .service(get_subscribers)
                     .service(post_subscribers)
-                    .service(publish_events),
+                    .service(get_subscriber)
+                    .service(patch_subscriber)
+                    .service(delete_subscriber)
+                    .service(get_subscriber_stream)
+                    .service(get_subscriber_websocket)
+                    .service(publish_events)
+                    .service(get_delivery_statuses)
+                    .service(get_delivery_status)
+                    .service(get_dead_letters)
+                    .service(get_monitoring_event_subscriptions)
+                    .service(post_monitoring_event_subscription)
+                    .service(get_monitoring_event_subscription)
+                    .service(put_monitoring_event_subscription)
+                    .service(delete_monitoring_event_subscription)
+                    .service(get_analytics_exposure_subscriptions)
+                    .service(post_analytics_exposure_subscription)
+                    .service(get_analytics_exposure_subscription)
+                    .service(put_analytics_exposure_subscription)
+                    .service(delete_analytics_exposure_subscription)
+                    .service(fetch_analytics)
+                    .service(get_traffic_influence_subscriptions)
+                    .service(post_traffic_influence_subscription)
+                    .service(get_traffic_influence_subscription)
+                    .service(put_traffic_influence_subscription)
+                    .service(delete_traffic_influence_subscription)
+                    .service(get_as_session_with_qos_subscriptions)
+                    .service(post_as_session_with_qos_subscription)
+                    .service(get_as_session_with_qos_subscription)
+                    .service(put_as_session_with_qos_subscription)
+                    .service(delete_as_session_with_qos_subscription),
             )
             .app_data(network_wrapper_data.clone())
             .app_data(mnc_wrapper_data.clone())
             .app_data(mnce_wrapper_data.clone())
+            .app_data(live_state_wrapper_data.clone())
+            .app_data(metrics_data.clone())
             .app_data(db_client_data.clone())
+            .app_data(authorization_data.clone())
             .wrap(cors)
     })
     .bind((opts.host, opts.port))?
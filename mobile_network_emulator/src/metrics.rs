@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::{
+    live_state::StateDelta,
+    mobile_network_core::MobileNetworkCore,
+    network::{NetworkLogEntry, RequestOutcome},
+};
+
+/// Prometheus metrics for the emulator.
+/// Cloning is cheap, every clone reports into the same registry.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    connected_users: IntGauge,
+    orphaned_users: IntGauge,
+    users_per_ran: IntGaugeVec,
+    handovers: IntCounter,
+    pdn_connections_created: IntCounter,
+    pdn_connections_released: IntCounter,
+    application_requests: IntCounterVec,
+    application_delay: HistogramVec,
+    application_slo_violations: IntCounterVec,
+    webhook_deliveries: IntCounterVec,
+    tick_duration: Histogram,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("mn_system".to_string()), None).unwrap();
+
+        let connected_users =
+            IntGauge::new("connected_users", "Users with an active PDU session").unwrap();
+        let orphaned_users =
+            IntGauge::new("orphaned_users", "Users not connected to any RAN").unwrap();
+        let users_per_ran = IntGaugeVec::new(
+            Opts::new("users_per_ran", "Users connected to each RAN"),
+            &["ran_id"],
+        )
+        .unwrap();
+        let handovers = IntCounter::new("handovers_total", "Handovers between RANs").unwrap();
+        let pdn_connections_created =
+            IntCounter::new("pdn_connections_created_total", "PDN connections created").unwrap();
+        let pdn_connections_released =
+            IntCounter::new("pdn_connections_released_total", "PDN connections released").unwrap();
+        let application_requests = IntCounterVec::new(
+            Opts::new(
+                "application_requests_total",
+                "Requests to an application by how they ended",
+            ),
+            &["data_center", "application_id", "outcome"],
+        )
+        .unwrap();
+        let application_delay = HistogramVec::new(
+            HistogramOpts::new(
+                "application_delay_seconds",
+                "Delay experienced by requests to an application that succeeded",
+            )
+            .buckets(exponential_buckets(0.001, 2.0, 22).unwrap()),
+            &["data_center", "application_id"],
+        )
+        .unwrap();
+        let application_slo_violations = IntCounterVec::new(
+            Opts::new(
+                "application_slo_violations_total",
+                "Requests to an application that did not succeed within its latency SLO",
+            ),
+            &["application_id"],
+        )
+        .unwrap();
+        let webhook_deliveries = IntCounterVec::new(
+            Opts::new(
+                "webhook_deliveries_total",
+                "Notifications posted to subscribers by the exposure function",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let tick_duration = Histogram::with_opts(
+            HistogramOpts::new("tick_duration_seconds", "Wall-clock time spent on a tick")
+                .buckets(exponential_buckets(0.001, 2.0, 14).unwrap()),
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(connected_users.clone()))
+            .unwrap();
+        registry.register(Box::new(orphaned_users.clone())).unwrap();
+        registry.register(Box::new(users_per_ran.clone())).unwrap();
+        registry.register(Box::new(handovers.clone())).unwrap();
+        registry
+            .register(Box::new(pdn_connections_created.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pdn_connections_released.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(application_requests.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(application_delay.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(application_slo_violations.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(webhook_deliveries.clone()))
+            .unwrap();
+        registry.register(Box::new(tick_duration.clone())).unwrap();
+
+        Self {
+            registry,
+            connected_users,
+            orphaned_users,
+            users_per_ran,
+            handovers,
+            pdn_connections_created,
+            pdn_connections_released,
+            application_requests,
+            application_delay,
+            application_slo_violations,
+            webhook_deliveries,
+            tick_duration,
+        }
+    }
+
+    /// Records everything that happened during a tick.
+    pub fn observe_tick(
+        &self,
+        mobile_network_core: &MobileNetworkCore,
+        delta: &StateDelta,
+        network_logs: &[NetworkLogEntry],
+        duration: Duration,
+    ) {
+        let connected_users = mobile_network_core.get_connected_users().len();
+        let all_users = mobile_network_core.get_all_users().len();
+        self.connected_users.set(connected_users as i64);
+        self.orphaned_users
+            .set(all_users.saturating_sub(connected_users) as i64);
+
+        self.users_per_ran.reset();
+        for ran in mobile_network_core.get_rans() {
+            self.users_per_ran
+                .with_label_values(&[&ran.get_id().to_string()])
+                .set(ran.get_current_connected_users().len() as i64);
+        }
+
+        self.handovers.inc_by(delta.get_handovers().len() as u64);
+        self.pdn_connections_created
+            .inc_by(delta.get_attached().len() as u64);
+        self.pdn_connections_released
+            .inc_by(delta.get_detached().len() as u64);
+
+        for network_log in network_logs {
+            let labels = [
+                network_log.get_data_center().to_string(),
+                network_log.get_application_id().to_string(),
+            ];
+            let labels = [labels[0].as_str(), labels[1].as_str()];
+            let outcome = network_log.get_outcome();
+            self.application_requests
+                .with_label_values(&[labels[0], labels[1], outcome.as_str()])
+                .inc();
+            if let (RequestOutcome::Success, Some(delay)) = (outcome, network_log.get_delay()) {
+                self.application_delay
+                    .with_label_values(&labels)
+                    .observe(delay.as_secs_f64());
+            }
+            if network_log.get_within_slo() == Some(false) {
+                self.application_slo_violations
+                    .with_label_values(&[labels[1]])
+                    .inc();
+            }
+        }
+
+        self.tick_duration.observe(duration.as_secs_f64());
+    }
+
+    pub fn record_webhook_delivery(&self, success: bool) {
+        let outcome = if success { "success" } else { "failure" };
+        self.webhook_deliveries.with_label_values(&[outcome]).inc();
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn gather(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_webhook_delivery() {
+        let metrics = Metrics::new();
+        metrics.record_webhook_delivery(true);
+        metrics.record_webhook_delivery(false);
+        metrics.record_webhook_delivery(false);
+
+        let output = metrics.gather();
+
+        assert!(output.contains("mn_system_webhook_deliveries_total{outcome=\"success\"} 1"));
+        assert!(output.contains("mn_system_webhook_deliveries_total{outcome=\"failure\"} 2"));
+    }
+
+    #[test]
+    fn clones_share_registry() {
+        let metrics = Metrics::new();
+        metrics.clone().handovers.inc_by(3);
+
+        assert!(metrics.gather().contains("mn_system_handovers_total 3"));
+    }
+}
@@ -0,0 +1,11 @@
+use actix_web::{get, web::Data, HttpResponse};
+
+use crate::metrics::Metrics;
+
+/// Exposes all metrics in the Prometheus text format.
+#[get("/metrics")]
+pub async fn get_metrics(metrics: Data<Metrics>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(metrics.gather())
+}
@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+
+use mobile_network_core_event::{ApplicationMigrationInformation, MigrationStatus};
+use serde::{Deserialize, Serialize};
+
+/// State an application carries over to its new edge data center when neither the request nor its profile give a size.
+pub const DEFAULT_STATE_SIZE_MB: u64 = 256;
+
+/// How long an application is unavailable while switching over when no downtime is given.
+pub const DEFAULT_DOWNTIME: Duration = Duration::from_millis(500);
+
+/// Body of `POST /network/applications/{id}/migrate`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MigrationRequest {
+    pub source_edge_data_center_id: u32,
+    pub target_edge_data_center_id: u32,
+    /// In MB, the one of the application profile if not given.
+    pub state_size: Option<u64>,
+    /// In milliseconds.
+    pub downtime: Option<u64>,
+}
+
+/// An application moving between edge data centers.
+/// Its state is copied over the backhaul while the source keeps serving, then the application
+/// is interrupted for the downtime before the target takes over with the usage history of the source.
+#[derive(Debug, Clone, Serialize)]
+pub struct Migration {
+    id: u32,
+    application_id: u32,
+    source_edge_data_center_id: u32,
+    target_edge_data_center_id: u32,
+    status: MigrationStatus,
+    /// In MB.
+    state_size: u64,
+    /// In milliseconds.
+    transfer_time: u64,
+    /// In milliseconds.
+    downtime: u64,
+    #[serde(skip)]
+    started: Instant,
+}
+
+impl Migration {
+    /// `state_size` is the one the request gives or else the one of the application profile.
+    pub fn new(
+        id: u32,
+        application_id: u32,
+        request: &MigrationRequest,
+        state_size: u64,
+        transfer_time: Duration,
+        started: Instant,
+    ) -> Self {
+        Migration {
+            id,
+            application_id,
+            source_edge_data_center_id: request.source_edge_data_center_id,
+            target_edge_data_center_id: request.target_edge_data_center_id,
+            status: MigrationStatus::Transferring,
+            state_size,
+            transfer_time: transfer_time.as_millis() as u64,
+            downtime: request
+                .downtime
+                .map_or(DEFAULT_DOWNTIME, Duration::from_millis)
+                .as_millis() as u64,
+            started,
+        }
+    }
+
+    pub fn get_application_id(&self) -> u32 {
+        self.application_id
+    }
+
+    pub fn get_source_edge_data_center_id(&self) -> u32 {
+        self.source_edge_data_center_id
+    }
+
+    pub fn get_target_edge_data_center_id(&self) -> u32 {
+        self.target_edge_data_center_id
+    }
+
+    pub fn get_status(&self) -> MigrationStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: MigrationStatus) {
+        self.status = status;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            MigrationStatus::Completed | MigrationStatus::Failed
+        )
+    }
+
+    /// When the state has been copied and the service interruption starts.
+    pub fn interrupted_at(&self) -> Instant {
+        self.started + Duration::from_millis(self.transfer_time)
+    }
+
+    /// When the service interruption ends and the target takes over.
+    pub fn completes_at(&self) -> Instant {
+        self.interrupted_at() + Duration::from_millis(self.downtime)
+    }
+
+    pub fn information(&self) -> ApplicationMigrationInformation {
+        ApplicationMigrationInformation::new(
+            self.id,
+            self.application_id,
+            self.source_edge_data_center_id,
+            self.target_edge_data_center_id,
+            self.status,
+            self.state_size,
+            self.transfer_time,
+            self.downtime,
+        )
+    }
+}
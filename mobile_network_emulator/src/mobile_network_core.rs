@@ -1,44 +1,124 @@
 use std::{
+    error::Error,
+    fmt::{Display, Formatter},
     net::{IpAddr, Ipv4Addr},
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use geo::{Contains, Point};
-use log::info;
+use log::{info, warn};
 use mobile_network_core_event::{
-    AccuracyFulfillmentIndicator, CivicAddress, Event,
-    EventKind::{LocationReporting, PdnConnectionEvent},
+    AccuracyFulfillmentIndicator, AreaOfInterestInformation, CivicAddress, Event,
+    EventKind::{
+        ApplicationMigration, AreaOfInterest as AreaOfInterestKind, LocationReporting,
+        PdnConnectionEvent, QosMonitoring, UpPathChange,
+    },
     GeographicArea, InterfaceIndication, LdrType, LocationInfo, MinorLocationQoS,
     MobileNetworkCoreEvent, PdnConnectionInformation, PdnConnectionStatus, PdnType,
-    PositioningMethod,
+    PositioningMethod, QosMonitoringInformation, UpPathChangeInformation,
 };
-use mongodb::{Collection, Database};
+use mongodb::{bson::doc, Collection, Database};
+use tokio::sync::broadcast;
 
 use crate::{
     application::Application,
+    area_of_interest::AreaOfInterest,
+    migration::Migration,
     network::{Network, NetworkLogEntry},
     pdu_session::PDUSession,
+    qos::QosFlow,
     ran::Ran,
     user::User,
 };
 
+#[derive(Debug)]
+pub struct MobileNetworkCoreError {
+    message: String,
+}
+
+impl MobileNetworkCoreError {
+    pub fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl Display for MobileNetworkCoreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for MobileNetworkCoreError {}
+
+/// Number of events buffered for consumers that have not caught up yet.
+const EVENT_CHANNEL_CAPACITY: usize = 4096;
+
 pub struct MobileNetworkCore {
     rans: Vec<Ran>,
     orphans: Vec<User>,
     available_ip_addresses: Vec<IpAddr>,
+    event_sender: broadcast::Sender<MobileNetworkCoreEvent>,
+    next_sequence_number: u64,
+    areas_of_interest: Vec<AreaOfInterest>,
+    /// QoS flows waiting for the UE with the given id to set up a PDU session.
+    pending_qos_flows: Vec<(u32, QosFlow)>,
 }
 
 impl MobileNetworkCore {
     pub fn new(rans: Vec<Ran>, orphans: Vec<User>, ip_addesses: Vec<IpAddr>) -> Self {
+        let (event_sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
         MobileNetworkCore {
             rans,
             orphans,
             available_ip_addresses: ip_addesses,
+            event_sender,
+            next_sequence_number: 1,
+            areas_of_interest: Vec::new(),
+            pending_qos_flows: Vec::new(),
         }
     }
 
-    pub async fn try_connect_orphans(&mut self, database: &Database) {
+    /// Continues the event sequence numbers after the last event in the event store.
+    pub async fn resume_sequence_numbers(&mut self, database: &Database) {
+        let collection: Collection<MobileNetworkCoreEvent> = database.collection("Events");
+        if let Some(last_event) = collection
+            .find_one(doc! {})
+            .sort(doc! { "sequence_number": -1 })
+            .await
+            .unwrap()
+        {
+            self.next_sequence_number = last_event.get_sequence_number() + 1;
+        }
+    }
+
+    /// Receives every event the core produces from now on.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<MobileNetworkCoreEvent> {
+        self.event_sender.subscribe()
+    }
+
+    /// Sequence number of the last event produced, 0 if there is none yet.
+    pub fn get_last_sequence_number(&self) -> u64 {
+        self.next_sequence_number - 1
+    }
+
+    /// Numbers `events`, stores them in the event store and hands them to the event subscribers.
+    async fn store_events(&mut self, database: &Database, mut events: Vec<MobileNetworkCoreEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        for event in events.iter_mut() {
+            event.set_sequence_number(self.next_sequence_number);
+            self.next_sequence_number += 1;
+        }
         let collection: Collection<MobileNetworkCoreEvent> = database.collection("Events");
+        collection.insert_many(&events).await.unwrap();
+        for event in events {
+            // Nobody listening is fine, the events are in the event store.
+            let _ = self.event_sender.send(event);
+        }
+    }
+
+    pub async fn try_connect_orphans(&mut self, database: &Database) {
         let mut new_events: Vec<MobileNetworkCoreEvent> = Vec::new();
         let mut tmp_orphans = Vec::new();
         for user in self.orphans.drain(..) {
@@ -49,7 +129,17 @@ impl MobileNetworkCore {
                         Some(ip_address) => ip_address,
                         None => unreachable!(),
                     };
-                    let pdu_session = PDUSession::new(tmp_orphans.pop().unwrap(), ip_address, ran);
+                    let mut pdu_session =
+                        PDUSession::new(tmp_orphans.pop().unwrap(), ip_address, ran);
+                    let user_id = pdu_session.user().get_id();
+                    self.pending_qos_flows.retain(|(ue_id, qos_flow)| {
+                        if *ue_id != user_id {
+                            return true;
+                        }
+                        info!("QoS flow {} set up for user {}", qos_flow.get_id(), user_id);
+                        pdu_session.add_qos_flow(qos_flow.clone());
+                        false
+                    });
                     new_events.push(Self::create_location_reporting_event(
                         &ran.get_id().to_string(),
                         pdu_session.user().current_pos(),
@@ -70,14 +160,11 @@ impl MobileNetworkCore {
             }
         }
         self.orphans = tmp_orphans;
-        if !new_events.is_empty() {
-            collection.insert_many(new_events).await.unwrap();
-        }
+        self.store_events(database, new_events).await;
     }
 
     /// Updates all users positions and places new orphans in orphans.
     pub async fn update_user_positions(&mut self, database: &Database) {
-        let collection: Collection<MobileNetworkCoreEvent> = database.collection("Events");
         let mut new_events: Vec<MobileNetworkCoreEvent> = Vec::new();
         self.orphans.iter_mut().for_each(|user| {
             user.next_pos();
@@ -85,7 +172,7 @@ impl MobileNetworkCore {
         let mut new_orphans = Vec::new();
         for ran_index in 0..self.rans.len() {
             let pdu_sessions = self.rans[ran_index].update_connected_users();
-            'next_pdu_session: for pdu_session in pdu_sessions {
+            'next_pdu_session: for mut pdu_session in pdu_sessions {
                 for ran in self.rans.iter_mut() {
                     if ran.contains(pdu_session.user()) {
                         info!(
@@ -94,6 +181,7 @@ impl MobileNetworkCore {
                             pdu_session.ip().to_string(),
                             ran.get_id()
                         );
+                        pdu_session.handover(ran);
                         ran.connect_user(pdu_session);
                         continue 'next_pdu_session;
                     }
@@ -115,13 +203,10 @@ impl MobileNetworkCore {
             }
         }
         self.orphans.append(&mut new_orphans);
-        if !new_events.is_empty() {
-            collection.insert_many(new_events).await.unwrap();
-        }
+        self.store_events(database, new_events).await;
     }
 
-    pub async fn generate_location_events(&self, database: &Database) {
-        let collection: Collection<MobileNetworkCoreEvent> = database.collection("Events");
+    pub async fn generate_location_events(&mut self, database: &Database) {
         let all_events: Vec<MobileNetworkCoreEvent> = self
             .get_connected_users()
             .iter()
@@ -134,14 +219,61 @@ impl MobileNetworkCore {
                 )
             })
             .collect();
-        if !all_events.is_empty() {
-            collection.insert_many(all_events).await.unwrap();
+        self.store_events(database, all_events).await;
+    }
+
+    /// Stores an event for every UE entering, leaving or staying inside an area of interest.
+    pub async fn evaluate_areas_of_interest(&mut self, database: &Database) {
+        let users: Vec<(u32, Point)> = self
+            .get_all_users()
+            .into_iter()
+            .map(|user| (user.get_id(), user.current_pos()))
+            .collect();
+        let mut new_events = Vec::new();
+        for area_of_interest in self.areas_of_interest.iter_mut() {
+            let (changes, number_of_ues) = area_of_interest.evaluate(&users);
+            for (user_id, position, ldr_type) in changes {
+                new_events.push(MobileNetworkCoreEvent::new(
+                    Event::AreaOfInterest(AreaOfInterestInformation::new(
+                        area_of_interest.get_id(),
+                        ldr_type,
+                        position,
+                        number_of_ues,
+                    )),
+                    AreaOfInterestKind,
+                    user_id,
+                ));
+            }
         }
+        self.store_events(database, new_events).await;
+    }
+
+    /// Starts watching `area_of_interest`, replacing an area with the same id.
+    pub fn add_area_of_interest(&mut self, area_of_interest: AreaOfInterest) {
+        self.remove_area_of_interest(area_of_interest.get_id());
+        self.areas_of_interest.push(area_of_interest);
     }
 
-    pub async fn use_applications(&mut self, network: &mut Network, database: &Database) {
+    pub fn remove_area_of_interest(&mut self, id: u32) -> Option<AreaOfInterest> {
+        let index = self
+            .areas_of_interest
+            .iter()
+            .position(|area_of_interest| area_of_interest.get_id() == id)?;
+        Some(self.areas_of_interest.remove(index))
+    }
+
+    /// Lets every connected user use an application and returns the resulting network logs.
+    /// Each RAN schedules its requests by the priority of the QoS flow carrying them.
+    /// A user plane path change event is stored whenever an application is served by another edge data center than before,
+    /// and a QoS monitoring event whenever a flow starts to exceed its packet delay budget.
+    pub async fn use_applications(
+        &mut self,
+        network: &mut Network,
+        database: &Database,
+    ) -> Vec<NetworkLogEntry> {
         let collection: Collection<NetworkLogEntry> = database.collection("NetworkLog");
-        let connected_users = self.get_connected_users_mut();
+        network.reset_processing_queues();
+        let mut new_events: Vec<MobileNetworkCoreEvent> = Vec::new();
         let applications: Vec<(Application, usize)> = network
             .get_applictions()
             .into_iter()
@@ -152,31 +284,163 @@ impl MobileNetworkCore {
             })
             .collect();
 
+        let indexes: Vec<usize> = applications.iter().map(|(_application, id)| *id).collect();
+
         let mut network_logs = Vec::new();
-        for user in connected_users {
-            let indexes = applications.iter().map(|(_application, id)| *id).collect();
-            let application_index = user.user_mut().choose_application(&indexes);
-            let mut application;
-            loop {
-                match applications
+        for ran in self.rans.iter_mut() {
+            let ran_id = ran.get_id();
+            let scheduling_delays: Vec<Duration> = (0..ran.get_current_connected_users().len())
+                .map(|rank| ran.scheduling_delay(rank))
+                .collect();
+            let mut requests: Vec<(&mut PDUSession, &Application)> = Vec::new();
+            for user in ran.get_current_connected_users_mut() {
+                let Some(application_index) = user.user_mut().choose_application(&indexes) else {
+                    continue;
+                };
+                //We know that the user chose among the applications.
+                let (application, _id) = applications
                     .iter()
                     .find(|(_application, id)| id == &application_index)
+                    .unwrap();
+                requests.push((user, application));
+            }
+            requests.sort_by_key(|(user, application)| {
+                user.qos_flow(application.id()).scheduling_priority()
+            });
+
+            for ((user, application), scheduling_delay) in
+                requests.into_iter().zip(scheduling_delays)
+            {
+                let res = match network.use_application(user, application, ran_id, scheduling_delay)
                 {
-                    Some(a) => {
-                        application = a;
-                        break;
+                    Ok(res) => res,
+                    Err(err) => {
+                        warn!(
+                            "request of user {} not served: {}",
+                            user.user().get_id(),
+                            err
+                        );
+                        continue;
                     }
-                    None => continue,
+                };
+                let v4addr = match user.ip() {
+                    IpAddr::V4(v4addr) => *v4addr,
+                    _ => unreachable!(),
+                };
+                // Requests served by the cloud leave the user plane path to the edge as it was.
+                if let Some((source_edge_data_center_id, edge_data_center_id)) = res
+                    .get_edge_data_center_id()
+                    .and_then(|edge_data_center_id| {
+                        user.route_application(res.get_application_id(), edge_data_center_id)
+                            .map(|source| (source, edge_data_center_id))
+                    })
+                {
+                    new_events.push(Self::create_up_path_change_event(
+                        UpPathChangeInformation::new(
+                            res.get_application_id(),
+                            source_edge_data_center_id,
+                            edge_data_center_id,
+                            v4addr,
+                        ),
+                        user.user().get_id(),
+                    ));
+                }
+                let user_id = user.user().get_id();
+                let qos_flow = user.qos_flow_mut(res.get_application_id());
+                // Lost requests have no delay to hold against the packet delay budget.
+                let violated_delay = res
+                    .get_delay()
+                    .filter(|delay| qos_flow.record_delay(*delay));
+                if let Some(delay) = violated_delay {
+                    new_events.push(Self::create_qos_monitoring_event(
+                        QosMonitoringInformation::new(
+                            qos_flow.get_qos_reference().to_string(),
+                            res.get_application_id(),
+                            v4addr,
+                            qos_flow.get_packet_delay_budget().as_millis() as u32,
+                            delay.as_millis() as u64,
+                        ),
+                        user_id,
+                    ));
                 }
+                network_logs.push(res);
             }
-            let res = network
-                .use_application(user, &application.0, &user.get_ran().get_position())
-                .unwrap();
-            network_logs.push(res);
         }
         if !network_logs.is_empty() {
-            collection.insert_many(network_logs).await.unwrap();
+            collection.insert_many(&network_logs).await.unwrap();
         }
+        self.store_events(database, new_events).await;
+        network_logs
+    }
+
+    /// Stores an application migration event for each of `migrations`.
+    pub async fn report_migrations(&mut self, database: &Database, migrations: &[Migration]) {
+        let events = migrations
+            .iter()
+            .map(|migration| {
+                MobileNetworkCoreEvent::new(
+                    Event::ApplicationMigration(migration.information()),
+                    ApplicationMigration,
+                    0,
+                )
+            })
+            .collect();
+        self.store_events(database, events).await;
+    }
+
+    /// Adds `qos_flow` to the PDU session holding `ip_address` and returns the id of its UE.
+    pub fn add_qos_flow(
+        &mut self,
+        ip_address: &IpAddr,
+        qos_flow: QosFlow,
+    ) -> Result<u32, MobileNetworkCoreError> {
+        match self
+            .get_connected_users_mut()
+            .into_iter()
+            .find(|pdu_session| pdu_session.ip() == ip_address)
+        {
+            Some(pdu_session) => {
+                pdu_session.add_qos_flow(qos_flow);
+                Ok(pdu_session.user().get_id())
+            }
+            None => Err(MobileNetworkCoreError::new(format!(
+                "No PDU session with ip address {}",
+                ip_address
+            ))),
+        }
+    }
+
+    /// Adds `qos_flow` to the PDU session of the UE with `user_id`,
+    /// or to the next one the UE sets up if it has none at the moment.
+    pub fn add_user_qos_flow(&mut self, user_id: u32, qos_flow: QosFlow) {
+        match self
+            .get_connected_users_mut()
+            .into_iter()
+            .find(|pdu_session| pdu_session.user().get_id() == user_id)
+        {
+            Some(pdu_session) => pdu_session.add_qos_flow(qos_flow),
+            None => self.pending_qos_flows.push((user_id, qos_flow)),
+        }
+    }
+
+    /// Removes the QoS flow with `id` from whichever PDU session holds it.
+    pub fn remove_qos_flow(&mut self, id: u32) -> Option<QosFlow> {
+        if let Some(index) = self
+            .pending_qos_flows
+            .iter()
+            .position(|(_, qos_flow)| qos_flow.get_id() == id)
+        {
+            return Some(self.pending_qos_flows.remove(index).1);
+        }
+        self.get_connected_users_mut()
+            .into_iter()
+            .find_map(|pdu_session| pdu_session.remove_qos_flow(id))
+    }
+
+    /// Releases the area of interest and QoS flow a subscription with `id` set up.
+    pub fn release_subscription(&mut self, id: u32) {
+        self.remove_area_of_interest(id);
+        self.remove_qos_flow(id);
     }
 
     pub fn get_rans(&self) -> Vec<&Ran> {
@@ -246,6 +510,20 @@ impl MobileNetworkCore {
         )
     }
 
+    fn create_up_path_change_event(
+        up_path_change: UpPathChangeInformation,
+        user_id: u32,
+    ) -> MobileNetworkCoreEvent {
+        MobileNetworkCoreEvent::new(Event::UpPathChange(up_path_change), UpPathChange, user_id)
+    }
+
+    fn create_qos_monitoring_event(
+        qos_monitoring: QosMonitoringInformation,
+        user_id: u32,
+    ) -> MobileNetworkCoreEvent {
+        MobileNetworkCoreEvent::new(Event::QosMonitoring(qos_monitoring), QosMonitoring, user_id)
+    }
+
     fn release_pdn_connection_event(ipv4_addr: Ipv4Addr, user_id: u32) -> MobileNetworkCoreEvent {
         MobileNetworkCoreEvent::new(
             Event::PdnConnectionEvent(PdnConnectionInformation::new(
@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use actix_web::{
     get, post,
     web::{Data, Json},
@@ -7,12 +9,13 @@ use mongodb::Database;
 use tokio::sync::RwLock;
 
 use crate::{
+    live_state::StateSnapshot, live_state_endpoints::LiveStateWrapper, metrics::Metrics,
     mobile_network_core::MobileNetworkCore, network_endpoints::NetworkWrapper,
     pdu_session::PDUSession, ran::Ran, user::User,
 };
 
 pub struct MobileNetworkCoreWrapper {
-    mobile_network_core: RwLock<MobileNetworkCore>,
+    pub mobile_network_core: RwLock<MobileNetworkCore>,
 }
 
 impl MobileNetworkCoreWrapper {
@@ -57,6 +60,8 @@ pub async fn get_connected_users(
 pub async fn update_user_positions(
     mobile_network_core_wrapper: Data<MobileNetworkCoreWrapper>,
     network_wrapper: Data<NetworkWrapper>,
+    live_state_wrapper: Data<LiveStateWrapper>,
+    metrics: Data<Metrics>,
     database: Data<Database>,
 ) -> impl Responder {
     let mut network = network_wrapper.network.write().await;
@@ -64,10 +69,21 @@ pub async fn update_user_positions(
         .mobile_network_core
         .write()
         .await;
+    let tick_start = Instant::now();
     mnc.try_connect_orphans(&database).await;
     mnc.update_user_positions(&database).await;
     mnc.generate_location_events(&database).await;
-    mnc.use_applications(&mut network, &database).await;
+    mnc.evaluate_areas_of_interest(&database).await;
+    network.update_applications(Instant::now());
+    let migrations = network.update_migrations(Instant::now());
+    mnc.report_migrations(&database, &migrations).await;
+    let network_logs = mnc.use_applications(&mut network, &database).await;
+    let delta = live_state_wrapper
+        .live_state
+        .write()
+        .await
+        .publish(StateSnapshot::new(&mnc, &network));
+    metrics.observe_tick(&mnc, &delta, &network_logs, tick_start.elapsed());
     "OK"
 }
 
@@ -1,114 +1,873 @@
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::{Display, Formatter},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
+use chrono::{DateTime, Utc};
 use futures::StreamExt;
+use log::{error, info};
 use mongodb::{bson::doc, Collection, Database};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
-use mobile_network_core_event::{EventKind, MobileNetworkCoreEvent};
+use mobile_network_core_event::{Event, EventKind, LdrType, MobileNetworkCoreEvent};
 use url::Url;
 
+use crate::{
+    analytics_exposure::AnalyticsExposureSubscription,
+    area_of_interest::Area,
+    as_session_with_qos::AsSessionWithQosSubscription,
+    delivery::{
+        DeadLetter, Deliveries, DeliveryConfig, DeliveryResults, DeliveryStatus, DueDeliveries,
+    },
+    metrics::Metrics,
+    monitoring_event::MonitoringEventSubscription,
+    traffic_influence::TrafficInfluenceSubscription,
+};
+
+/// How far back analytics look, older location reports are not read for them.
+pub const ANALYTICS_WINDOW: Duration = Duration::from_secs(300);
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EventSubscriber {
-    notify_endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<u32>,
+    #[serde(rename = "self", default, skip_serializing_if = "Option::is_none")]
+    self_link: Option<String>,
+    /// Where notifications are pushed to, subscribers without one read their events from a stream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notify_endpoint: Option<String>,
     kind: EventKind,
+    /// For `AreaOfInterest` subscriptions an empty list stands for any UE.
     user_ids: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expire_time: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    maximum_number_of_reports: Option<u32>,
+    /// The area watched by `AreaOfInterest` subscriptions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    area: Option<Area>,
+    /// Which area events to report, all of them if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ldr_types: Vec<LdrType>,
+    /// Shared secret the notifications are signed with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+    /// The AF that created the subscription when authorization is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    af_id: Option<String>,
 }
 
 impl EventSubscriber {
+    #[allow(dead_code)]
     pub fn new(notify_endpoint: Url, kind: EventKind, user_ids: Vec<u32>) -> Self {
         EventSubscriber {
-            notify_endpoint: notify_endpoint.as_str().to_string(),
+            id: None,
+            self_link: None,
+            notify_endpoint: Some(notify_endpoint.as_str().to_string()),
             kind,
             user_ids,
+            expire_time: None,
+            maximum_number_of_reports: None,
+            area: None,
+            ldr_types: Vec::new(),
+            secret: None,
+            af_id: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), MobileNetworkExposureError> {
+        if let Some(notify_endpoint) = &self.notify_endpoint {
+            if Url::parse(notify_endpoint).is_err() {
+                return Err(MobileNetworkExposureError::new(format!(
+                    "notify_endpoint {} is not a valid URL",
+                    notify_endpoint
+                )));
+            }
+        }
+        if self.maximum_number_of_reports == Some(0) {
+            return Err(MobileNetworkExposureError::new(
+                "maximum_number_of_reports must be at least 1".to_string(),
+            ));
+        }
+        if self.secret.as_deref() == Some("") {
+            return Err(MobileNetworkExposureError::new(
+                "secret must not be empty".to_string(),
+            ));
         }
+        match (&self.kind, &self.area) {
+            (EventKind::AreaOfInterest, None) => {
+                return Err(MobileNetworkExposureError::new(
+                    "AreaOfInterest subscriptions need an area".to_string(),
+                ))
+            }
+            (EventKind::AreaOfInterest, Some(area)) => {
+                area.validate().map_err(MobileNetworkExposureError::new)?
+            }
+            (_, Some(_)) => {
+                return Err(MobileNetworkExposureError::new(
+                    "only AreaOfInterest subscriptions can have an area".to_string(),
+                ))
+            }
+            (_, None) => (),
+        }
+        if let Some(ldr_type) = self.ldr_types.iter().find(|ldr_type| {
+            !matches!(
+                ldr_type,
+                LdrType::EnteringIntoArea | LdrType::LeavingFromArea | LdrType::BeingInsideArea
+            )
+        }) {
+            return Err(MobileNetworkExposureError::new(format!(
+                "{:?} is not an area event",
+                ldr_type
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn set_af_id(&mut self, af_id: Option<String>) {
+        self.af_id = af_id;
+    }
+
+    /// Sets the identity the exposure function gave the subscription.
+    pub fn set_resource(&mut self, id: u32, self_link: String) {
+        self.id = Some(id);
+        self.self_link = Some(self_link);
     }
 
-    pub fn get_event_type(&self) -> &EventKind {
-        &self.kind
+    /// Applies the fields present in `patch`, the result is not validated.
+    pub fn patch(&mut self, patch: EventSubscriberPatch) {
+        if patch.notify_endpoint.is_some() {
+            self.notify_endpoint = patch.notify_endpoint;
+        }
+        if let Some(kind) = patch.kind {
+            self.kind = kind;
+        }
+        if let Some(user_ids) = patch.user_ids {
+            self.user_ids = user_ids;
+        }
+        if patch.expire_time.is_some() {
+            self.expire_time = patch.expire_time;
+        }
+        if patch.maximum_number_of_reports.is_some() {
+            self.maximum_number_of_reports = patch.maximum_number_of_reports;
+        }
+        if patch.area.is_some() {
+            self.area = patch.area;
+        }
+        if let Some(ldr_types) = patch.ldr_types {
+            self.ldr_types = ldr_types;
+        }
+        if patch.secret.is_some() {
+            self.secret = patch.secret;
+        }
     }
 
-    pub fn get_notify_endpoint(&self) -> Url {
-        Url::parse(&self.notify_endpoint).unwrap()
+    /// The subscription as shown to clients, without its secret.
+    pub fn redacted(&self) -> Self {
+        Self {
+            secret: None,
+            ..self.clone()
+        }
     }
 
-    pub fn get_user_ids(&self) -> Vec<&u32> {
-        self.user_ids.iter().collect()
+    fn matches(&self, event: &MobileNetworkCoreEvent) -> bool {
+        if event.get_event_type() != &self.kind {
+            return false;
+        }
+        match event.get_event() {
+            Event::AreaOfInterest(area_of_interest) => {
+                self.id == Some(area_of_interest.area_id)
+                    && (self.ldr_types.is_empty()
+                        || self.ldr_types.contains(&area_of_interest.ldr_type))
+                    && (self.user_ids.is_empty() || self.user_ids.contains(&event.get_user_id()))
+            }
+            // Migrations are not about a UE.
+            Event::ApplicationMigration(_) => true,
+            _ => self.user_ids.contains(&event.get_user_id()),
+        }
     }
+
+    /// The notification body, area of interest events are wrapped in an `AreaOfInterestReport`.
+    fn notification(&self, events: &[MobileNetworkCoreEvent]) -> serde_json::Value {
+        let number_of_ues = events
+            .iter()
+            .rev()
+            .find_map(|event| match event.get_event() {
+                Event::AreaOfInterest(area_of_interest) => Some(area_of_interest.number_of_ues),
+                _ => None,
+            });
+        match number_of_ues {
+            Some(number_of_ues) => serde_json::to_value(AreaOfInterestReport {
+                id: self.id,
+                self_link: self.self_link.clone(),
+                number_of_ues,
+                events: events.to_vec(),
+            }),
+            None => serde_json::to_value(events),
+        }
+        .unwrap()
+    }
+
+    pub fn get_area(&self) -> Option<&Area> {
+        self.area.as_ref()
+    }
+
+    pub fn get_notify_endpoint(&self) -> Option<Url> {
+        self.notify_endpoint
+            .as_deref()
+            .map(|notify_endpoint| Url::parse(notify_endpoint).unwrap())
+    }
+}
+
+/// Partial update of an `EventSubscriber`, absent fields are left unchanged.
+#[derive(Clone, Debug, Deserialize)]
+pub struct EventSubscriberPatch {
+    notify_endpoint: Option<String>,
+    kind: Option<EventKind>,
+    user_ids: Option<Vec<u32>>,
+    expire_time: Option<DateTime<Utc>>,
+    maximum_number_of_reports: Option<u32>,
+    area: Option<Area>,
+    ldr_types: Option<Vec<LdrType>>,
+    secret: Option<String>,
 }
 
+/// The events of one area of interest subscription together with the current number of UEs inside.
 #[derive(Clone, Debug, Serialize)]
+pub struct AreaOfInterestReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u32>,
+    #[serde(rename = "self", skip_serializing_if = "Option::is_none")]
+    self_link: Option<String>,
+    number_of_ues: u32,
+    events: Vec<MobileNetworkCoreEvent>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TerminationCause {
+    Expired,
+    MaximumNumberOfReportsReached,
+}
+
+/// Sent to an event subscriber when the exposure function ends its subscription.
+#[derive(Clone, Debug, Serialize)]
+pub struct SubscriptionTermination {
+    id: u32,
+    #[serde(rename = "self", skip_serializing_if = "Option::is_none")]
+    self_link: Option<String>,
+    cause: TerminationCause,
+}
+
+#[derive(Debug)]
+pub struct MobileNetworkExposureError {
+    message: String,
+}
+
+impl MobileNetworkExposureError {
+    pub fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl Display for MobileNetworkExposureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for MobileNetworkExposureError {}
+
+/// The different kinds of subscriptions the exposure function delivers events to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Subscription {
+    Event(EventSubscriber),
+    MonitoringEvent {
+        scs_as_id: String,
+        subscription: MonitoringEventSubscription,
+    },
+    AnalyticsExposure {
+        af_id: String,
+        subscription: AnalyticsExposureSubscription,
+    },
+    TrafficInfluence {
+        af_id: String,
+        subscription: TrafficInfluenceSubscription,
+    },
+    AsSessionWithQos {
+        scs_as_id: String,
+        subscription: AsSessionWithQosSubscription,
+        /// The UE the QoS flow was set up for, it keeps its id when its PDU session changes.
+        #[serde(default)]
+        ue_id: Option<u32>,
+    },
+}
+
+impl Subscription {
+    fn name(&self) -> &'static str {
+        match self {
+            Subscription::Event(_) => "event",
+            Subscription::MonitoringEvent { .. } => "monitoring event",
+            Subscription::AnalyticsExposure { .. } => "analytics exposure",
+            Subscription::TrafficInfluence { .. } => "traffic influence",
+            Subscription::AsSessionWithQos { .. } => "AS session with QoS",
+        }
+    }
+
+    fn matches(&self, event: &MobileNetworkCoreEvent) -> bool {
+        match self {
+            Subscription::Event(event_subscriber) => event_subscriber.matches(event),
+            Subscription::MonitoringEvent { subscription, .. } => subscription.matches(event),
+            Subscription::AnalyticsExposure { subscription, .. } => subscription.matches(event),
+            Subscription::TrafficInfluence { subscription, .. } => subscription.matches(event),
+            Subscription::AsSessionWithQos {
+                subscription,
+                ue_id,
+                ..
+            } => subscription.matches(event, *ue_id),
+        }
+    }
+
+    fn get_notify_endpoint(&self) -> Option<Url> {
+        match self {
+            Subscription::Event(event_subscriber) => event_subscriber.get_notify_endpoint(),
+            Subscription::MonitoringEvent { subscription, .. } => {
+                Some(subscription.get_notification_destination())
+            }
+            Subscription::AnalyticsExposure { subscription, .. } => {
+                Some(subscription.get_notif_uri())
+            }
+            Subscription::TrafficInfluence { subscription, .. } => {
+                subscription.get_notification_destination()
+            }
+            Subscription::AsSessionWithQos { subscription, .. } => {
+                Some(subscription.get_notification_destination())
+            }
+        }
+    }
+
+    fn get_maximum_number_of_reports(&self) -> Option<u32> {
+        match self {
+            Subscription::Event(event_subscriber) => event_subscriber.maximum_number_of_reports,
+            Subscription::MonitoringEvent { subscription, .. } => {
+                subscription.get_maximum_number_of_reports()
+            }
+            _ => None,
+        }
+    }
+
+    /// The AF owning the subscription, `None` for native subscriptions made without authorization.
+    fn get_af_id(&self) -> Option<&str> {
+        match self {
+            Subscription::Event(event_subscriber) => event_subscriber.af_id.as_deref(),
+            Subscription::MonitoringEvent { scs_as_id, .. }
+            | Subscription::AsSessionWithQos { scs_as_id, .. } => Some(scs_as_id),
+            Subscription::AnalyticsExposure { af_id, .. }
+            | Subscription::TrafficInfluence { af_id, .. } => Some(af_id),
+        }
+    }
+
+    /// The secret notifications are signed with, only native event subscriptions can have one.
+    fn get_secret(&self) -> Option<String> {
+        match self {
+            Subscription::Event(event_subscriber) => event_subscriber.secret.clone(),
+            _ => None,
+        }
+    }
+
+    fn get_expire_time(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Subscription::Event(event_subscriber) => event_subscriber.expire_time,
+            Subscription::MonitoringEvent { subscription, .. } => {
+                subscription.get_monitor_expire_time()
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Subscriber {
-    subscriber: EventSubscriber,
-    recieved_events: HashSet<MobileNetworkCoreEvent>,
+    id: u32,
+    subscription: Subscription,
+    /// Sequence number of the last event this subscriber has been notified about.
+    cursor: u64,
+    number_of_reports: u32,
 }
 
 impl Subscriber {
-    pub fn new(subscriber: EventSubscriber) -> Self {
+    pub fn new(id: u32, subscription: Subscription) -> Self {
         Self {
-            subscriber,
-            recieved_events: HashSet::new(),
+            id,
+            subscription,
+            cursor: 0,
+            number_of_reports: 0,
+        }
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn get_subscription(&self) -> &Subscription {
+        &self.subscription
+    }
+
+    pub fn get_subscriber(&self) -> Option<&EventSubscriber> {
+        match &self.subscription {
+            Subscription::Event(event_subscriber) => Some(event_subscriber),
+            _ => None,
         }
     }
 
-    pub fn get_subscriber(&self) -> &EventSubscriber {
-        &self.subscriber
+    /// How many more notifications may be sent, `None` if there is no limit.
+    fn remaining_reports(&self) -> Option<usize> {
+        self.subscription
+            .get_maximum_number_of_reports()
+            .map(|maximum| maximum.saturating_sub(self.number_of_reports) as usize)
+    }
+
+    /// Sequence number of the last event in `events` that the cursor can move to, only across
+    /// events directly following it. Events past a gap wait for the missing ones to be caught up.
+    fn reach(&self, events: &[MobileNetworkCoreEvent]) -> u64 {
+        let mut sequence_numbers: Vec<u64> = events
+            .iter()
+            .map(|event| event.get_sequence_number())
+            .filter(|sequence_number| *sequence_number > self.cursor)
+            .collect();
+        sequence_numbers.sort_unstable();
+        sequence_numbers
+            .into_iter()
+            .fold(self.cursor, |reach, sequence_number| {
+                if sequence_number == reach + 1 {
+                    sequence_number
+                } else {
+                    reach
+                }
+            })
+    }
+
+    /// Builds the notification body for the events in `events` directly following the cursor
+    /// together with the events it reports, `None` if there is nothing to send.
+    /// Analytics are computed over `history`, the location reports within the analytics window.
+    fn notification(
+        &self,
+        events: &[MobileNetworkCoreEvent],
+        history: &[MobileNetworkCoreEvent],
+    ) -> Option<(serde_json::Value, Vec<MobileNetworkCoreEvent>)> {
+        let reach = self.reach(events);
+        let res: Vec<MobileNetworkCoreEvent> = events
+            .iter()
+            .filter(|event| {
+                let sequence_number = event.get_sequence_number();
+                sequence_number > self.cursor
+                    && sequence_number <= reach
+                    && self.subscription.matches(event)
+            })
+            .cloned()
+            .collect();
+        let last_report = self.remaining_reports() == Some(1);
+        let body = match &self.subscription {
+            Subscription::Event(event_subscriber) => {
+                if res.is_empty() {
+                    return None;
+                }
+                Ok(event_subscriber.notification(&res))
+            }
+            Subscription::MonitoringEvent { subscription, .. } => {
+                if res.is_empty() {
+                    return None;
+                }
+                serde_json::to_value(subscription.notification(&res, last_report))
+            }
+            Subscription::AnalyticsExposure { subscription, .. } => {
+                if res.is_empty() {
+                    return None;
+                }
+                serde_json::to_value(subscription.notification(history)?)
+            }
+            Subscription::TrafficInfluence { subscription, .. } => {
+                if res.is_empty() {
+                    return None;
+                }
+                serde_json::to_value(subscription.notification(&res))
+            }
+            Subscription::AsSessionWithQos { subscription, .. } => {
+                if res.is_empty() {
+                    return None;
+                }
+                serde_json::to_value(subscription.notification(&res))
+            }
+        };
+        Some((body.unwrap(), res))
+    }
+
+    fn is_expired(&self, now: &DateTime<Utc>) -> bool {
+        match self.subscription.get_expire_time() {
+            Some(expire_time) => expire_time <= *now,
+            None => false,
+        }
+    }
+
+    /// Why the subscription has ended, `None` while it is still active.
+    fn termination_cause(&self, now: &DateTime<Utc>) -> Option<TerminationCause> {
+        if self.remaining_reports() == Some(0) {
+            Some(TerminationCause::MaximumNumberOfReportsReached)
+        } else if self.is_expired(now) {
+            Some(TerminationCause::Expired)
+        } else {
+            None
+        }
+    }
+
+    /// The termination notification for native event subscribers with a notify endpoint,
+    /// the 3GPP APIs signal the end of a subscription in their own notifications.
+    fn termination(&self, cause: TerminationCause) -> Option<(Url, SubscriptionTermination)> {
+        match &self.subscription {
+            Subscription::Event(event_subscriber) => Some((
+                event_subscriber.get_notify_endpoint()?,
+                SubscriptionTermination {
+                    id: self.id,
+                    self_link: event_subscriber.self_link.clone(),
+                    cause,
+                },
+            )),
+            _ => None,
+        }
     }
 }
 
 pub struct MobileNetworkExposure {
     event_subscribers: Vec<Subscriber>,
-    http_client: Client,
+    next_subscription_id: u32,
+    deliveries: Deliveries,
+    terminated_subscriptions: Vec<u32>,
+    /// Sequence number of the last event handed to the subscribers, new subscriptions start after it.
+    last_sequence_number: u64,
+    /// Subscriptions added, changed or removed since they were last persisted.
+    changed_subscriptions: BTreeSet<u32>,
 }
 
 impl MobileNetworkExposure {
-    pub fn new() -> Self {
+    pub fn new(metrics: Metrics) -> Self {
         Self {
             event_subscribers: Vec::new(),
-            http_client: Client::new(),
+            next_subscription_id: 0,
+            deliveries: Deliveries::new(DeliveryConfig::default(), metrics),
+            terminated_subscriptions: Vec::new(),
+            last_sequence_number: 0,
+            changed_subscriptions: BTreeSet::new(),
         }
     }
 
-    pub fn add_subscriber(&mut self, event_subscriber: EventSubscriber) {
-        self.event_subscribers
-            .push(Subscriber::new(event_subscriber));
+    /// Loads the subscriptions persisted in the event store, replacing the current ones.
+    pub async fn restore_subscriptions(&mut self, database: &Database) {
+        let collection: Collection<Subscriber> = database.collection("Subscriptions");
+        let mut subscribers: Vec<Subscriber> = collection
+            .find(doc! {})
+            .await
+            .unwrap()
+            .collect::<Vec<Result<_, _>>>()
+            .await
+            .into_iter()
+            .filter_map(|subscriber| match subscriber {
+                Ok(subscriber) => Some(subscriber),
+                Err(err) => {
+                    error!("could not restore a subscription: {}", err);
+                    None
+                }
+            })
+            .collect();
+        subscribers.sort_by_key(|subscriber| subscriber.id);
+        self.next_subscription_id = subscribers
+            .last()
+            .map(|subscriber| subscriber.id + 1)
+            .unwrap_or_default();
+
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for subscriber in subscribers.iter() {
+            *counts.entry(subscriber.subscription.name()).or_default() += 1;
+        }
+        info!(
+            "restored {} subscriptions{}",
+            subscribers.len(),
+            counts
+                .iter()
+                .map(|(name, count)| format!(", {} {}", count, name))
+                .collect::<String>()
+        );
+        self.event_subscribers = subscribers;
+    }
+
+    /// Writes the subscriptions changed since the last call to the event store.
+    /// Failed writes are retried on the next call.
+    pub async fn persist_subscriptions(&mut self, database: &Database) {
+        let collection: Collection<Subscriber> = database.collection("Subscriptions");
+        for id in std::mem::take(&mut self.changed_subscriptions) {
+            let result = match self.get_subscription(id) {
+                Some(subscriber) => collection
+                    .replace_one(doc! { "id": id }, subscriber)
+                    .upsert(true)
+                    .await
+                    .map(|_| ()),
+                None => collection.delete_one(doc! { "id": id }).await.map(|_| ()),
+            };
+            if let Err(err) = result {
+                error!("could not persist subscription {}: {}", id, err);
+                self.changed_subscriptions.insert(id);
+            }
+        }
+    }
+
+    pub fn add_subscriber(&mut self, event_subscriber: EventSubscriber) -> u32 {
+        self.add_subscription(Subscription::Event(event_subscriber))
+    }
+
+    pub fn add_subscription(&mut self, subscription: Subscription) -> u32 {
+        let id = self.next_subscription_id;
+        self.next_subscription_id += 1;
+        let mut subscriber = Subscriber::new(id, subscription);
+        subscriber.cursor = self.last_sequence_number;
+        self.event_subscribers.push(subscriber);
+        self.changed_subscriptions.insert(id);
+        id
     }
 
     pub fn get_subscribers(&self) -> Vec<&Subscriber> {
         self.event_subscribers.iter().collect()
     }
 
-    pub async fn publish_events(&mut self, database: &Database) {
-        let events = self.get_events(database).await;
-        for subscriber in self.event_subscribers.iter_mut() {
-            let res = events
+    pub fn get_subscription(&self, id: u32) -> Option<&Subscriber> {
+        self.event_subscribers
+            .iter()
+            .find(|subscriber| subscriber.id == id)
+    }
+
+    /// Whether `af_id` may see the subscription with `id`, everybody may without authorization.
+    pub fn is_visible_to(&self, id: u32, af_id: Option<&str>) -> bool {
+        af_id.is_none_or(|af_id| {
+            self.get_subscription(id)
+                .is_some_and(|subscriber| subscriber.subscription.get_af_id() == Some(af_id))
+        })
+    }
+
+    /// The events in `events` the native subscription `id` is interested in,
+    /// `None` once the subscription has ended.
+    pub fn filter_events(
+        &self,
+        id: u32,
+        events: &[MobileNetworkCoreEvent],
+    ) -> Option<Vec<MobileNetworkCoreEvent>> {
+        let event_subscriber = self.get_subscription(id)?.get_subscriber()?;
+        Some(
+            events
                 .iter()
-                .filter(|event| {
-                    event.get_event_type() == subscriber.subscriber.get_event_type()
-                        && !subscriber.recieved_events.contains(event)
-                        && subscriber
-                            .subscriber
-                            .get_user_ids()
-                            .contains(&&event.get_user_id())
-                })
+                .filter(|event| event_subscriber.matches(event))
                 .cloned()
-                .collect();
-            self.http_client
-                .post(subscriber.subscriber.get_notify_endpoint())
-                .json::<Vec<MobileNetworkCoreEvent>>(&res)
-                .send()
-                .await
-                .unwrap();
-            for event in res {
-                subscriber.recieved_events.insert(event);
+                .collect(),
+        )
+    }
+
+    /// Replaces the subscription with the given id, already delivered events stay delivered.
+    pub fn update_subscription(
+        &mut self,
+        id: u32,
+        subscription: Subscription,
+    ) -> Result<(), MobileNetworkExposureError> {
+        match self
+            .event_subscribers
+            .iter_mut()
+            .find(|subscriber| subscriber.id == id)
+        {
+            Some(subscriber) => {
+                subscriber.subscription = subscription;
+                self.changed_subscriptions.insert(id);
+                Ok(())
+            }
+            None => Err(MobileNetworkExposureError::new(format!(
+                "Subscription with id {} does not exist",
+                id
+            ))),
+        }
+    }
+
+    pub fn remove_subscription(
+        &mut self,
+        id: u32,
+    ) -> Result<Subscriber, MobileNetworkExposureError> {
+        match self
+            .event_subscribers
+            .iter()
+            .position(|subscriber| subscriber.id == id)
+        {
+            Some(index) => {
+                self.changed_subscriptions.insert(id);
+                self.deliveries.remove(id);
+                Ok(self.event_subscribers.remove(index))
+            }
+            None => Err(MobileNetworkExposureError::new(format!(
+                "Subscription with id {} does not exist",
+                id
+            ))),
+        }
+    }
+
+    /// Notifies the subscribers about every stored event past their cursor.
+    pub async fn publish_events(&mut self, database: &Database) {
+        let oldest_cursor = self
+            .event_subscribers
+            .iter()
+            .map(|subscriber| subscriber.cursor)
+            .min()
+            .unwrap_or_default();
+        let events = self.get_events_since(database, oldest_cursor).await;
+        let history = self.get_history(&events, database).await;
+        self.notify(&events, &history);
+        self.persist_subscriptions(database).await;
+    }
+
+    /// Notifies the subscribers about `events` as they are produced by the core.
+    /// The stored history is only read when an analytics subscription needs it.
+    pub async fn dispatch_events(
+        &mut self,
+        events: &[MobileNetworkCoreEvent],
+        database: &Database,
+    ) {
+        let history = self.get_history(events, database).await;
+        self.notify(events, &history);
+        self.persist_subscriptions(database).await;
+    }
+
+    /// The analytics history if `events` bring an analytics subscription new location reports,
+    /// otherwise nothing.
+    async fn get_history(
+        &self,
+        events: &[MobileNetworkCoreEvent],
+        database: &Database,
+    ) -> Vec<MobileNetworkCoreEvent> {
+        let needs_history = self.event_subscribers.iter().any(|subscriber| {
+            matches!(
+                subscriber.subscription,
+                Subscription::AnalyticsExposure { .. }
+            ) && events.iter().any(|event| {
+                event.get_sequence_number() > subscriber.cursor
+                    && subscriber.subscription.matches(event)
+            })
+        });
+        if needs_history {
+            self.get_analytics_history(database).await
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Hands the notifications for `events` to the delivery queues, sending them is up to the caller.
+    /// Events count as reported once queued, retries are up to the delivery queues.
+    /// Every subscriber's cursor moves past the events directly following it whether or not they matched.
+    fn notify(&mut self, events: &[MobileNetworkCoreEvent], history: &[MobileNetworkCoreEvent]) {
+        self.terminate_subscriptions(&Utc::now());
+        if let Some(last_sequence_number) =
+            events.iter().map(|event| event.get_sequence_number()).max()
+        {
+            self.last_sequence_number = self.last_sequence_number.max(last_sequence_number);
+        }
+        for subscriber in self.event_subscribers.iter_mut() {
+            let notification = subscriber.notification(events, history);
+            let reach = subscriber.reach(events);
+            if reach > subscriber.cursor {
+                subscriber.cursor = reach;
+                self.changed_subscriptions.insert(subscriber.id);
+            }
+            let (body, _) = match notification {
+                Some(notification) => notification,
+                None => continue,
+            };
+            // One report per notification, whether it goes to the notify endpoint or only to the streams.
+            subscriber.number_of_reports += 1;
+            self.changed_subscriptions.insert(subscriber.id);
+            if let Some(notify_endpoint) = subscriber.subscription.get_notify_endpoint() {
+                self.deliveries.enqueue(
+                    subscriber.id,
+                    notify_endpoint,
+                    body,
+                    subscriber.subscription.get_secret(),
+                );
+            }
+        }
+        self.terminate_subscriptions(&Utc::now());
+    }
+
+    /// The ids of the subscriptions terminated since the last call,
+    /// whatever they set up in the core should be released.
+    pub fn take_terminated_subscriptions(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.terminated_subscriptions)
+    }
+
+    /// Takes out the notifications due to be sent, they are sent without holding on to the exposure
+    /// and their results handed back with [`MobileNetworkExposure::record_deliveries`].
+    pub fn take_due_deliveries(&mut self) -> DueDeliveries {
+        self.deliveries.take_due()
+    }
+
+    pub fn record_deliveries(&mut self, results: DeliveryResults) {
+        self.deliveries.record(results);
+    }
+
+    pub fn get_delivery_statuses(&self) -> Vec<&DeliveryStatus> {
+        self.deliveries.get_statuses()
+    }
+
+    pub fn get_delivery_status(&self, id: u32) -> Option<&DeliveryStatus> {
+        self.deliveries.get_status(id)
+    }
+
+    pub fn get_dead_letters(&self) -> Vec<&DeadLetter> {
+        self.deliveries.get_dead_letters()
+    }
+
+    /// Removes expired subscriptions and those that reached their maximum number of reports,
+    /// queueing a termination notification for native event subscribers.
+    fn terminate_subscriptions(&mut self, now: &DateTime<Utc>) {
+        let (terminated, active): (Vec<Subscriber>, Vec<Subscriber>) = self
+            .event_subscribers
+            .drain(..)
+            .partition(|subscriber| subscriber.termination_cause(now).is_some());
+        self.event_subscribers = active;
+        for subscriber in terminated {
+            let cause = subscriber.termination_cause(now).unwrap();
+            info!("subscription {} terminated: {:?}", subscriber.id, cause);
+            self.terminated_subscriptions.push(subscriber.id);
+            self.changed_subscriptions.insert(subscriber.id);
+            if let Some((notify_endpoint, termination)) = subscriber.termination(cause) {
+                self.deliveries.enqueue(
+                    subscriber.id,
+                    notify_endpoint,
+                    serde_json::to_value(termination).unwrap(),
+                    subscriber.subscription.get_secret(),
+                );
             }
         }
     }
 
     pub async fn get_events(&self, database: &Database) -> Vec<MobileNetworkCoreEvent> {
+        self.get_events_since(database, 0).await
+    }
+
+    /// The stored location reports within the analytics window, in order, the events analytics are computed over.
+    pub async fn get_analytics_history(&self, database: &Database) -> Vec<MobileNetworkCoreEvent> {
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .saturating_sub(ANALYTICS_WINDOW);
         let collection: Collection<MobileNetworkCoreEvent> = database.collection("Events");
         collection
-            .find(doc! {})
+            .find(doc! {
+                "kind": "LocationReporting",
+                "timestamp.secs": { "$gte": since.as_secs() as i64 },
+            })
+            .sort(doc! { "sequence_number": 1 })
             .await
             .unwrap()
             .collect::<Vec<Result<_, _>>>()
@@ -117,4 +876,396 @@ impl MobileNetworkExposure {
             .filter_map(|r| r.clone().ok())
             .collect()
     }
+
+    /// The stored events with a sequence number above `sequence_number`, in order.
+    pub async fn get_events_since(
+        &self,
+        database: &Database,
+        sequence_number: u64,
+    ) -> Vec<MobileNetworkCoreEvent> {
+        let collection: Collection<MobileNetworkCoreEvent> = database.collection("Events");
+        collection
+            .find(doc! { "sequence_number": { "$gt": sequence_number as i64 } })
+            .sort(doc! { "sequence_number": 1 })
+            .await
+            .unwrap()
+            .collect::<Vec<Result<_, _>>>()
+            .await
+            .iter()
+            .filter_map(|r| r.clone().ok())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, ops::RangeInclusive};
+
+    use geo::Point;
+    use mobile_network_core_event::{
+        AccuracyFulfillmentIndicator, AreaOfInterestInformation, CivicAddress, GeographicArea,
+        LocationInfo, MinorLocationQoS, PositioningMethod, UpPathChangeInformation,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    fn monitoring_event_subscription(maximum_number_of_reports: u32) -> Subscription {
+        Subscription::MonitoringEvent {
+            scs_as_id: "test".to_string(),
+            subscription: serde_json::from_value(json!({
+                "msisdn": "1",
+                "notificationDestination": "http://localhost:8789/",
+                "monitoringType": "LOCATION_REPORTING",
+                "maximumNumberOfReports": maximum_number_of_reports,
+                "monitorExpireTime": "2020-01-27T17:00:09.012Z"
+            }))
+            .unwrap(),
+        }
+    }
+
+    #[test]
+    fn add_update_remove_subscription() {
+        let mut exposure = MobileNetworkExposure::new(Metrics::new());
+        let id = exposure.add_subscriber(EventSubscriber::new(
+            Url::parse("http://localhost:8789/").unwrap(),
+            EventKind::LocationReporting,
+            vec![1, 2, 3],
+        ));
+        assert!(exposure
+            .get_subscription(id)
+            .unwrap()
+            .get_subscriber()
+            .is_some());
+
+        exposure
+            .update_subscription(id, monitoring_event_subscription(1))
+            .unwrap();
+        assert!(exposure
+            .get_subscription(id)
+            .unwrap()
+            .get_subscriber()
+            .is_none());
+
+        assert!(exposure.remove_subscription(id).is_ok());
+        assert!(exposure.remove_subscription(id).is_err());
+        assert!(exposure.get_subscribers().is_empty());
+    }
+
+    #[test]
+    fn subscription_ids_are_unique() {
+        let mut exposure = MobileNetworkExposure::new(Metrics::new());
+        let first = exposure.add_subscription(monitoring_event_subscription(1));
+        exposure.remove_subscription(first).unwrap();
+        let second = exposure.add_subscription(monitoring_event_subscription(1));
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn remaining_reports_and_expiry() {
+        let mut subscriber = Subscriber::new(0, monitoring_event_subscription(3));
+        assert_eq!(subscriber.remaining_reports(), Some(3));
+        subscriber.number_of_reports = 3;
+        assert_eq!(subscriber.remaining_reports(), Some(0));
+        assert!(subscriber.is_expired(&Utc::now()));
+
+        let subscriber = Subscriber::new(
+            1,
+            Subscription::Event(EventSubscriber::new(
+                Url::parse("http://localhost:8789/").unwrap(),
+                EventKind::LocationReporting,
+                vec![],
+            )),
+        );
+        assert_eq!(subscriber.remaining_reports(), None);
+        assert!(!subscriber.is_expired(&Utc::now()));
+    }
+
+    fn up_path_changes(sequence_numbers: RangeInclusive<u64>) -> Vec<MobileNetworkCoreEvent> {
+        sequence_numbers
+            .map(|sequence_number| {
+                let mut event = MobileNetworkCoreEvent::new(
+                    Event::UpPathChange(UpPathChangeInformation::new(
+                        0,
+                        0,
+                        1,
+                        Ipv4Addr::new(10, 0, 0, 1),
+                    )),
+                    EventKind::UpPathChange,
+                    1,
+                );
+                event.set_sequence_number(sequence_number);
+                event
+            })
+            .collect()
+    }
+
+    #[test]
+    fn skipped_events_are_caught_up() {
+        let mut exposure = MobileNetworkExposure::new(Metrics::new());
+        let id = exposure.add_subscriber(EventSubscriber::new(
+            Url::parse("http://localhost:8789/").unwrap(),
+            EventKind::UpPathChange,
+            vec![1],
+        ));
+        let events = up_path_changes(1..=6);
+
+        exposure.notify(&events[..2], &[]);
+        // Events 3 and 4 are skipped, the cursor waits for them.
+        exposure.notify(&events[4..], &[]);
+        let subscriber = exposure.get_subscription(id).unwrap();
+        assert_eq!(subscriber.cursor, 2);
+        assert_eq!(subscriber.number_of_reports, 1);
+
+        // Caught up from the store, only what was not notified yet is reported.
+        exposure.notify(&events, &[]);
+        let subscriber = exposure.get_subscription(id).unwrap();
+        assert_eq!(subscriber.cursor, 6);
+        assert_eq!(subscriber.number_of_reports, 2);
+    }
+
+    #[test]
+    fn stream_only_subscriber_reaches_maximum_number_of_reports() {
+        let event_subscriber: EventSubscriber = serde_json::from_value(json!({
+            "kind": "UpPathChange",
+            "user_ids": [1],
+            "maximum_number_of_reports": 2
+        }))
+        .unwrap();
+        let mut exposure = MobileNetworkExposure::new(Metrics::new());
+        let id = exposure.add_subscriber(event_subscriber);
+        let events = up_path_changes(1..=3);
+
+        exposure.notify(&events[..2], &[]);
+        assert_eq!(exposure.get_subscription(id).unwrap().number_of_reports, 1);
+        exposure.notify(&events[2..], &[]);
+        assert!(exposure.get_subscription(id).is_none());
+        assert_eq!(exposure.take_terminated_subscriptions(), vec![id]);
+    }
+
+    fn location_report(sequence_number: u64) -> MobileNetworkCoreEvent {
+        let mut event = MobileNetworkCoreEvent::new(
+            Event::LocationReporting(LocationInfo::new(
+                0,
+                "0".to_string(),
+                GeographicArea::Point(Point::new(0.0, 0.0)),
+                CivicAddress {},
+                vec![PositioningMethod::CellId],
+                AccuracyFulfillmentIndicator::RequestedAccuracyFulfilled,
+                1.0,
+                LdrType::Motion,
+                MinorLocationQoS::new(1.0, 1.0),
+            )),
+            EventKind::LocationReporting,
+            1,
+        );
+        event.set_sequence_number(sequence_number);
+        event
+    }
+
+    #[test]
+    fn analytics_subscriber_waits_for_location_reports() {
+        let mut exposure = MobileNetworkExposure::new(Metrics::new());
+        let id = exposure.add_subscription(Subscription::AnalyticsExposure {
+            af_id: "test".to_string(),
+            subscription: serde_json::from_value(json!({
+                "analyEventsSubs": [{ "analyEvent": "UE_MOBILITY", "tgtUe": { "anyUeInd": true } }],
+                "notifUri": "http://localhost:8789/",
+                "notifId": "test"
+            }))
+            .unwrap(),
+        });
+        let history = vec![location_report(0)];
+
+        exposure.notify(&up_path_changes(1..=2), &history);
+        assert_eq!(exposure.get_subscription(id).unwrap().number_of_reports, 0);
+
+        exposure.notify(&[location_report(3)], &history);
+        assert_eq!(exposure.get_subscription(id).unwrap().number_of_reports, 1);
+    }
+
+    #[test]
+    fn subscriptions_start_after_last_event() {
+        let mut exposure = MobileNetworkExposure::new(Metrics::new());
+        exposure.notify(&up_path_changes(1..=3), &[]);
+        let id = exposure.add_subscription(monitoring_event_subscription(1));
+
+        assert_eq!(exposure.get_subscription(id).unwrap().cursor, 3);
+    }
+
+    #[test]
+    fn notification_starts_after_cursor() {
+        let mut subscriber = Subscriber::new(
+            0,
+            Subscription::Event(EventSubscriber::new(
+                Url::parse("http://localhost:8789/").unwrap(),
+                EventKind::UpPathChange,
+                vec![1],
+            )),
+        );
+        let events = up_path_changes(1..=3);
+
+        subscriber.cursor = 1;
+        let (_, res) = subscriber.notification(&events, &[]).unwrap();
+        assert_eq!(
+            res.iter()
+                .map(|event| event.get_sequence_number())
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+
+        subscriber.cursor = 3;
+        assert!(subscriber.notification(&events, &[]).is_none());
+    }
+
+    #[test]
+    fn area_of_interest_subscriber() {
+        let mut event_subscriber = EventSubscriber::new(
+            Url::parse("http://localhost:8789/").unwrap(),
+            EventKind::AreaOfInterest,
+            vec![],
+        );
+        assert!(event_subscriber.validate().is_err());
+        event_subscriber.patch(
+            serde_json::from_value(json!({
+                "area": {"circle": {"center": {"x": 0.0, "y": 0.0}, "radius": 50.0}},
+                "ldr_types": ["EnteringIntoArea", "LeavingFromArea"]
+            }))
+            .unwrap(),
+        );
+        assert!(event_subscriber.validate().is_ok());
+        event_subscriber.set_resource(3, "http://localhost/subscribers/3".to_string());
+
+        let area_event = |area_id, ldr_type| {
+            let mut event = MobileNetworkCoreEvent::new(
+                Event::AreaOfInterest(AreaOfInterestInformation::new(
+                    area_id,
+                    ldr_type,
+                    Point::new(1.0, 1.0),
+                    4,
+                )),
+                EventKind::AreaOfInterest,
+                7,
+            );
+            event.set_sequence_number(1);
+            event
+        };
+        assert!(event_subscriber.matches(&area_event(3, LdrType::EnteringIntoArea)));
+        assert!(!event_subscriber.matches(&area_event(3, LdrType::BeingInsideArea)));
+        assert!(!event_subscriber.matches(&area_event(2, LdrType::EnteringIntoArea)));
+
+        let subscriber = Subscriber::new(3, Subscription::Event(event_subscriber));
+        let (body, _) = subscriber
+            .notification(&[area_event(3, LdrType::LeavingFromArea)], &[])
+            .unwrap();
+        assert_eq!(body["number_of_ues"], 4);
+        assert_eq!(body["events"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn subscriber_survives_the_event_store() {
+        let mut event_subscriber = EventSubscriber::new(
+            Url::parse("http://localhost:8789/").unwrap(),
+            EventKind::AreaOfInterest,
+            vec![],
+        );
+        event_subscriber.area = Some(Area::Polygon {
+            points: vec![
+                Point::new(0.0, 0.0),
+                Point::new(10.0, 0.0),
+                Point::new(10.0, 10.0),
+            ],
+        });
+        for subscription in [
+            Subscription::Event(event_subscriber),
+            monitoring_event_subscription(2),
+        ] {
+            let mut subscriber = Subscriber::new(5, subscription);
+            subscriber.cursor = 42;
+            subscriber.number_of_reports = 1;
+
+            let document = mongodb::bson::to_document(&subscriber).unwrap();
+            let restored: Subscriber = mongodb::bson::from_document(document).unwrap();
+            assert_eq!(restored.cursor, 42);
+            assert_eq!(restored.number_of_reports, 1);
+            assert_eq!(
+                serde_json::to_value(&restored).unwrap(),
+                serde_json::to_value(&subscriber).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn patch_and_terminate_event_subscriber() {
+        let mut event_subscriber = EventSubscriber::new(
+            Url::parse("http://localhost:8789/").unwrap(),
+            EventKind::LocationReporting,
+            vec![1],
+        );
+        event_subscriber.patch(
+            serde_json::from_value(json!({
+                "user_ids": [1, 2],
+                "expire_time": "2020-01-27T17:00:09.012Z",
+                "maximum_number_of_reports": 2
+            }))
+            .unwrap(),
+        );
+        assert!(event_subscriber.validate().is_ok());
+        assert_eq!(event_subscriber.user_ids, vec![1, 2]);
+        event_subscriber.set_resource(4, "http://localhost/subscribers/4".to_string());
+
+        let mut subscriber = Subscriber::new(4, Subscription::Event(event_subscriber));
+        assert_eq!(
+            subscriber.termination_cause(&Utc::now()),
+            Some(TerminationCause::Expired)
+        );
+        subscriber.number_of_reports = 2;
+        let (_, termination) = subscriber
+            .termination(subscriber.termination_cause(&Utc::now()).unwrap())
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(termination).unwrap(),
+            json!({
+                "id": 4,
+                "self": "http://localhost/subscribers/4",
+                "cause": "MAXIMUM_NUMBER_OF_REPORTS_REACHED"
+            })
+        );
+    }
+
+    #[test]
+    fn stream_only_subscriber() {
+        let event_subscriber: EventSubscriber = serde_json::from_value(json!({
+            "kind": "UpPathChange",
+            "user_ids": [1]
+        }))
+        .unwrap();
+        assert!(event_subscriber.validate().is_ok());
+        let mut exposure = MobileNetworkExposure::new(Metrics::new());
+        let id = exposure.add_subscriber(event_subscriber);
+
+        let events: Vec<MobileNetworkCoreEvent> = [1, 2]
+            .into_iter()
+            .map(|user_id| {
+                MobileNetworkCoreEvent::new(
+                    Event::UpPathChange(UpPathChangeInformation::new(
+                        0,
+                        0,
+                        user_id,
+                        Ipv4Addr::new(10, 0, 0, 1),
+                    )),
+                    EventKind::UpPathChange,
+                    user_id,
+                )
+            })
+            .collect();
+        let filtered = exposure.filter_events(id, &events).unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].get_user_id(), 1);
+
+        let subscriber = exposure.remove_subscription(id).unwrap();
+        assert!(subscriber.termination(TerminationCause::Expired).is_none());
+        assert!(exposure.filter_events(id, &events).is_none());
+    }
 }
@@ -1,81 +1,583 @@
+use std::{collections::VecDeque, time::Duration};
+
 use actix_web::{
-    get, post,
-    web::{Data, Json},
-    Responder,
+    delete,
+    error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound},
+    get, patch, post, rt,
+    web::{Bytes, Data, Json, Path, Payload, Query, ReqData},
+    HttpRequest, HttpResponse, Responder,
 };
+use actix_ws::{CloseCode, CloseReason, Message};
+use futures::{stream, Stream, StreamExt};
+use log::warn;
 use mobile_network_core_event::MobileNetworkCoreEvent;
 use mongodb::Database;
-use tokio::sync::RwLock;
+use serde::Deserialize;
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    RwLock,
+};
+
+use crate::{
+    area_of_interest::AreaOfInterest,
+    authorization_endpoints::{operator_only, AfId, Operator},
+    delivery::{DeadLetter, DeliveryStatus},
+    mobile_network_core_endpoints::MobileNetworkCoreWrapper,
+    mobile_network_exposure::{
+        EventSubscriber, EventSubscriberPatch, MobileNetworkExposure, Subscription,
+    },
+    northbound::resource_url,
+};
 
-use crate::mobile_network_exposure::{MobileNetworkExposure, EventSubscriber};
+const DELIVERY_RETRY_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct MobileNetworkExposureWrapper {
-    mobile_network_core: RwLock<MobileNetworkExposure>,
+    pub mobile_network_exposure: RwLock<MobileNetworkExposure>,
 }
 
 impl MobileNetworkExposureWrapper {
     pub fn new(mobile_network_exposure: MobileNetworkExposure) -> Self {
         MobileNetworkExposureWrapper {
-            mobile_network_core: RwLock::new(mobile_network_exposure),
+            mobile_network_exposure: RwLock::new(mobile_network_exposure),
         }
     }
 }
 
+/// Pushes the events the core produces to the subscribers.
+/// Events arriving together, e.g. during one tick, are dispatched as one batch.
+/// Events skipped when the dispatcher falls behind are caught up from the event store.
+/// Whatever terminated subscriptions set up in the core is released afterwards.
+pub async fn dispatch_events(
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    mobile_network_core_wrapper: Data<MobileNetworkCoreWrapper>,
+    mut events: broadcast::Receiver<MobileNetworkCoreEvent>,
+    database: Data<Database>,
+) {
+    loop {
+        let batch = match events.recv().await {
+            Ok(event) => {
+                let mut batch = vec![event];
+                while let Ok(event) = events.try_recv() {
+                    batch.push(event);
+                }
+                Some(batch)
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(
+                    "event dispatcher fell behind and skipped {} events, catching up from the event store",
+                    skipped
+                );
+                None
+            }
+            Err(RecvError::Closed) => return,
+        };
+        let terminated_subscriptions = {
+            let mut mobile_network_exposure = mobile_network_exposure_wrapper
+                .mobile_network_exposure
+                .write()
+                .await;
+            match batch {
+                Some(batch) => {
+                    mobile_network_exposure
+                        .dispatch_events(&batch, &database)
+                        .await
+                }
+                None => mobile_network_exposure.publish_events(&database).await,
+            }
+            mobile_network_exposure.take_terminated_subscriptions()
+        };
+        deliver_due(&mobile_network_exposure_wrapper).await;
+        if terminated_subscriptions.is_empty() {
+            continue;
+        }
+        let mut mobile_network_core = mobile_network_core_wrapper
+            .mobile_network_core
+            .write()
+            .await;
+        for id in terminated_subscriptions {
+            mobile_network_core.release_subscription(id);
+        }
+    }
+}
+
+/// Periodically retries notifications that failed to be delivered.
+pub async fn retry_deliveries(mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>) {
+    let mut interval = tokio::time::interval(DELIVERY_RETRY_INTERVAL);
+    loop {
+        interval.tick().await;
+        deliver_due(&mobile_network_exposure_wrapper).await;
+    }
+}
+
+/// Sends the notifications that are due without holding the exposure lock,
+/// which is only taken to take them out and to record how they went.
+async fn deliver_due(mobile_network_exposure_wrapper: &MobileNetworkExposureWrapper) {
+    let due = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await
+        .take_due_deliveries();
+    if due.is_empty() {
+        return;
+    }
+    let results = due.send().await;
+    mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await
+        .record_deliveries(results);
+}
+
 /// This function makes a subscriber subscribe to events
+/// An area of interest in the subscription is handed to the core to be watched.
 #[post("/subscribers")]
 pub async fn post_subscribers(
+    request: HttpRequest,
     mobile_network_core_wrapper: Data<MobileNetworkExposureWrapper>,
+    core_wrapper: Data<MobileNetworkCoreWrapper>,
+    database: Data<Database>,
+    af_id: Option<ReqData<AfId>>,
     event_subscription: Json<EventSubscriber>,
-) -> impl Responder {
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut event_subscription = event_subscription.into_inner();
+    event_subscription.validate().map_err(ErrorBadRequest)?;
+    event_subscription.set_af_id(af_id.map(|af_id| af_id.into_inner().0));
     let mut mnc = mobile_network_core_wrapper
-        .mobile_network_core
+        .mobile_network_exposure
         .write()
         .await;
-    mnc.add_subscriber(event_subscription.into_inner());
-    "OK"
+    let id = mnc.add_subscriber(event_subscription.clone());
+    let location = resource_url(&request, id);
+    event_subscription.set_resource(id, location.clone());
+    mnc.update_subscription(id, Subscription::Event(event_subscription.clone()))
+        .map_err(ErrorInternalServerError)?;
+    if let Some(area) = event_subscription.get_area() {
+        core_wrapper
+            .mobile_network_core
+            .write()
+            .await
+            .add_area_of_interest(AreaOfInterest::new(id, area.clone()));
+    }
+    mnc.persist_subscriptions(&database).await;
+    Ok(HttpResponse::Created()
+        .insert_header(("Location", location))
+        .json(event_subscription.redacted()))
 }
 
 #[get("/subscribers")]
 pub async fn get_subscribers(
     mobile_network_core_wrapper: Data<MobileNetworkExposureWrapper>,
+    af_id: Option<ReqData<AfId>>,
 ) -> impl Responder {
-    let subscribers: Vec<EventSubscriber> = mobile_network_core_wrapper
-        .mobile_network_core
+    let af_id = af_id.as_deref().map(|AfId(af_id)| af_id.as_str());
+    let mnc = mobile_network_core_wrapper
+        .mobile_network_exposure
         .read()
-        .await
+        .await;
+    let subscribers: Vec<EventSubscriber> = mnc
         .get_subscribers()
         .into_iter()
-        .map(|subscriber| subscriber.get_subscriber())
-        .cloned()
+        .filter(|subscriber| mnc.is_visible_to(subscriber.get_id(), af_id))
+        .filter_map(|subscriber| subscriber.get_subscriber())
+        .map(|event_subscriber| event_subscriber.redacted())
         .collect();
     Json(subscribers)
 }
 
+#[get("/subscribers/{id}")]
+pub async fn get_subscriber(
+    id: Path<u32>,
+    mobile_network_core_wrapper: Data<MobileNetworkExposureWrapper>,
+    af_id: Option<ReqData<AfId>>,
+) -> Result<impl Responder, actix_web::Error> {
+    let af_id = af_id.as_deref().map(|AfId(af_id)| af_id.as_str());
+    let mnc = mobile_network_core_wrapper
+        .mobile_network_exposure
+        .read()
+        .await;
+    match mnc
+        .get_subscription(*id)
+        .filter(|_| mnc.is_visible_to(*id, af_id))
+        .and_then(|subscriber| subscriber.get_subscriber())
+    {
+        Some(event_subscriber) => Ok(Json(event_subscriber.redacted())),
+        None => Err(ErrorNotFound(format!(
+            "Subscriber with id {} does not exist",
+            id
+        ))),
+    }
+}
+
+/// Updates the fields of a subscription given in the body.
+/// The core only starts over with the area of interest if the area changed.
+#[patch("/subscribers/{id}")]
+pub async fn patch_subscriber(
+    id: Path<u32>,
+    mobile_network_core_wrapper: Data<MobileNetworkExposureWrapper>,
+    core_wrapper: Data<MobileNetworkCoreWrapper>,
+    database: Data<Database>,
+    af_id: Option<ReqData<AfId>>,
+    patch: Json<EventSubscriberPatch>,
+) -> Result<impl Responder, actix_web::Error> {
+    let af_id = af_id.as_deref().map(|AfId(af_id)| af_id.as_str());
+    let mut mnc = mobile_network_core_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    let mut event_subscriber = match mnc
+        .get_subscription(*id)
+        .filter(|_| mnc.is_visible_to(*id, af_id))
+        .and_then(|subscriber| subscriber.get_subscriber())
+    {
+        Some(event_subscriber) => event_subscriber.clone(),
+        None => {
+            return Err(ErrorNotFound(format!(
+                "Subscriber with id {} does not exist",
+                id
+            )))
+        }
+    };
+    let previous_area = event_subscriber.get_area().cloned();
+    event_subscriber.patch(patch.into_inner());
+    event_subscriber.validate().map_err(ErrorBadRequest)?;
+    mnc.update_subscription(*id, Subscription::Event(event_subscriber.clone()))
+        .map_err(ErrorInternalServerError)?;
+    if event_subscriber.get_area() != previous_area.as_ref() {
+        let mut core = core_wrapper.mobile_network_core.write().await;
+        core.remove_area_of_interest(*id);
+        if let Some(area) = event_subscriber.get_area() {
+            core.add_area_of_interest(AreaOfInterest::new(*id, area.clone()));
+        }
+    }
+    mnc.persist_subscriptions(&database).await;
+    Ok(Json(event_subscriber.redacted()))
+}
+
+#[delete("/subscribers/{id}")]
+pub async fn delete_subscriber(
+    id: Path<u32>,
+    mobile_network_core_wrapper: Data<MobileNetworkExposureWrapper>,
+    core_wrapper: Data<MobileNetworkCoreWrapper>,
+    database: Data<Database>,
+    af_id: Option<ReqData<AfId>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let af_id = af_id.as_deref().map(|AfId(af_id)| af_id.as_str());
+    let mut mnc = mobile_network_core_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    if mnc
+        .get_subscription(*id)
+        .filter(|_| mnc.is_visible_to(*id, af_id))
+        .and_then(|subscriber| subscriber.get_subscriber())
+        .is_none()
+    {
+        return Err(ErrorNotFound(format!(
+            "Subscriber with id {} does not exist",
+            id
+        )));
+    }
+    mnc.remove_subscription(*id)
+        .map_err(ErrorInternalServerError)?;
+    core_wrapper
+        .mobile_network_core
+        .write()
+        .await
+        .remove_area_of_interest(*id);
+    mnc.persist_subscriptions(&database).await;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Where an event stream resumes, the sequence number of the last event the client received.
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    cursor: Option<u64>,
+}
+
+/// The events of one native subscriber read from a stream instead of being pushed to its notify endpoint.
+struct EventStream {
+    id: u32,
+    cursor: u64,
+    pending: VecDeque<MobileNetworkCoreEvent>,
+    events: broadcast::Receiver<MobileNetworkCoreEvent>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    database: Data<Database>,
+}
+
+impl EventStream {
+    /// Starts after `cursor`, replaying the stored events past it, or with the next event the core produces.
+    async fn open(
+        id: u32,
+        cursor: Option<u64>,
+        af_id: Option<&str>,
+        mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+        core_wrapper: Data<MobileNetworkCoreWrapper>,
+        database: Data<Database>,
+    ) -> Result<Self, actix_web::Error> {
+        // Subscribing and reading the last sequence number together leaves no gap to the stored events.
+        let (events, last_sequence_number) = {
+            let mobile_network_core = core_wrapper.mobile_network_core.read().await;
+            (
+                mobile_network_core.subscribe_events(),
+                mobile_network_core.get_last_sequence_number(),
+            )
+        };
+        let cursor = cursor.unwrap_or(last_sequence_number);
+        let mnc = mobile_network_exposure_wrapper
+            .mobile_network_exposure
+            .read()
+            .await;
+        let stored = if cursor < last_sequence_number {
+            mnc.get_events_since(&database, cursor).await
+        } else {
+            Vec::new()
+        };
+        let pending = mnc
+            .filter_events(id, &stored)
+            .filter(|_| mnc.is_visible_to(id, af_id))
+            .ok_or_else(|| ErrorNotFound(format!("Subscriber with id {} does not exist", id)))?;
+        drop(mnc);
+        Ok(Self {
+            id,
+            cursor: stored
+                .last()
+                .map(|event| event.get_sequence_number())
+                .unwrap_or(cursor),
+            pending: pending.into(),
+            events,
+            mobile_network_exposure_wrapper,
+            database,
+        })
+    }
+
+    /// The next event for the subscriber, `None` once its subscription has ended.
+    /// Events the stream fell behind on are read back from the event store.
+    async fn next(&mut self) -> Option<MobileNetworkCoreEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            let mnc = &self.mobile_network_exposure_wrapper.mobile_network_exposure;
+            let batch = match self.events.recv().await {
+                Ok(event) => vec![event],
+                Err(RecvError::Lagged(_)) => {
+                    mnc.read()
+                        .await
+                        .get_events_since(&self.database, self.cursor)
+                        .await
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            let batch: Vec<MobileNetworkCoreEvent> = batch
+                .into_iter()
+                .filter(|event| event.get_sequence_number() > self.cursor)
+                .collect();
+            if let Some(last_event) = batch.last() {
+                self.cursor = last_event.get_sequence_number();
+            }
+            self.pending = mnc.read().await.filter_events(self.id, &batch)?.into();
+        }
+    }
+
+    fn into_stream(self) -> impl Stream<Item = MobileNetworkCoreEvent> {
+        stream::unfold(self, |mut event_stream| async move {
+            event_stream.next().await.map(|event| (event, event_stream))
+        })
+    }
+}
+
+/// Server-Sent Events stream of the events a native subscriber would be notified about.
+/// Each message has the event's sequence number as id, reconnecting with `Last-Event-ID`
+/// or `?cursor=` resumes after it. The stream ends when the subscription does.
+#[get("/subscribers/{id}/stream")]
+pub async fn get_subscriber_stream(
+    request: HttpRequest,
+    id: Path<u32>,
+    query: Query<StreamQuery>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    core_wrapper: Data<MobileNetworkCoreWrapper>,
+    database: Data<Database>,
+    af_id: Option<ReqData<AfId>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let last_event_id = request
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok());
+    let event_stream = EventStream::open(
+        *id,
+        query.cursor.or(last_event_id),
+        af_id.as_deref().map(|AfId(af_id)| af_id.as_str()),
+        mobile_network_exposure_wrapper,
+        core_wrapper,
+        database,
+    )
+    .await?;
+    let messages = event_stream.into_stream().map(|event| {
+        Ok::<_, actix_web::Error>(Bytes::from(format!(
+            "id: {}\nevent: event\ndata: {}\n\n",
+            event.get_sequence_number(),
+            serde_json::to_string(&event).unwrap()
+        )))
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(messages))
+}
+
+/// WebSocket carrying the same events as `/subscribers/{id}/stream`, one JSON event per text message.
+/// Resumes after `?cursor=`, the connection is closed when the subscription ends.
+#[get("/subscribers/{id}/ws")]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_subscriber_websocket(
+    request: HttpRequest,
+    body: Payload,
+    id: Path<u32>,
+    query: Query<StreamQuery>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    core_wrapper: Data<MobileNetworkCoreWrapper>,
+    database: Data<Database>,
+    af_id: Option<ReqData<AfId>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let event_stream = EventStream::open(
+        *id,
+        query.cursor,
+        af_id.as_deref().map(|AfId(af_id)| af_id.as_str()),
+        mobile_network_exposure_wrapper,
+        core_wrapper,
+        database,
+    )
+    .await?;
+    let (response, mut session, mut messages) = actix_ws::handle(&request, body)?;
+    // The WebSocket session is tied to this worker's thread.
+    rt::spawn(async move {
+        let mut events = Box::pin(event_stream.into_stream());
+        loop {
+            let sent = tokio::select! {
+                event = events.next() => match event {
+                    Some(event) => session.text(serde_json::to_string(&event).unwrap()).await,
+                    None => {
+                        let _ = session
+                            .close(Some(CloseReason {
+                                code: CloseCode::Normal,
+                                description: Some("subscription ended".to_string()),
+                            }))
+                            .await;
+                        return;
+                    }
+                },
+                message = messages.next() => match message {
+                    Some(Ok(Message::Ping(bytes))) => session.pong(&bytes).await,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                        let _ = session.close(None).await;
+                        return;
+                    }
+                    _ => Ok(()),
+                },
+            };
+            if sent.is_err() {
+                return;
+            }
+        }
+    });
+    Ok(response)
+}
+
+#[get("/deliveries")]
+pub async fn get_delivery_statuses(
+    mobile_network_core_wrapper: Data<MobileNetworkExposureWrapper>,
+    af_id: Option<ReqData<AfId>>,
+) -> impl Responder {
+    let af_id = af_id.as_deref().map(|AfId(af_id)| af_id.as_str());
+    let mnc = mobile_network_core_wrapper
+        .mobile_network_exposure
+        .read()
+        .await;
+    let statuses: Vec<DeliveryStatus> = mnc
+        .get_delivery_statuses()
+        .into_iter()
+        .filter(|status| mnc.is_visible_to(status.get_subscription_id(), af_id))
+        .cloned()
+        .collect();
+    Json(statuses)
+}
+
+#[get("/deliveries/{id}")]
+pub async fn get_delivery_status(
+    id: Path<u32>,
+    mobile_network_core_wrapper: Data<MobileNetworkExposureWrapper>,
+    af_id: Option<ReqData<AfId>>,
+) -> Result<impl Responder, actix_web::Error> {
+    let af_id = af_id.as_deref().map(|AfId(af_id)| af_id.as_str());
+    let mnc = mobile_network_core_wrapper
+        .mobile_network_exposure
+        .read()
+        .await;
+    match mnc
+        .get_delivery_status(*id)
+        .filter(|_| mnc.is_visible_to(*id, af_id))
+    {
+        Some(status) => Ok(Json(status.clone())),
+        None => Err(ErrorNotFound(format!(
+            "No deliveries for subscription with id {}",
+            id
+        ))),
+    }
+}
+
+#[get("/dead_letters")]
+pub async fn get_dead_letters(
+    mobile_network_core_wrapper: Data<MobileNetworkExposureWrapper>,
+    af_id: Option<ReqData<AfId>>,
+) -> impl Responder {
+    let af_id = af_id.as_deref().map(|AfId(af_id)| af_id.as_str());
+    let mnc = mobile_network_core_wrapper
+        .mobile_network_exposure
+        .read()
+        .await;
+    let dead_letters: Vec<DeadLetter> = mnc
+        .get_dead_letters()
+        .into_iter()
+        .filter(|dead_letter| mnc.is_visible_to(dead_letter.get_subscription_id(), af_id))
+        .cloned()
+        .collect();
+    Json(dead_letters)
+}
+
+/// Every stored event, whoever it concerns, so only operator clients may read it when authorization is enabled.
 #[get("/events")]
 pub async fn get_events(
     mobile_network_core_wrapper: Data<MobileNetworkExposureWrapper>,
     database: Data<Database>,
-) -> impl Responder {
+    af_id: Option<ReqData<AfId>>,
+    operator: Option<ReqData<Operator>>,
+) -> Result<impl Responder, actix_web::Error> {
+    operator_only(af_id, operator)?;
     let events: Vec<MobileNetworkCoreEvent> = mobile_network_core_wrapper
-        .mobile_network_core
+        .mobile_network_exposure
         .read()
         .await
         .get_events(&database)
         .await;
-    Json(events)
+    Ok(Json(events))
 }
 
 /// Endpoint to publish events
+/// It notifies every subscriber, so only operator clients may publish when authorization is enabled.
 #[post("/events/publish")]
 pub async fn publish_events(
     mobile_network_core_wrapper: Data<MobileNetworkExposureWrapper>,
     database: Data<Database>,
-) -> impl Responder {
+    af_id: Option<ReqData<AfId>>,
+    operator: Option<ReqData<Operator>>,
+) -> Result<impl Responder, actix_web::Error> {
+    operator_only(af_id, operator)?;
     mobile_network_core_wrapper
-        .mobile_network_core
+        .mobile_network_exposure
         .write()
         .await
         .publish_events(&database)
         .await;
-    "OK"
+    Ok("OK")
 }
@@ -0,0 +1,309 @@
+use chrono::{DateTime, Utc};
+use mobile_network_core_event::{
+    Event, EventKind, GeographicArea, InterfaceIndication, MobileNetworkCoreEvent,
+    PdnConnectionStatus, PdnType,
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::northbound::{msisdn, resolve_user_id};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MonitoringType {
+    LocationReporting,
+    PdnConnectivityStatus,
+}
+
+impl MonitoringType {
+    fn event_kind(&self) -> EventKind {
+        match self {
+            MonitoringType::LocationReporting => EventKind::LocationReporting,
+            MonitoringType::PdnConnectivityStatus => EventKind::PdnConnectionEvent,
+        }
+    }
+}
+
+/// The MonitoringEventSubscription resource of TS 29.122.
+/// Only the attributes the emulator can act on are modelled, everything else is ignored.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitoringEventSubscription {
+    #[serde(rename = "self", skip_serializing_if = "Option::is_none")]
+    self_link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    msisdn: Option<String>,
+    notification_destination: String,
+    monitoring_type: MonitoringType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maximum_number_of_reports: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    monitor_expire_time: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supported_features: Option<String>,
+}
+
+impl MonitoringEventSubscription {
+    /// Checks that the subscription targets a known kind of UE identifier and a valid callback.
+    pub fn validate(&self) -> Result<(), String> {
+        if Url::parse(&self.notification_destination).is_err() {
+            return Err(format!(
+                "notificationDestination {} is not a valid URL",
+                self.notification_destination
+            ));
+        }
+        if self.maximum_number_of_reports == Some(0) {
+            return Err("maximumNumberOfReports must be at least 1".to_string());
+        }
+        match self.user_id() {
+            Some(_) => Ok(()),
+            None => Err("externalId or msisdn must identify a UE".to_string()),
+        }
+    }
+
+    pub fn user_id(&self) -> Option<u32> {
+        self.external_id
+            .as_deref()
+            .or(self.msisdn.as_deref())
+            .and_then(resolve_user_id)
+    }
+
+    pub fn set_self_link(&mut self, self_link: String) {
+        self.self_link = Some(self_link);
+    }
+
+    pub fn get_notification_destination(&self) -> Url {
+        Url::parse(&self.notification_destination).unwrap()
+    }
+
+    pub fn get_maximum_number_of_reports(&self) -> Option<u32> {
+        self.maximum_number_of_reports
+    }
+
+    pub fn get_monitor_expire_time(&self) -> Option<DateTime<Utc>> {
+        self.monitor_expire_time
+    }
+
+    pub fn matches(&self, event: &MobileNetworkCoreEvent) -> bool {
+        *event.get_event_type() == self.monitoring_type.event_kind()
+            && Some(event.get_user_id()) == self.user_id()
+    }
+
+    /// Wraps `events` in a MonitoringNotification, `cancel` marks the final notification.
+    pub fn notification(
+        &self,
+        events: &[MobileNetworkCoreEvent],
+        cancel: bool,
+    ) -> MonitoringNotification {
+        MonitoringNotification {
+            subscription: self.self_link.clone().unwrap_or_default(),
+            monitoring_event_reports: events.iter().map(|event| self.report(event)).collect(),
+            cancel_ind: if cancel { Some(true) } else { None },
+        }
+    }
+
+    fn report(&self, event: &MobileNetworkCoreEvent) -> MonitoringEventReport {
+        let (location_info, pdn_conn_info_list) = match event.get_event() {
+            Event::LocationReporting(location_info) => (
+                Some(LocationInfo {
+                    age_of_location_info: 0,
+                    cell_id: location_info.e_node_b_id.clone(),
+                    enode_b_id: location_info.e_node_b_id.clone(),
+                    geographic_area: match &location_info.geographic_area {
+                        GeographicArea::Point(point) => Some(GeographicPoint {
+                            shape: "POINT".to_string(),
+                            point: GeographicalCoordinates {
+                                lon: point.x(),
+                                lat: point.y(),
+                            },
+                        }),
+                        _ => None,
+                    },
+                }),
+                None,
+            ),
+            Event::PdnConnectionEvent(pdn_connection_information) => (
+                None,
+                Some(vec![PdnConnectionInformation {
+                    status: match pdn_connection_information.status {
+                        PdnConnectionStatus::Created => "CREATED",
+                        PdnConnectionStatus::Released => "RELEASED",
+                    }
+                    .to_string(),
+                    apn: pdn_connection_information.apn.clone(),
+                    pdn_type: match pdn_connection_information.pdn_type {
+                        PdnType::Ipv4 => "IPV4",
+                        PdnType::Ipv6 => "IPV6",
+                        PdnType::Ipv4v6 => "IPV4V6",
+                        PdnType::NonIP => "NON_IP",
+                        PdnType::Ethernet => "ETHERNET",
+                    }
+                    .to_string(),
+                    interface_ind: match pdn_connection_information.interface_ind {
+                        InterfaceIndication::ExposureFunction => "EXPOSURE_FUNCTION",
+                        InterfaceIndication::PdnGateway => "PDN_GATEWAY",
+                    }
+                    .to_string(),
+                    ipv4_addr: pdn_connection_information.ipv4_addr.to_string(),
+                }]),
+            ),
+            _ => (None, None),
+        };
+        MonitoringEventReport {
+            external_id: self.external_id.clone(),
+            msisdn: match self.external_id {
+                Some(_) => None,
+                None => Some(msisdn(event.get_user_id())),
+            },
+            monitoring_type: self.monitoring_type.clone(),
+            event_time: DateTime::from_timestamp(
+                event.get_timestamp().as_secs() as i64,
+                event.get_timestamp().subsec_nanos(),
+            )
+            .unwrap_or_default(),
+            location_info,
+            pdn_conn_info_list,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeographicalCoordinates {
+    lon: f64,
+    lat: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeographicPoint {
+    shape: String,
+    point: GeographicalCoordinates,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationInfo {
+    age_of_location_info: u32,
+    cell_id: String,
+    enode_b_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geographic_area: Option<GeographicPoint>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdnConnectionInformation {
+    status: String,
+    apn: String,
+    pdn_type: String,
+    interface_ind: String,
+    ipv4_addr: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitoringEventReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    msisdn: Option<String>,
+    monitoring_type: MonitoringType,
+    event_time: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location_info: Option<LocationInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pdn_conn_info_list: Option<Vec<PdnConnectionInformation>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitoringNotification {
+    subscription: String,
+    monitoring_event_reports: Vec<MonitoringEventReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cancel_ind: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use mobile_network_core_event::PdnConnectionInformation as CorePdnConnectionInformation;
+    use serde_json::json;
+
+    use super::*;
+
+    fn subscription() -> MonitoringEventSubscription {
+        serde_json::from_value(json!({
+            "externalId": "3@domain.com",
+            "notificationDestination": "http://localhost:8789/",
+            "monitoringType": "PDN_CONNECTIVITY_STATUS",
+            "maximumNumberOfReports": 2,
+            "monitorExpireTime": "2030-01-27T17:00:09.012Z",
+            "reachabilityType": "DATA"
+        }))
+        .unwrap()
+    }
+
+    fn pdn_event(user_id: u32) -> MobileNetworkCoreEvent {
+        MobileNetworkCoreEvent::new(
+            Event::PdnConnectionEvent(CorePdnConnectionInformation::new(
+                PdnConnectionStatus::Created,
+                PdnType::Ipv4,
+                InterfaceIndication::ExposureFunction,
+                Ipv4Addr::new(10, 0, 0, 1),
+            )),
+            EventKind::PdnConnectionEvent,
+            user_id,
+        )
+    }
+
+    #[test]
+    fn deserialize_and_validate() {
+        let subscription = subscription();
+
+        assert!(subscription.validate().is_ok());
+        assert_eq!(subscription.user_id(), Some(3));
+        assert_eq!(subscription.get_maximum_number_of_reports(), Some(2));
+        assert!(subscription.get_monitor_expire_time().is_some());
+    }
+
+    #[test]
+    fn validate_unknown_ue_should_fail() {
+        let mut subscription = subscription();
+        subscription.external_id = Some("fredrik@domain.com".to_string());
+
+        assert!(subscription.validate().is_err());
+    }
+
+    #[test]
+    fn matches() {
+        let subscription = subscription();
+
+        assert!(subscription.matches(&pdn_event(3)));
+        assert!(!subscription.matches(&pdn_event(4)));
+    }
+
+    #[test]
+    fn notification() {
+        let mut subscription = subscription();
+        subscription.set_self_link("http://localhost/subscriptions/0".to_string());
+
+        let notification =
+            serde_json::to_value(subscription.notification(&[pdn_event(3)], true)).unwrap();
+
+        assert_eq!(
+            notification["subscription"],
+            "http://localhost/subscriptions/0"
+        );
+        assert_eq!(notification["cancelInd"], true);
+        let report = &notification["monitoringEventReports"][0];
+        assert_eq!(report["externalId"], "3@domain.com");
+        assert_eq!(report["monitoringType"], "PDN_CONNECTIVITY_STATUS");
+        assert_eq!(report["pdnConnInfoList"][0]["status"], "CREATED");
+        assert_eq!(report["pdnConnInfoList"][0]["ipv4Addr"], "10.0.0.1");
+    }
+}
@@ -0,0 +1,187 @@
+use actix_web::{
+    delete, get, post, put,
+    web::{Data, Json, Path},
+    HttpRequest, HttpResponse,
+};
+use mongodb::Database;
+
+use crate::{
+    mobile_network_exposure::Subscription,
+    mobile_network_exposure_endpoints::MobileNetworkExposureWrapper,
+    monitoring_event::MonitoringEventSubscription,
+    northbound::{request_url, resource_url, ProblemDetails},
+};
+
+fn monitoring_event_subscription<'a>(
+    subscription: &'a Subscription,
+    scs_as_id: &str,
+) -> Option<&'a MonitoringEventSubscription> {
+    match subscription {
+        Subscription::MonitoringEvent {
+            scs_as_id: owner,
+            subscription,
+        } if owner == scs_as_id => Some(subscription),
+        _ => None,
+    }
+}
+
+#[get("/3gpp-monitoring-event/v1/{scs_as_id}/subscriptions")]
+pub async fn get_monitoring_event_subscriptions(
+    scs_as_id: Path<String>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+) -> HttpResponse {
+    let subscriptions: Vec<MonitoringEventSubscription> = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .read()
+        .await
+        .get_subscribers()
+        .into_iter()
+        .filter_map(|subscriber| {
+            monitoring_event_subscription(subscriber.get_subscription(), &scs_as_id)
+        })
+        .cloned()
+        .collect();
+    HttpResponse::Ok().json(subscriptions)
+}
+
+/// Creates a monitoring event subscription, answering with the created resource and its location.
+#[post("/3gpp-monitoring-event/v1/{scs_as_id}/subscriptions")]
+pub async fn post_monitoring_event_subscription(
+    request: HttpRequest,
+    scs_as_id: Path<String>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    database: Data<Database>,
+    subscription: Json<MonitoringEventSubscription>,
+) -> HttpResponse {
+    let mut subscription = subscription.into_inner();
+    if let Err(err) = subscription.validate() {
+        return ProblemDetails::bad_request(&err);
+    }
+
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    let id = mobile_network_exposure.add_subscription(Subscription::MonitoringEvent {
+        scs_as_id: scs_as_id.clone(),
+        subscription: subscription.clone(),
+    });
+    let location = resource_url(&request, id);
+    subscription.set_self_link(location.clone());
+    mobile_network_exposure
+        .update_subscription(
+            id,
+            Subscription::MonitoringEvent {
+                scs_as_id: scs_as_id.into_inner(),
+                subscription: subscription.clone(),
+            },
+        )
+        .unwrap();
+
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::Created()
+        .insert_header(("Location", location))
+        .json(subscription)
+}
+
+#[get("/3gpp-monitoring-event/v1/{scs_as_id}/subscriptions/{subscription_id}")]
+pub async fn get_monitoring_event_subscription(
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+) -> HttpResponse {
+    let (scs_as_id, subscription_id) = path.into_inner();
+    match mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .read()
+        .await
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            monitoring_event_subscription(subscriber.get_subscription(), &scs_as_id)
+        }) {
+        Some(subscription) => HttpResponse::Ok().json(subscription),
+        None => ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        )),
+    }
+}
+
+#[put("/3gpp-monitoring-event/v1/{scs_as_id}/subscriptions/{subscription_id}")]
+pub async fn put_monitoring_event_subscription(
+    request: HttpRequest,
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    database: Data<Database>,
+    subscription: Json<MonitoringEventSubscription>,
+) -> HttpResponse {
+    let (scs_as_id, subscription_id) = path.into_inner();
+    let mut subscription = subscription.into_inner();
+    if let Err(err) = subscription.validate() {
+        return ProblemDetails::bad_request(&err);
+    }
+
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    if mobile_network_exposure
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            monitoring_event_subscription(subscriber.get_subscription(), &scs_as_id)
+        })
+        .is_none()
+    {
+        return ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        ));
+    }
+    subscription.set_self_link(request_url(&request));
+    mobile_network_exposure
+        .update_subscription(
+            subscription_id,
+            Subscription::MonitoringEvent {
+                scs_as_id,
+                subscription: subscription.clone(),
+            },
+        )
+        .unwrap();
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::Ok().json(subscription)
+}
+
+#[delete("/3gpp-monitoring-event/v1/{scs_as_id}/subscriptions/{subscription_id}")]
+pub async fn delete_monitoring_event_subscription(
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    database: Data<Database>,
+) -> HttpResponse {
+    let (scs_as_id, subscription_id) = path.into_inner();
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    if mobile_network_exposure
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            monitoring_event_subscription(subscriber.get_subscription(), &scs_as_id)
+        })
+        .is_none()
+    {
+        return ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        ));
+    }
+    mobile_network_exposure
+        .remove_subscription(subscription_id)
+        .unwrap();
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::NoContent().finish()
+}
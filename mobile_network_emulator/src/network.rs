@@ -1,9 +1,27 @@
-use std::{error::Error, fmt::Display, time::{Duration, SystemTime}};
+use std::{
+    error::Error,
+    fmt::Display,
+    time::{Duration, Instant, SystemTime},
+};
 
-use geo::{EuclideanDistance, Point};
-use serde::Serialize;
+use mobile_network_core_event::MigrationStatus;
 
-use crate::{application::Application, edge_data_center::EdgeDataCenter, pdu_session::PDUSession};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    application::{Application, DeploymentState},
+    application_catalogue::{
+        ApplicationCatalogue, ApplicationProfile, DEFAULT_COMPUTE_PER_REQUEST_MS,
+        DEFAULT_REQUEST_SIZE_KB, DEFAULT_RESPONSE_SIZE_KB,
+    },
+    edge_data_center::EdgeDataCenter,
+    migration::{Migration, MigrationRequest, DEFAULT_STATE_SIZE_MB},
+    pdu_session::PDUSession,
+    topology::{Node, Topology},
+    traffic_influence::TrafficInfluenceRule,
+    usage::Usage,
+};
 
 #[derive(Debug)]
 pub struct NetworkError {
@@ -26,75 +44,580 @@ impl Display for NetworkError {
 
 impl Error for NetworkError {}
 
+/// How a request to an application ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestOutcome {
+    Success,
+    /// The request was served, but later than the request timeout.
+    Timeout,
+    /// The request was lost on its way to the edge data center.
+    Dropped,
+}
+
+impl RequestOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestOutcome::Success => "success",
+            RequestOutcome::Timeout => "timeout",
+            RequestOutcome::Dropped => "dropped",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct NetworkLogEntry {
     user_id: u32,
     ip_address: String,
-    time_used: u64,
+    /// The delay of the request in microseconds, `None` if it was lost without a timeout to wait for.
+    time_used_us: Option<u64>,
+    outcome: RequestOutcome,
     application_id: u32,
+    /// The edge data center or the cloud that served the request.
+    data_center: Node,
+    /// Whether the request succeeded within the latency SLO of the application, `None` if it has none.
+    within_slo: Option<bool>,
     timestamp: u64,
+    #[serde(skip)]
+    delay: Option<Duration>,
 }
 
 impl NetworkLogEntry {
-    pub fn new(user_id: u32, ip_address: String, time_used: u64, application_id: u32) -> Self {
+    pub fn new(
+        user_id: u32,
+        ip_address: String,
+        delay: Option<Duration>,
+        outcome: RequestOutcome,
+        application_id: u32,
+        data_center: Node,
+        latency_slo: Option<Duration>,
+    ) -> Self {
         Self {
             user_id,
             ip_address,
-            time_used,
+            time_used_us: delay.map(|delay| delay.as_micros() as u64),
+            outcome,
+            delay,
             application_id,
+            data_center,
+            within_slo: latency_slo.map(|latency_slo| {
+                outcome == RequestOutcome::Success
+                    && delay.is_some_and(|delay| delay <= latency_slo)
+            }),
             timestamp: SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
         }
     }
+
+    /// `None` for a request lost without a timeout, the user never gets an answer.
+    pub fn get_delay(&self) -> Option<Duration> {
+        self.delay
+    }
+
+    pub fn get_outcome(&self) -> RequestOutcome {
+        self.outcome
+    }
+
+    pub fn get_application_id(&self) -> u32 {
+        self.application_id
+    }
+
+    pub fn get_data_center(&self) -> Node {
+        self.data_center
+    }
+
+    pub fn get_within_slo(&self) -> Option<bool> {
+        self.within_slo
+    }
+
+    /// The edge data center that served the request, `None` if the cloud did.
+    pub fn get_edge_data_center_id(&self) -> Option<u32> {
+        match self.data_center {
+            Node::EdgeDataCenter(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// How a request picks among the edge data centers hosting a replica of the application.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplicaSelection {
+    /// The replica with the lowest latency route from the RAN.
+    #[default]
+    LowestLatency,
+    /// The replica whose edge data center has the least work queued this tick.
+    LeastLoaded,
+    /// Any replica, chosen uniformly at random.
+    Random,
+}
+
+/// A replica of an application and the requests it has served.
+#[derive(Debug, Clone, Serialize)]
+pub struct Replica {
+    edge_data_center_id: u32,
+    usage: u32,
 }
 
 pub struct Network {
     edge_data_centers: Vec<EdgeDataCenter>,
+    topology: Topology,
+    traffic_influence_rules: Vec<TrafficInfluenceRule>,
+    /// How long users wait for a response, requests never time out without it.
+    request_timeout: Option<Duration>,
+    replica_selection: ReplicaSelection,
+    migrations: Vec<Migration>,
+    next_migration_id: u32,
+    /// Serves requests no edge data center can.
+    cloud: Option<EdgeDataCenter>,
+    /// The applications the cloud serves, every application if `None`.
+    cloud_application_ids: Option<Vec<u32>>,
+    catalogue: ApplicationCatalogue,
 }
 
 impl Network {
-    pub fn new(edge_data_centers: Vec<EdgeDataCenter>) -> Self {
-        Network { edge_data_centers }
+    pub fn new(edge_data_centers: Vec<EdgeDataCenter>, topology: Topology) -> Self {
+        Network {
+            edge_data_centers,
+            topology,
+            traffic_influence_rules: Vec::new(),
+            request_timeout: None,
+            replica_selection: ReplicaSelection::default(),
+            migrations: Vec::new(),
+            next_migration_id: 0,
+            cloud: None,
+            cloud_application_ids: None,
+            catalogue: ApplicationCatalogue::default(),
+        }
     }
 
-    pub fn use_application(
+    pub fn set_catalogue(&mut self, catalogue: ApplicationCatalogue) {
+        self.catalogue = catalogue;
+    }
+
+    pub fn get_catalogue(&self) -> &ApplicationCatalogue {
+        &self.catalogue
+    }
+
+    /// Adds the profile of application `id` to the catalogue, replacing the one it had.
+    /// Returns whether the application is new to the catalogue.
+    pub fn put_application_profile(&mut self, id: u32, profile: ApplicationProfile) -> bool {
+        self.catalogue.put(id, profile)
+    }
+
+    /// Removes an application from the catalogue, it may not be deployed to any edge data center.
+    pub fn remove_application_profile(
         &mut self,
-        user: &PDUSession,
-        application: &Application,
-        ran_position: &Point,
-    ) -> Result<NetworkLogEntry, NetworkError> {
-        match self
+        id: u32,
+    ) -> Result<ApplicationProfile, NetworkError> {
+        if let Some(edge_data_center) = self
             .edge_data_centers
-            .iter_mut()
-            .find(|edge_data_center| edge_data_center.contains_application(&application.id()))
+            .iter()
+            .find(|edge_data_center| edge_data_center.contains_application(&id))
         {
-            Some(edge_data_center) => {
-                //We know that the edge data center has the application.
-                let delay = Self::generate_delay(ran_position, edge_data_center.get_position());
-                let now = SystemTime::now();
-                let _usage = edge_data_center
-                    .use_application(*user.ip(), application)
-                    .unwrap();
-
-                let final_delay = now.elapsed().unwrap() + delay;
-                let network_log_entry = NetworkLogEntry::new(
-                    user.user().get_id(),
-                    user.ip().to_string(),
-                    final_delay.as_secs(),
-                    application.id(),
-                );
-
-                Ok(network_log_entry)
+            return Err(NetworkError::new(&format!(
+                "Application with id {} is still deployed to edge data center {}",
+                id,
+                edge_data_center.get_id()
+            )));
+        }
+        self.catalogue
+            .remove(id)
+            .map_err(|err| NetworkError::new(&err.to_string()))
+    }
+
+    /// Serves the applications in `application_ids`, or every application if `None`, from `cloud`
+    /// when no edge data center can. The cloud deploys an application the first time it is requested.
+    pub fn set_cloud(&mut self, cloud: EdgeDataCenter, application_ids: Option<Vec<u32>>) {
+        self.cloud = Some(cloud);
+        self.cloud_application_ids = application_ids;
+    }
+
+    /// Whether the cloud hosts the application and may serve it, applications are never deployed there on demand.
+    fn cloud_serves(&self, application_id: u32) -> bool {
+        self.cloud
+            .as_ref()
+            .is_some_and(|cloud| cloud.contains_application(&application_id))
+            && self
+                .cloud_application_ids
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&application_id))
+    }
+
+    pub fn get_cloud(&self) -> Option<&EdgeDataCenter> {
+        self.cloud.as_ref()
+    }
+
+    pub fn set_replica_selection(&mut self, replica_selection: ReplicaSelection) {
+        self.replica_selection = replica_selection;
+    }
+
+    pub fn set_request_timeout(&mut self, request_timeout: Option<Duration>) {
+        self.request_timeout = request_timeout;
+    }
+
+    pub fn get_topology(&self) -> &Topology {
+        &self.topology
+    }
+
+    /// Installs `rule`, replacing any rule with the same id.
+    pub fn add_traffic_influence_rule(&mut self, rule: TrafficInfluenceRule) {
+        self.traffic_influence_rules
+            .retain(|existing| existing.get_id() != rule.get_id());
+        self.traffic_influence_rules.push(rule);
+    }
+
+    pub fn remove_traffic_influence_rule(&mut self, id: u32) -> Result<(), NetworkError> {
+        match self
+            .traffic_influence_rules
+            .iter()
+            .position(|rule| rule.get_id() == id)
+        {
+            Some(index) => {
+                self.traffic_influence_rules.remove(index);
+                Ok(())
             }
             None => Err(NetworkError::new(&format!(
-                "Application with id {} does not exist",
-                application.id()
+                "Traffic influence rule with id {} does not exist",
+                id
             ))),
         }
     }
 
+    /// The edge data center that should serve `application_id` for `user` connected to the RAN `ran_id`.
+    /// The first matching traffic influence rule wins, otherwise a replica is picked by the replica selection policy,
+    /// among the running replicas if there are any.
+    /// Only edge data centers the RAN has a route to are considered.
+    fn select_edge_data_center(
+        &self,
+        user: &PDUSession,
+        application_id: u32,
+        ran_id: u32,
+        now: Instant,
+    ) -> Option<u32> {
+        let route_latency = |id: u32| {
+            self.topology
+                .get_route(ran_id, Node::EdgeDataCenter(id))
+                .map(|route| route.get_latency())
+        };
+        let readiness = |id: u32| {
+            route_latency(id)?;
+            self.get_edge_data_center(id)?
+                .get_readiness(application_id, now)
+        };
+        if let Some(id) = self
+            .traffic_influence_rules
+            .iter()
+            .filter(|rule| rule.applies_to(user, application_id))
+            .flat_map(|rule| rule.get_edge_data_center_ids())
+            .find(|id| readiness(**id).is_some())
+        {
+            return Some(*id);
+        }
+
+        let mut replicas: Vec<(&EdgeDataCenter, Duration)> = self
+            .edge_data_centers
+            .iter()
+            .filter_map(|edge_data_center| {
+                readiness(edge_data_center.get_id()).map(|wait| (edge_data_center, wait))
+            })
+            .collect();
+        if replicas.iter().any(|(_, wait)| wait.is_zero()) {
+            replicas.retain(|(_, wait)| wait.is_zero());
+        }
+        let replicas = replicas
+            .into_iter()
+            .map(|(edge_data_center, _)| edge_data_center);
+        let selected = match self.replica_selection {
+            ReplicaSelection::LowestLatency => {
+                replicas.min_by_key(|edge_data_center| route_latency(edge_data_center.get_id()))
+            }
+            ReplicaSelection::LeastLoaded => replicas.min_by_key(|edge_data_center| {
+                (
+                    edge_data_center.get_backlog(),
+                    route_latency(edge_data_center.get_id()),
+                )
+            }),
+            ReplicaSelection::Random => {
+                let replicas: Vec<&EdgeDataCenter> = replicas.collect();
+                replicas.choose(&mut rand::thread_rng()).copied()
+            }
+        };
+        selected.map(|edge_data_center| edge_data_center.get_id())
+    }
+
+    /// Advances the lifecycle of the applications in every edge data center to `now`.
+    pub fn update_applications(&mut self, now: Instant) {
+        for edge_data_center in self.edge_data_centers.iter_mut() {
+            edge_data_center.update_applications(now);
+        }
+    }
+
+    /// Starts moving `application_id` as `request` asks, its state is sent over the shortest route between the edge data centers.
+    /// The target has to have room for the application now, it is not reserved during the transfer.
+    pub fn migrate_application(
+        &mut self,
+        application_id: u32,
+        request: &MigrationRequest,
+        now: Instant,
+    ) -> Result<&Migration, NetworkError> {
+        let source_id = request.source_edge_data_center_id;
+        let target_id = request.target_edge_data_center_id;
+        if source_id == target_id {
+            return Err(NetworkError::new(
+                "An application cannot be migrated to the edge data center it runs in",
+            ));
+        }
+        let edge_data_center = |id: u32| {
+            self.get_edge_data_center(id).ok_or_else(|| {
+                NetworkError::new(&format!("Edge data center with id {} does not exist", id))
+            })
+        };
+        let requirements = *edge_data_center(source_id)?
+            .get_applications()
+            .into_iter()
+            .find(|application| {
+                application.id() == application_id && application.get_state().is_active()
+            })
+            .ok_or_else(|| {
+                NetworkError::new(&format!(
+                    "Application with id {} does not run in edge data center {}",
+                    application_id, source_id
+                ))
+            })?
+            .get_requirements();
+        if self.migrations.iter().any(|migration| {
+            !migration.is_finished()
+                && migration.get_application_id() == application_id
+                && migration.get_source_edge_data_center_id() == source_id
+        }) {
+            return Err(NetworkError::new(&format!(
+                "Application with id {} is already migrating away from edge data center {}",
+                application_id, source_id
+            )));
+        }
+        let target = edge_data_center(target_id)?;
+        if target.contains_application(&application_id) {
+            return Err(NetworkError::new(&format!(
+                "Application with id {} already runs in edge data center {}",
+                application_id, target_id
+            )));
+        }
+        if !target.fits(&requirements) {
+            return Err(NetworkError::new(&format!(
+                "Edge data center {} lacks the resources application {} requires",
+                target_id, application_id
+            )));
+        }
+        let route = self
+            .topology
+            .get_route_between_edge_data_centers(source_id, target_id)
+            .ok_or_else(|| {
+                NetworkError::new(&format!(
+                    "There is no route from edge data center {} to {}",
+                    source_id, target_id
+                ))
+            })?;
+        let state_size = request.state_size.unwrap_or_else(|| {
+            self.catalogue
+                .get(application_id)
+                .map_or(DEFAULT_STATE_SIZE_MB, |profile| profile.get_state_size())
+        });
+        let transfer_time = route.transfer_time(state_size);
+
+        let migration = Migration::new(
+            self.next_migration_id,
+            application_id,
+            request,
+            state_size,
+            transfer_time,
+            now,
+        );
+        self.next_migration_id += 1;
+        self.migrations.push(migration);
+        Ok(self.migrations.last().unwrap())
+    }
+
+    pub fn get_migrations(&self) -> Vec<&Migration> {
+        self.migrations.iter().collect()
+    }
+
+    /// Advances the migrations to `now` and returns those that changed their status.
+    /// Finished migrations move the application, with its usage history, to the target.
+    pub fn update_migrations(&mut self, now: Instant) -> Vec<Migration> {
+        let mut changed = Vec::new();
+        for migration in self
+            .migrations
+            .iter_mut()
+            .filter(|migration| !migration.is_finished())
+        {
+            if migration.get_status() == MigrationStatus::Transferring
+                && now >= migration.interrupted_at()
+            {
+                migration.set_status(MigrationStatus::Interrupted);
+                changed.push(migration.clone());
+            }
+            if migration.get_status() == MigrationStatus::Interrupted
+                && now >= migration.completes_at()
+            {
+                let status = match Self::move_application(&mut self.edge_data_centers, migration) {
+                    Ok(()) => MigrationStatus::Completed,
+                    Err(_) => MigrationStatus::Failed,
+                };
+                migration.set_status(status);
+                changed.push(migration.clone());
+            }
+        }
+        changed
+    }
+
+    /// Moves the application of `migration` to its target, it stays at the source if the target has no room for it.
+    fn move_application(
+        edge_data_centers: &mut [EdgeDataCenter],
+        migration: &Migration,
+    ) -> Result<(), NetworkError> {
+        let position = |id: u32| {
+            edge_data_centers
+                .iter()
+                .position(|edge_data_center| edge_data_center.get_id() == id)
+                .ok_or_else(|| {
+                    NetworkError::new(&format!("Edge data center with id {} does not exist", id))
+                })
+        };
+        let source = position(migration.get_source_edge_data_center_id())?;
+        let target = position(migration.get_target_edge_data_center_id())?;
+        let application = edge_data_centers[source]
+            .take_application(migration.get_application_id())
+            .map_err(|err| NetworkError::new(&err.to_string()))?;
+        if let Err(err) = edge_data_centers[target].put_application(application.clone()) {
+            //The application just left the source, so there is room for it there.
+            edge_data_centers[source]
+                .put_application(application)
+                .unwrap();
+            return Err(NetworkError::new(&err.to_string()));
+        }
+        Ok(())
+    }
+
+    /// How long a request for `application_id` arriving at `edge_data_center_id` at `now` waits for
+    /// the application to come back from the service interruption of a migration.
+    fn get_interruption(
+        &self,
+        application_id: u32,
+        edge_data_center_id: u32,
+        now: Instant,
+    ) -> Duration {
+        self.migrations
+            .iter()
+            .filter(|migration| {
+                !migration.is_finished()
+                    && migration.get_application_id() == application_id
+                    && migration.get_source_edge_data_center_id() == edge_data_center_id
+                    && now >= migration.interrupted_at()
+            })
+            .map(|migration| migration.completes_at().saturating_duration_since(now))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Starts a new tick, requests only queue at edge data centers behind requests of the same tick.
+    pub fn reset_processing_queues(&mut self) {
+        for edge_data_center in self.edge_data_centers.iter_mut().chain(self.cloud.as_mut()) {
+            edge_data_center.reset_processing_queue();
+        }
+    }
+
+    /// Serves a request of `user` connected to the RAN `ran_id`, `scheduling_delay` is the time the request queued in the RAN.
+    /// Requests for an application no reachable edge data center serves go to the cloud if it hosts the application.
+    /// The request is lost with the loss probability of the route to the data center, otherwise it arrives
+    /// after the latency of the route plus jitter and the time to transmit the request and response of the application,
+    /// waits for the application to start or come back from the service interruption of a migration,
+    /// and queues to be processed for the compute per request of the application.
+    /// Requests that are lost or take longer than the request timeout are logged with the timeout as their delay,
+    /// the time the user waits for them, or without a delay if there is no timeout.
+    pub fn use_application(
+        &mut self,
+        user: &PDUSession,
+        application: &Application,
+        ran_id: u32,
+        scheduling_delay: Duration,
+    ) -> Result<NetworkLogEntry, NetworkError> {
+        let mut rng = rand::thread_rng();
+        let now = Instant::now();
+        let data_center = match self.select_edge_data_center(user, application.id(), ran_id, now) {
+            Some(id) => Node::EdgeDataCenter(id),
+            // The cloud serves the request when no edge data center can.
+            None if self.cloud_serves(application.id())
+                && self.topology.get_route(ran_id, Node::Cloud).is_some() =>
+            {
+                Node::Cloud
+            }
+            None => {
+                return Err(NetworkError::new(&format!(
+                    "Application with id {} does not exist",
+                    application.id()
+                )))
+            }
+        };
+        let profile = self.catalogue.get(application.id());
+        let payload_size = profile.map_or(
+            DEFAULT_REQUEST_SIZE_KB + DEFAULT_RESPONSE_SIZE_KB,
+            ApplicationProfile::get_payload_size,
+        );
+        let mean_service_time = profile.map_or(
+            Duration::from_secs_f64(DEFAULT_COMPUTE_PER_REQUEST_MS / 1000.0),
+            ApplicationProfile::get_compute_per_request,
+        );
+        let latency_slo = profile.and_then(ApplicationProfile::get_latency_slo);
+        //We know that there is a route to the selected data center.
+        let route = self.topology.get_route(ran_id, data_center).unwrap();
+        let dropped = route.sample_loss(&mut rng);
+        let delay = route.sample_latency(&mut rng) + route.transmission_time(payload_size);
+        // Requests wait for an application that is still starting or interrupted by a migration.
+        let interruption = match data_center {
+            Node::EdgeDataCenter(id) => self
+                .get_edge_data_center(id)
+                .and_then(|edge_data_center| edge_data_center.get_readiness(application.id(), now))
+                .unwrap_or_default()
+                .max(self.get_interruption(application.id(), id, now)),
+            _ => Duration::ZERO,
+        };
+        let request_timeout = self.request_timeout;
+        let data_center_host = match data_center {
+            Node::EdgeDataCenter(id) => self.get_mut_edge_data_center(id),
+            _ => self.cloud.as_mut(),
+        }
+        .unwrap();
+        // A lost request costs the user the timeout, without one it is only a loss.
+        let (final_delay, outcome) = if dropped {
+            (request_timeout, RequestOutcome::Dropped)
+        } else {
+            //We know that the data center has the application.
+            let _usage = data_center_host
+                .use_application(*user.ip(), ran_id, application)
+                .unwrap();
+            let arrival = scheduling_delay + delay + interruption;
+            let processing_delay = data_center_host.process_request(arrival, mean_service_time);
+            let final_delay = arrival + processing_delay;
+            match request_timeout {
+                Some(timeout) if final_delay > timeout => (Some(timeout), RequestOutcome::Timeout),
+                _ => (Some(final_delay), RequestOutcome::Success),
+            }
+        };
+
+        Ok(NetworkLogEntry::new(
+            user.user().get_id(),
+            user.ip().to_string(),
+            final_delay,
+            outcome,
+            application.id(),
+            data_center,
+            latency_slo,
+        ))
+    }
+
     pub fn get_edge_data_centers(&self) -> Vec<&EdgeDataCenter> {
         self.edge_data_centers.iter().collect()
     }
@@ -121,10 +644,37 @@ impl Network {
         }
     }
 
+    /// Every application that is or will be serving once, however many replicas it has.
     pub fn get_applictions(&self) -> Vec<&Application> {
-        self.edge_data_centers
+        let mut applications: Vec<&Application> = self
+            .edge_data_centers
             .iter()
+            .chain(self.cloud.as_ref())
             .flat_map(|edc| edc.get_applications())
+            .filter(|application| application.get_state().is_active())
+            .collect();
+        applications.sort_by_key(|application| application.id());
+        applications.dedup_by_key(|application| application.id());
+        applications
+    }
+
+    /// The running replicas of an application with the requests each has served.
+    /// Replicas still starting, draining or terminated are left out.
+    pub fn get_replicas(&self, application_id: u32) -> Vec<Replica> {
+        self.edge_data_centers
+            .iter()
+            .filter_map(|edc| {
+                edc.get_applications()
+                    .into_iter()
+                    .find(|application| {
+                        application.id() == application_id
+                            && application.get_state() == DeploymentState::Running
+                    })
+                    .map(|application| Replica {
+                        edge_data_center_id: edc.get_id(),
+                        usage: application.get_total_usage(),
+                    })
+            })
             .collect()
     }
 
@@ -155,29 +705,68 @@ impl Network {
             })
     }
 
-    fn generate_delay(first_point: &Point, second_point: &Point) -> Duration {
-        let distance = first_point.euclidean_distance(second_point).abs();
-        Duration::new((distance * 1.5) as u64, 0)
+    /// The uses of an application in an edge data center from `since`, since the UNIX epoch, on.
+    pub fn get_application_usage(
+        &self,
+        edc_id: u32,
+        application_id: u32,
+        since: Duration,
+    ) -> Result<Usage, NetworkError> {
+        let edc = self.get_edge_data_center(edc_id).ok_or_else(|| {
+            NetworkError::new(&format!(
+                "Edge data center with id {} does not exist",
+                edc_id
+            ))
+        })?;
+        edc.get_usage_of_application(application_id, since)
+            .map_err(|_| {
+                NetworkError::new(&format!(
+                    "Application with id {} does not exist",
+                    application_id
+                ))
+            })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{ran::Ran, user::User};
+    use crate::{
+        application::DEFAULT_REQUIREMENTS, ran::Ran, resources::Resources, topology::Link,
+        user::User,
+    };
 
     use super::*;
-    use std::{iter::repeat, net::Ipv4Addr};
+    use std::{iter::repeat_n, net::Ipv4Addr};
 
     use geo::Point;
 
+    const DEFAULT_CAPACITY: Resources = Resources::new(8.0, 8192);
+
+    /// A network with RAN 0 at (1, 1) linked to every edge data center and to the cloud.
+    fn create_network(edge_data_centers: Vec<EdgeDataCenter>) -> Network {
+        let positions: Vec<(u32, Point)> = edge_data_centers
+            .iter()
+            .map(|edge_data_center| (edge_data_center.get_id(), *edge_data_center.get_position()))
+            .collect();
+        let topology = Topology::full_mesh(
+            &[(0, Point::new(1.0, 1.0))],
+            &positions,
+            Duration::from_millis(1),
+            Duration::from_millis(100),
+        );
+        Network::new(edge_data_centers, topology)
+    }
+
     #[test]
     fn create() {
-        let edge_data_centers = repeat((0, "Fredrik's edge data center", Point::new(0.0, 0.0)))
-            .take(32)
-            .map(|(id, name, position)| (EdgeDataCenter::new(id, name, position)))
-            .collect();
+        let edge_data_centers =
+            repeat_n((0, "Fredrik's edge data center", Point::new(0.0, 0.0)), 32)
+                .map(|(id, name, position)| {
+                    EdgeDataCenter::new(id, name, position, DEFAULT_CAPACITY)
+                })
+                .collect();
 
-        let network = Network::new(edge_data_centers);
+        let network = create_network(edge_data_centers);
 
         assert_eq!(network.edge_data_centers.len(), 32);
     }
@@ -185,11 +774,12 @@ mod tests {
     #[tokio::test]
     async fn use_application() {
         let mut edge_data_centers: Vec<EdgeDataCenter> =
-            repeat((0, "Fredrik's edge data center", Point::new(0.0, 0.0)))
-                .take(2)
-                .map(|(id, name, position)| (EdgeDataCenter::new(id, name, position)))
+            repeat_n((0, "Fredrik's edge data center", Point::new(0.0, 0.0)), 2)
+                .map(|(id, name, position)| {
+                    EdgeDataCenter::new(id, name, position, DEFAULT_CAPACITY)
+                })
                 .collect();
-        let application = Application::new(0);
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
         let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
         let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
         let pdu_session = PDUSession::new(
@@ -197,10 +787,12 @@ mod tests {
             std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
             &ran,
         );
-        edge_data_centers[0].add_application(0).unwrap();
-        let mut network = Network::new(edge_data_centers);
+        edge_data_centers[0]
+            .add_application(0, DEFAULT_REQUIREMENTS)
+            .unwrap();
+        let mut network = create_network(edge_data_centers);
 
-        let result = network.use_application(&pdu_session, &application, &Point::new(1.0, 1.0));
+        let result = network.use_application(&pdu_session, &application, 0, Duration::ZERO);
 
         assert!(result.is_ok());
     }
@@ -208,13 +800,87 @@ mod tests {
     #[tokio::test]
     async fn use_application_not_present_should_fail() {
         let edge_data_centers: Vec<EdgeDataCenter> =
-            repeat((0, "Fredrik's edge data center", Point::new(0.0, 0.0)))
-                .take(1)
-                .map(|(id, name, position)| (EdgeDataCenter::new(id, name, position)))
+            repeat_n((0, "Fredrik's edge data center", Point::new(0.0, 0.0)), 1)
+                .map(|(id, name, position)| {
+                    EdgeDataCenter::new(id, name, position, DEFAULT_CAPACITY)
+                })
                 .collect();
-        let application = Application::new(0);
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+
+        let mut network = create_network(edge_data_centers);
+        let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
+        let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
+        let pdu_session = PDUSession::new(
+            user,
+            std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            &ran,
+        );
+
+        let result = network.use_application(&pdu_session, &application, 0, Duration::ZERO);
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn use_application_falls_back_to_cloud() {
+        let edge_data_centers = vec![EdgeDataCenter::new(
+            0,
+            "Fredrik's edge data center",
+            Point::new(0.0, 0.0),
+            DEFAULT_CAPACITY,
+        )];
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+
+        let mut cloud =
+            EdgeDataCenter::new(u32::MAX, "cloud", Point::new(0.0, 0.0), DEFAULT_CAPACITY);
+        cloud
+            .put_application(Application::new(0, DEFAULT_REQUIREMENTS))
+            .unwrap();
+
+        let mut network = create_network(edge_data_centers);
+        network.set_cloud(cloud, None);
+        let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
+        let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
+        let pdu_session = PDUSession::new(
+            user,
+            std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            &ran,
+        );
+
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+
+        assert_eq!(result.get_data_center(), Node::Cloud);
+        assert_eq!(result.get_edge_data_center_id(), None);
+        assert!(result.get_delay().unwrap() >= Duration::from_millis(100));
+        assert!(network
+            .get_cloud()
+            .unwrap()
+            .contains_application(&application.id()));
+        assert_eq!(network.get_applictions().len(), 1);
+
+        network
+            .get_mut_edge_data_center(0)
+            .unwrap()
+            .add_application(0, DEFAULT_REQUIREMENTS)
+            .unwrap();
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+
+        assert_eq!(result.get_data_center(), Node::EdgeDataCenter(0));
+    }
+
+    #[tokio::test]
+    async fn use_application_not_served_by_cloud_should_fail() {
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
 
-        let mut network = Network::new(edge_data_centers);
+        let mut network = create_network(Vec::new());
+        network.set_cloud(
+            EdgeDataCenter::new(u32::MAX, "cloud", Point::new(0.0, 0.0), DEFAULT_CAPACITY),
+            Some(vec![1]),
+        );
         let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
         let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
         let pdu_session = PDUSession::new(
@@ -223,8 +889,422 @@ mod tests {
             &ran,
         );
 
-        let result = network.use_application(&pdu_session, &application, &Point::new(1.0, 1.0));
+        let result = network.use_application(&pdu_session, &application, 0, Duration::ZERO);
+
+        assert!(result.is_err());
+
+        // Nor is one the cloud does not host, it is not deployed on demand.
+        network.set_cloud(
+            EdgeDataCenter::new(u32::MAX, "cloud", Point::new(0.0, 0.0), DEFAULT_CAPACITY),
+            None,
+        );
+        let result = network.use_application(&pdu_session, &application, 0, Duration::ZERO);
 
         assert!(result.is_err());
+        assert!(!network
+            .get_cloud()
+            .unwrap()
+            .contains_application(&application.id()));
+    }
+
+    #[tokio::test]
+    async fn use_application_follows_traffic_influence_rule() {
+        let mut edge_data_centers: Vec<EdgeDataCenter> = (0..3)
+            .map(|id| {
+                EdgeDataCenter::new(
+                    id,
+                    "Fredrik's edge data center",
+                    Point::new(0.0, 0.0),
+                    DEFAULT_CAPACITY,
+                )
+            })
+            .collect();
+        for edge_data_center in edge_data_centers.iter_mut() {
+            edge_data_center
+                .add_application(0, DEFAULT_REQUIREMENTS)
+                .unwrap();
+        }
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+        let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
+        let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
+        let pdu_session = PDUSession::new(
+            user,
+            std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            &ran,
+        );
+        let mut network = create_network(edge_data_centers);
+        let subscription: crate::traffic_influence::TrafficInfluenceSubscription =
+            serde_json::from_value(serde_json::json!({
+                "afAppId": "0",
+                "anyUeInd": true,
+                "trafficRoutes": [{"dnai": "edc-7"}, {"dnai": "edc-2"}]
+            }))
+            .unwrap();
+        network.add_traffic_influence_rule(subscription.rule(0).unwrap());
+
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+        assert_eq!(result.get_edge_data_center_id(), Some(2));
+
+        network.remove_traffic_influence_rule(0).unwrap();
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+        assert_eq!(result.get_edge_data_center_id(), Some(0));
+        assert!(network.remove_traffic_influence_rule(0).is_err());
+    }
+
+    #[tokio::test]
+    async fn use_application_over_the_topology() {
+        let mut edge_data_centers: Vec<EdgeDataCenter> = (0..2)
+            .map(|id| {
+                EdgeDataCenter::new(
+                    id,
+                    "Fredrik's edge data center",
+                    Point::new(0.0, 0.0),
+                    DEFAULT_CAPACITY,
+                )
+            })
+            .collect();
+        for edge_data_center in edge_data_centers.iter_mut() {
+            edge_data_center
+                .add_application(0, DEFAULT_REQUIREMENTS)
+                .unwrap();
+        }
+        let topology = Topology::new(vec![
+            Link::new(
+                Node::Ran(0),
+                Node::Router(0),
+                Duration::from_millis(2),
+                1000.0,
+                0.0,
+            ),
+            Link::new(
+                Node::Router(0),
+                Node::EdgeDataCenter(1),
+                Duration::from_millis(5),
+                1000.0,
+                0.0,
+            ),
+        ])
+        .unwrap();
+        let mut network = Network::new(edge_data_centers, topology);
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+        let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
+        let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
+        let pdu_session = PDUSession::new(
+            user,
+            std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            &ran,
+        );
+
+        // Edge data center 0 hosts the application too but cannot be reached.
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+        assert_eq!(result.get_edge_data_center_id(), Some(1));
+        assert!(result.get_delay().unwrap() > Duration::from_millis(7));
+        assert!(network
+            .use_application(&pdu_session, &application, 1, Duration::ZERO)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn use_application_drops_and_times_out_requests() {
+        let mut edge_data_center = EdgeDataCenter::new(
+            0,
+            "Fredrik's edge data center",
+            Point::new(0.0, 0.0),
+            DEFAULT_CAPACITY,
+        );
+        edge_data_center
+            .add_application(0, DEFAULT_REQUIREMENTS)
+            .unwrap();
+        let topology = Topology::new(vec![
+            Link::new(
+                Node::Ran(0),
+                Node::EdgeDataCenter(0),
+                Duration::from_micros(2500),
+                1000.0,
+                0.0,
+            ),
+            Link::new(
+                Node::Ran(1),
+                Node::EdgeDataCenter(0),
+                Duration::from_millis(2),
+                1000.0,
+                1.0,
+            ),
+        ])
+        .unwrap();
+        let mut network = Network::new(vec![edge_data_center], topology);
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+        let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
+        let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
+        let pdu_session = PDUSession::new(
+            user,
+            std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            &ran,
+        );
+
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+        assert_eq!(result.get_outcome(), RequestOutcome::Success);
+        assert!(result.get_delay().unwrap() > Duration::from_micros(2500));
+
+        let result = network
+            .use_application(&pdu_session, &application, 1, Duration::ZERO)
+            .unwrap();
+        assert_eq!(result.get_outcome(), RequestOutcome::Dropped);
+        assert_eq!(result.get_delay(), None);
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["outcome"], serde_json::json!("dropped"));
+        assert!(json["time_used_us"].is_null());
+
+        network.set_request_timeout(Some(Duration::from_millis(1)));
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+        assert_eq!(result.get_outcome(), RequestOutcome::Timeout);
+        assert_eq!(result.get_delay(), Some(Duration::from_millis(1)));
+    }
+
+    #[tokio::test]
+    async fn use_application_selects_a_replica() {
+        let mut edge_data_centers: Vec<EdgeDataCenter> = (0..3)
+            .map(|id| {
+                EdgeDataCenter::new(
+                    id,
+                    "Fredrik's edge data center",
+                    Point::new(id as f64 * 10.0, 0.0),
+                    DEFAULT_CAPACITY,
+                )
+            })
+            .collect();
+        for edge_data_center in edge_data_centers.iter_mut().skip(1) {
+            edge_data_center
+                .put_application(Application::new(0, DEFAULT_REQUIREMENTS))
+                .unwrap();
+        }
+        let mut network = create_network(edge_data_centers);
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+        let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
+        let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
+        let pdu_session = PDUSession::new(
+            user,
+            std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            &ran,
+        );
+        assert_eq!(network.get_applictions().len(), 1);
+
+        for _ in 0..3 {
+            let result = network
+                .use_application(&pdu_session, &application, 0, Duration::ZERO)
+                .unwrap();
+            assert_eq!(result.get_edge_data_center_id(), Some(1));
+        }
+
+        network.set_replica_selection(ReplicaSelection::LeastLoaded);
+        // Edge data center 1 now has to work through the requests above first.
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+        assert_eq!(result.get_edge_data_center_id(), Some(2));
+
+        let usages: Vec<(u32, u32)> = network
+            .get_replicas(0)
+            .iter()
+            .map(|replica| (replica.edge_data_center_id, replica.usage))
+            .collect();
+        assert_eq!(usages, vec![(1, 3), (2, 1)]);
+    }
+
+    #[tokio::test]
+    async fn migrate_application() {
+        let mut edge_data_centers: Vec<EdgeDataCenter> = (0..2)
+            .map(|id| {
+                EdgeDataCenter::new(
+                    id,
+                    "Fredrik's edge data center",
+                    Point::new(0.0, 0.0),
+                    DEFAULT_CAPACITY,
+                )
+            })
+            .collect();
+        edge_data_centers[0]
+            .add_application(0, DEFAULT_REQUIREMENTS)
+            .unwrap();
+        let topology = Topology::new(vec![
+            Link::new(
+                Node::Ran(0),
+                Node::EdgeDataCenter(0),
+                Duration::from_millis(1),
+                1000.0,
+                0.0,
+            ),
+            Link::new(
+                Node::Ran(0),
+                Node::EdgeDataCenter(1),
+                Duration::from_millis(1),
+                1000.0,
+                0.0,
+            ),
+        ])
+        .unwrap();
+        let mut network = Network::new(edge_data_centers, topology);
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+        let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
+        let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
+        let pdu_session = PDUSession::new(
+            user,
+            std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            &ran,
+        );
+        network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+
+        let request: MigrationRequest = serde_json::from_value(serde_json::json!({
+            "source_edge_data_center_id": 0,
+            "target_edge_data_center_id": 1,
+            "state_size": 125,
+            "downtime": 300
+        }))
+        .unwrap();
+        let start = Instant::now();
+        let migration = network.migrate_application(0, &request, start).unwrap();
+        // 125 MB over the 1000 Mbit/s of the two links take 1 s, plus 2 ms latency.
+        assert_eq!(
+            migration.interrupted_at(),
+            start + Duration::from_millis(1002)
+        );
+        assert_eq!(
+            migration.completes_at(),
+            start + Duration::from_millis(1302)
+        );
+        assert!(network.migrate_application(0, &request, start).is_err());
+
+        assert!(network.update_migrations(start).is_empty());
+        let changed = network.update_migrations(start + Duration::from_millis(1100));
+        assert_eq!(changed[0].get_status(), MigrationStatus::Interrupted);
+        assert_eq!(
+            network.get_interruption(0, 0, start + Duration::from_millis(1100)),
+            Duration::from_millis(202)
+        );
+
+        let changed = network.update_migrations(start + Duration::from_millis(1302));
+        assert_eq!(changed[0].get_status(), MigrationStatus::Completed);
+        assert!(!network
+            .get_edge_data_center(0)
+            .unwrap()
+            .contains_application(&0));
+        assert_eq!(network.get_total_application_usage(1, 0).unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn use_application_follows_application_profile() {
+        let mut edge_data_centers = vec![EdgeDataCenter::new(
+            0,
+            "Fredrik's edge data center",
+            Point::new(0.0, 0.0),
+            DEFAULT_CAPACITY,
+        )];
+        edge_data_centers[0]
+            .put_application(Application::new(0, DEFAULT_REQUIREMENTS))
+            .unwrap();
+        let mut network = create_network(edge_data_centers);
+        let profile: ApplicationProfile = serde_json::from_value(serde_json::json!({
+            "name": "video-analytics",
+            "image": "registry.local/video-analytics:1.2",
+            "request_size": 0,
+            "response_size": 125000,
+            "compute_per_request": 0.0,
+            "latency_slo": 50
+        }))
+        .unwrap();
+        assert!(network.put_application_profile(0, profile));
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+        let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
+        let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
+        let pdu_session = PDUSession::new(
+            user,
+            std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            &ran,
+        );
+
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+
+        // 125 MB over the 10 Gbit/s link take 100 ms, plus the latency of the link.
+        assert!(result.get_delay().unwrap() > Duration::from_millis(100));
+        assert!(result.get_delay().unwrap() < Duration::from_millis(102));
+        assert_eq!(result.get_within_slo(), Some(false));
+        assert!(network.remove_application_profile(0).is_err());
+        network
+            .get_mut_edge_data_center(0)
+            .unwrap()
+            .take_application(0)
+            .unwrap();
+        assert!(network.remove_application_profile(0).is_ok());
+    }
+
+    #[tokio::test]
+    async fn use_application_waits_for_starting_replicas() {
+        let mut edge_data_centers: Vec<EdgeDataCenter> = (0..2)
+            .map(|id| {
+                EdgeDataCenter::new(
+                    id,
+                    "Fredrik's edge data center",
+                    Point::new(id as f64 * 10.0, 0.0),
+                    DEFAULT_CAPACITY,
+                )
+            })
+            .collect();
+        for edge_data_center in edge_data_centers.iter_mut() {
+            edge_data_center.set_cold_start(Duration::from_secs(5));
+        }
+        edge_data_centers[1]
+            .put_application(Application::new(0, DEFAULT_REQUIREMENTS))
+            .unwrap();
+        edge_data_centers[0]
+            .add_application(0, DEFAULT_REQUIREMENTS)
+            .unwrap();
+        let mut network = create_network(edge_data_centers);
+        network.update_applications(Instant::now());
+        let application = Application::new(0, DEFAULT_REQUIREMENTS);
+        let ran = Ran::new(0, Point::new(1.0, 1.0), 50.0);
+        let user = User::new(0, Point::new(1.0, 1.0), 1.0, &(-1.0..1.0));
+        let pdu_session = PDUSession::new(
+            user,
+            std::net::IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            &ran,
+        );
+
+        // Edge data center 0 is closer, but still starting.
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+        assert_eq!(result.get_edge_data_center_id(), Some(1));
+        let replicas: Vec<u32> = network
+            .get_replicas(0)
+            .iter()
+            .map(|replica| replica.edge_data_center_id)
+            .collect();
+        assert_eq!(replicas, vec![1]);
+
+        network
+            .get_mut_edge_data_center(1)
+            .unwrap()
+            .remove_application(0)
+            .unwrap();
+        assert!(network.get_replicas(0).is_empty());
+        let result = network
+            .use_application(&pdu_session, &application, 0, Duration::ZERO)
+            .unwrap();
+        assert_eq!(result.get_edge_data_center_id(), Some(0));
+        assert!(result.get_delay().unwrap() > Duration::from_secs(4));
     }
 }
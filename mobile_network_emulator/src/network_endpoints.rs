@@ -1,12 +1,20 @@
-use actix_web::error::ErrorInternalServerError;
-use actix_web::web::{Data, Json, Path};
-use actix_web::{delete, get, post, Responder};
+use std::time::{Duration, Instant};
+
+use actix_web::error::{ErrorBadRequest, ErrorConflict, ErrorNotFound};
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{delete, get, post, put, HttpResponse, Responder};
+use mongodb::Database;
+use serde::Deserialize;
 use tokio::sync::RwLock;
 
 use crate::application::Application;
+use crate::application_catalogue::ApplicationProfile;
 
 use crate::edge_data_center::EdgeDataCenter;
+use crate::migration::{Migration, MigrationRequest};
+use crate::mobile_network_core_endpoints::MobileNetworkCoreWrapper;
 use crate::network::Network;
+use crate::resources::Resources;
 
 pub struct NetworkWrapper {
     pub network: RwLock<Network>,
@@ -33,6 +41,18 @@ pub async fn get_edge_data_centers(network_wrapper: Data<NetworkWrapper>) -> imp
     Json(edge_data_centers)
 }
 
+/// The transport network with the shortest route from every RAN to every data center.
+#[get("/topology")]
+pub async fn get_topology(network_wrapper: Data<NetworkWrapper>) -> impl Responder {
+    Json(network_wrapper.network.read().await.get_topology().clone())
+}
+
+/// The central cloud with the applications it serves, `null` if there is none.
+#[get("/cloud")]
+pub async fn get_cloud(network_wrapper: Data<NetworkWrapper>) -> impl Responder {
+    Json(network_wrapper.network.read().await.get_cloud().cloned())
+}
+
 #[get("/edge_data_centers/{id}/applications")]
 pub async fn get_applications(
     id: Path<u32>,
@@ -51,23 +71,32 @@ pub async fn get_applications(
     Json(applications)
 }
 
+/// Deploys an application of the catalogue, the body may give other resources than its profile requires.
+/// Deployments the edge data center has no capacity left for are rejected.
 #[post("/edge_data_centers/{edc_id}/applications/{application_id}")]
 pub async fn add_application(
     path: Path<(u32, u32)>,
     network_wrapper: Data<NetworkWrapper>,
+    requirements: Option<Json<Resources>>,
 ) -> Result<impl Responder, actix_web::Error> {
     let (edc_id, application_id) = path.into_inner();
-    match network_wrapper
-        .network
-        .write()
-        .await
+    let mut network = network_wrapper.network.write().await;
+    let profile = network.get_catalogue().get(application_id).ok_or_else(|| {
+        ErrorNotFound(format!(
+            "Application with id {} is not in the catalogue",
+            application_id
+        ))
+    })?;
+    let requirements =
+        requirements.map_or(*profile.get_requirements(), |requirements| *requirements);
+    requirements.validate().map_err(ErrorBadRequest)?;
+    // The application is already deployed or does not fit next to the others.
+    network
         .get_mut_edge_data_center(edc_id)
-        .unwrap()
-        .add_application(application_id)
-    {
-        Ok(url) => Ok(url.to_string()),
-        Err(err) => Err(ErrorInternalServerError(err)),
-    }
+        .ok_or_else(|| edge_data_center_not_found(edc_id))?
+        .add_application(application_id, requirements)
+        .map(|id| id.to_string())
+        .map_err(ErrorConflict)
 }
 
 #[delete("/edge_data_centers/{edc_id}/applications/{application_id}")]
@@ -76,17 +105,22 @@ pub async fn delete_application(
     network_wrapper: Data<NetworkWrapper>,
 ) -> Result<impl Responder, actix_web::Error> {
     let (edc_id, application_id) = path.into_inner();
-    match network_wrapper
+    network_wrapper
         .network
         .write()
         .await
         .get_mut_edge_data_center(edc_id)
-        .unwrap()
+        .ok_or_else(|| edge_data_center_not_found(edc_id))?
         .remove_application(application_id)
-    {
-        Ok(_) => Ok("OK"),
-        Err(err) => Err(ErrorInternalServerError(err)),
-    }
+        .map(|_| "OK")
+        .map_err(ErrorNotFound)
+}
+
+fn edge_data_center_not_found(edc_id: u32) -> actix_web::Error {
+    ErrorNotFound(format!(
+        "Edge data center with id {} does not exist",
+        edc_id
+    ))
 }
 
 #[get("/edge_data_centers/{edc_id}/applications/{application_id}/total_usages")]
@@ -104,3 +138,155 @@ pub async fn get_total_application_usage(
             .unwrap(),
     )
 }
+
+/// The application catalogue.
+#[get("/applications")]
+pub async fn get_application_profiles(network_wrapper: Data<NetworkWrapper>) -> impl Responder {
+    Json(
+        network_wrapper
+            .network
+            .read()
+            .await
+            .get_catalogue()
+            .get_profiles()
+            .to_vec(),
+    )
+}
+
+#[get("/applications/{application_id}")]
+pub async fn get_application_profile(
+    application_id: Path<u32>,
+    network_wrapper: Data<NetworkWrapper>,
+) -> Result<impl Responder, actix_web::Error> {
+    match network_wrapper
+        .network
+        .read()
+        .await
+        .get_catalogue()
+        .get(*application_id)
+    {
+        Some(profile) => Ok(Json(profile.clone())),
+        None => Err(ErrorNotFound(format!(
+            "Application with id {} is not in the catalogue",
+            application_id
+        ))),
+    }
+}
+
+/// Adds an application to the catalogue or replaces its profile,
+/// deployments made before keep the resources they were given.
+#[put("/applications/{application_id}")]
+pub async fn put_application_profile(
+    application_id: Path<u32>,
+    profile: Json<ApplicationProfile>,
+    network_wrapper: Data<NetworkWrapper>,
+) -> Result<impl Responder, actix_web::Error> {
+    profile.validate().map_err(ErrorBadRequest)?;
+    let mut network = network_wrapper.network.write().await;
+    let created = network.put_application_profile(*application_id, profile.into_inner());
+    let profile = network
+        .get_catalogue()
+        .get(*application_id)
+        .unwrap()
+        .clone();
+    if created {
+        Ok(HttpResponse::Created().json(profile))
+    } else {
+        Ok(HttpResponse::Ok().json(profile))
+    }
+}
+
+/// Removes an application from the catalogue, it has to be deleted from the edge data centers first.
+#[delete("/applications/{application_id}")]
+pub async fn delete_application_profile(
+    application_id: Path<u32>,
+    network_wrapper: Data<NetworkWrapper>,
+) -> Result<impl Responder, actix_web::Error> {
+    let mut network = network_wrapper.network.write().await;
+    if network.get_catalogue().get(*application_id).is_none() {
+        return Err(ErrorNotFound(format!(
+            "Application with id {} is not in the catalogue",
+            application_id
+        )));
+    }
+    network
+        .remove_application_profile(*application_id)
+        .map_err(ErrorBadRequest)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    /// In milliseconds since the UNIX epoch.
+    since: u64,
+}
+
+/// The uses of an application still in its usage history from `since` on, with counts per IP address and RAN.
+#[get("/edge_data_centers/{edc_id}/applications/{application_id}/usage")]
+pub async fn get_application_usage(
+    path: Path<(u32, u32)>,
+    query: Query<UsageQuery>,
+    network_wrapper: Data<NetworkWrapper>,
+) -> Result<impl Responder, actix_web::Error> {
+    let (edc_id, application_id) = path.into_inner();
+    network_wrapper
+        .network
+        .read()
+        .await
+        .get_application_usage(edc_id, application_id, Duration::from_millis(query.since))
+        .map(Json)
+        .map_err(ErrorNotFound)
+}
+
+/// The edge data centers hosting a replica of the application with the requests each has served.
+#[get("/applications/{application_id}/replicas")]
+pub async fn get_application_replicas(
+    application_id: Path<u32>,
+    network_wrapper: Data<NetworkWrapper>,
+) -> impl Responder {
+    Json(
+        network_wrapper
+            .network
+            .read()
+            .await
+            .get_replicas(*application_id),
+    )
+}
+
+/// Starts migrating an application between edge data centers, the migration finishes over the following ticks.
+#[post("/applications/{application_id}/migrate")]
+pub async fn migrate_application(
+    application_id: Path<u32>,
+    request: Json<MigrationRequest>,
+    network_wrapper: Data<NetworkWrapper>,
+    mobile_network_core_wrapper: Data<MobileNetworkCoreWrapper>,
+    database: Data<Database>,
+) -> Result<impl Responder, actix_web::Error> {
+    let migration = network_wrapper
+        .network
+        .write()
+        .await
+        .migrate_application(*application_id, &request, Instant::now())
+        .map_err(ErrorBadRequest)?
+        .clone();
+    mobile_network_core_wrapper
+        .mobile_network_core
+        .write()
+        .await
+        .report_migrations(&database, std::slice::from_ref(&migration))
+        .await;
+    Ok(HttpResponse::Created().json(migration))
+}
+
+#[get("/migrations")]
+pub async fn get_migrations(network_wrapper: Data<NetworkWrapper>) -> impl Responder {
+    let migrations: Vec<Migration> = network_wrapper
+        .network
+        .read()
+        .await
+        .get_migrations()
+        .into_iter()
+        .cloned()
+        .collect();
+    Json(migrations)
+}
@@ -0,0 +1,99 @@
+use actix_web::{http::StatusCode, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+/// Error body used by all 3GPP northbound APIs (TS 29.122 ProblemDetails).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProblemDetails {
+    title: String,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode, detail: &str) -> Self {
+        Self {
+            title: status.canonical_reason().unwrap_or("Unknown").to_string(),
+            status: status.as_u16(),
+            detail: Some(detail.to_string()),
+        }
+    }
+
+    pub fn into_response(self) -> HttpResponse {
+        HttpResponse::build(StatusCode::from_u16(self.status).unwrap())
+            .content_type("application/problem+json")
+            .json(self)
+    }
+
+    pub fn bad_request(detail: &str) -> HttpResponse {
+        Self::new(StatusCode::BAD_REQUEST, detail).into_response()
+    }
+
+    pub fn not_found(detail: &str) -> HttpResponse {
+        Self::new(StatusCode::NOT_FOUND, detail).into_response()
+    }
+}
+
+/// Absolute URL of the resource `id` below the collection the request was sent to.
+pub fn resource_url(request: &HttpRequest, id: u32) -> String {
+    let connection_info = request.connection_info();
+    format!(
+        "{}://{}{}/{}",
+        connection_info.scheme(),
+        connection_info.host(),
+        request.path().trim_end_matches('/'),
+        id
+    )
+}
+
+/// Absolute URL of the resource the request was sent to.
+pub fn request_url(request: &HttpRequest) -> String {
+    let connection_info = request.connection_info();
+    format!(
+        "{}://{}{}",
+        connection_info.scheme(),
+        connection_info.host(),
+        request.path()
+    )
+}
+
+/// Resolves a 3GPP UE identifier to an emulator user id.
+///
+/// The emulator uses the user id as both MSISDN (`"42"`) and the local part of the
+/// external identifier (`"42@domain.com"`). GPSIs (`"msisdn-42"`, `"extid-42@domain.com"`)
+/// are accepted as well.
+pub fn resolve_user_id(identifier: &str) -> Option<u32> {
+    let identifier = identifier
+        .strip_prefix("msisdn-")
+        .or_else(|| identifier.strip_prefix("extid-"))
+        .unwrap_or(identifier);
+    let local_part = identifier.split('@').next()?;
+    local_part.parse().ok()
+}
+
+/// The MSISDN the emulator uses for `user_id`.
+pub fn msisdn(user_id: u32) -> String {
+    user_id.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_user_id_formats() {
+        assert_eq!(resolve_user_id("42"), Some(42));
+        assert_eq!(resolve_user_id("42@domain.com"), Some(42));
+        assert_eq!(resolve_user_id("msisdn-42"), Some(42));
+        assert_eq!(resolve_user_id("extid-42@domain.com"), Some(42));
+        assert_eq!(resolve_user_id("fredrik@domain.com"), None);
+    }
+
+    #[test]
+    fn problem_details_title() {
+        let problem = ProblemDetails::new(StatusCode::NOT_FOUND, "missing");
+        assert_eq!(problem.title, "Not Found");
+        assert_eq!(problem.status, 404);
+    }
+}
@@ -1,14 +1,18 @@
 use geo::Point;
 use serde::{ser::SerializeStruct, Serialize};
-use std::{net::IpAddr, sync::Arc};
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
 
-use crate::{ran::Ran, user::User};
+use crate::{qos::QosFlow, ran::Ran, user::User};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct PDUSession {
     user: User,
     ip_address: IpAddr,
     ran: Arc<Ran>,
+    dnn: String,
+    user_plane_paths: HashMap<u32, u32>,
+    default_qos_flow: QosFlow,
+    qos_flows: Vec<QosFlow>,
 }
 
 impl PDUSession {
@@ -17,6 +21,10 @@ impl PDUSession {
             user,
             ip_address,
             ran: Arc::new(ran.clone()),
+            dnn: "Default".to_string(),
+            user_plane_paths: HashMap::new(),
+            default_qos_flow: QosFlow::default_flow(),
+            qos_flows: Vec::new(),
         }
     }
 
@@ -43,6 +51,54 @@ impl PDUSession {
     pub fn get_ran(&self) -> Arc<Ran> {
         self.ran.clone()
     }
+
+    pub fn handover(&mut self, ran: &Ran) {
+        self.ran = Arc::new(ran.clone());
+    }
+
+    pub fn dnn(&self) -> &str {
+        &self.dnn
+    }
+
+    /// Records that traffic for `application_id` is served by `edge_data_center_id`
+    /// and returns the edge data center that served it before, if it was another one.
+    pub fn route_application(
+        &mut self,
+        application_id: u32,
+        edge_data_center_id: u32,
+    ) -> Option<u32> {
+        self.user_plane_paths
+            .insert(application_id, edge_data_center_id)
+            .filter(|previous| *previous != edge_data_center_id)
+    }
+
+    /// Adds `qos_flow` to the session, replacing any flow with the same id.
+    pub fn add_qos_flow(&mut self, qos_flow: QosFlow) {
+        self.remove_qos_flow(qos_flow.get_id());
+        self.qos_flows.push(qos_flow);
+    }
+
+    pub fn remove_qos_flow(&mut self, id: u32) -> Option<QosFlow> {
+        let index = self.qos_flows.iter().position(|flow| flow.get_id() == id)?;
+        Some(self.qos_flows.remove(index))
+    }
+
+    /// The highest priority flow carrying `application_id`, the default flow if no other does.
+    pub fn qos_flow(&self, application_id: u32) -> &QosFlow {
+        self.qos_flows
+            .iter()
+            .filter(|flow| flow.carries(application_id))
+            .min_by_key(|flow| flow.scheduling_priority())
+            .unwrap_or(&self.default_qos_flow)
+    }
+
+    pub fn qos_flow_mut(&mut self, application_id: u32) -> &mut QosFlow {
+        self.qos_flows
+            .iter_mut()
+            .filter(|flow| flow.carries(application_id))
+            .min_by_key(|flow| flow.scheduling_priority())
+            .unwrap_or(&mut self.default_qos_flow)
+    }
 }
 
 impl Serialize for PDUSession {
@@ -50,10 +106,12 @@ impl Serialize for PDUSession {
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("PDUSession", 3)?;
+        let mut state = serializer.serialize_struct("PDUSession", 5)?;
         state.serialize_field("user", &self.user())?;
         state.serialize_field("ip", &self.ip())?;
         state.serialize_field("ran", &self.ran.get_id())?;
+        state.serialize_field("dnn", &self.dnn)?;
+        state.serialize_field("qos_flows", &self.qos_flows)?;
         state.end()
     }
 }
@@ -72,10 +130,40 @@ mod tests {
 
         let pbu_session = PDUSession::new(user.clone(), std::net::IpAddr::V4(ip_address), &ran);
         let (user_1, ip_address_1) = pbu_session.release();
-        assert_eq!(
-            user,
-            user_1
-        );
+        assert_eq!(user, user_1);
         assert_eq!(Ipv4Addr::LOCALHOST, ip_address_1);
     }
+
+    #[test]
+    fn handover_and_route_application() {
+        let user = User::new(1, Point::new(50.0, 50.0), 1.5, &(-50.0..50.0));
+        let ran = Ran::new(0, Point::new(0.0, 0.0), 100.0);
+        let mut pdu_session =
+            PDUSession::new(user, std::net::IpAddr::V4(Ipv4Addr::LOCALHOST), &ran);
+
+        pdu_session.handover(&Ran::new(1, Point::new(50.0, 50.0), 100.0));
+        assert_eq!(pdu_session.get_ran().get_id(), 1);
+
+        assert_eq!(pdu_session.route_application(0, 2), None);
+        assert_eq!(pdu_session.route_application(0, 2), None);
+        assert_eq!(pdu_session.route_application(0, 3), Some(2));
+    }
+
+    #[test]
+    fn qos_flows() {
+        let user = User::new(1, Point::new(50.0, 50.0), 1.5, &(-50.0..50.0));
+        let ran = Ran::new(0, Point::new(0.0, 0.0), 100.0);
+        let mut pdu_session =
+            PDUSession::new(user, std::net::IpAddr::V4(Ipv4Addr::LOCALHOST), &ran);
+        assert_eq!(pdu_session.qos_flow(1).get_qos_reference(), "5qi-9");
+
+        pdu_session.add_qos_flow(QosFlow::new(0, "5qi-7", vec![], None, None).unwrap());
+        pdu_session.add_qos_flow(QosFlow::new(1, "5qi-82", vec![1], None, None).unwrap());
+        assert_eq!(pdu_session.qos_flow(1).get_qos_reference(), "5qi-82");
+        assert_eq!(pdu_session.qos_flow(2).get_qos_reference(), "5qi-7");
+
+        assert!(pdu_session.remove_qos_flow(1).is_some());
+        assert!(pdu_session.remove_qos_flow(1).is_none());
+        assert_eq!(pdu_session.qos_flow(1).get_qos_reference(), "5qi-7");
+    }
 }
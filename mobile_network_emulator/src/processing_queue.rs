@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+/// Requests waiting for and being processed by the servers of an edge data center, first come first served.
+/// Times are offsets from the start of the current tick, work left at the end of a tick is dropped.
+#[derive(Debug, Clone)]
+pub struct ProcessingQueue {
+    /// When each server is done with the requests it has been given.
+    free_at: Vec<Duration>,
+}
+
+impl ProcessingQueue {
+    pub fn new(servers: usize) -> Self {
+        ProcessingQueue {
+            free_at: vec![Duration::ZERO; servers.max(1)],
+        }
+    }
+
+    pub fn get_servers(&self) -> usize {
+        self.free_at.len()
+    }
+
+    /// How long the servers are busy with the requests of this tick on average.
+    pub fn get_backlog(&self) -> Duration {
+        self.free_at.iter().sum::<Duration>() / self.free_at.len() as u32
+    }
+
+    /// Starts a new tick with every server idle.
+    pub fn reset(&mut self) {
+        self.free_at.fill(Duration::ZERO);
+    }
+
+    /// Hands a request arriving at `arrival` to the server that is free first
+    /// and returns the time it spends waiting and being served.
+    pub fn process(&mut self, arrival: Duration, service_time: Duration) -> Duration {
+        let free_at = self.free_at.iter_mut().min().unwrap();
+        let start = (*free_at).max(arrival);
+        *free_at = start + service_time;
+        *free_at - arrival
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_wait_for_a_free_server() {
+        let mut processing_queue = ProcessingQueue::new(2);
+        let service_time = Duration::from_millis(10);
+
+        let delays: Vec<Duration> = (0..4)
+            .map(|_| processing_queue.process(Duration::ZERO, service_time))
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+                Duration::from_millis(20),
+                Duration::from_millis(20)
+            ]
+        );
+
+        // Arriving after the backlog is gone there is no waiting.
+        assert_eq!(
+            processing_queue.process(Duration::from_millis(50), service_time),
+            service_time
+        );
+
+        processing_queue.reset();
+        assert_eq!(
+            processing_queue.process(Duration::ZERO, service_time),
+            service_time
+        );
+    }
+}
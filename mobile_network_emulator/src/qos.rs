@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Standardized 5QI characteristics of TS 23.501 table 5.7.4-1:
+/// (5QI, GBR, priority level, packet delay budget in milliseconds).
+const STANDARDIZED_5QI: [(u8, bool, u8, u32); 14] = [
+    (1, true, 20, 100),
+    (2, true, 40, 150),
+    (3, true, 30, 50),
+    (4, true, 50, 300),
+    (5, false, 10, 100),
+    (6, false, 60, 300),
+    (7, false, 70, 100),
+    (8, false, 80, 300),
+    (9, false, 90, 300),
+    (80, false, 68, 10),
+    (82, true, 19, 10),
+    (83, true, 22, 10),
+    (84, true, 24, 30),
+    (85, true, 21, 5),
+];
+
+const DEFAULT_5QI: u8 = 9;
+
+/// A QoS flow of a PDU session.
+/// Flows are created from a QoS reference of the form `5qi-{5QI}` naming a standardized 5QI.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QosFlow {
+    id: u32,
+    qos_reference: String,
+    five_qi: u8,
+    gbr: bool,
+    priority_level: u8,
+    packet_delay_budget: u32,
+    guaranteed_bit_rate: Option<u64>,
+    maximum_bit_rate: Option<u64>,
+    application_ids: Vec<u32>,
+    budget_violated: bool,
+}
+
+impl QosFlow {
+    /// Creates the flow `qos_reference` names, `None` if the reference is unknown.
+    /// An empty `application_ids` makes the flow carry all traffic of the session.
+    pub fn new(
+        id: u32,
+        qos_reference: &str,
+        application_ids: Vec<u32>,
+        guaranteed_bit_rate: Option<u64>,
+        maximum_bit_rate: Option<u64>,
+    ) -> Option<Self> {
+        let five_qi: u8 = qos_reference.strip_prefix("5qi-")?.parse().ok()?;
+        let (_, gbr, priority_level, packet_delay_budget) = STANDARDIZED_5QI
+            .iter()
+            .find(|(standardized, ..)| *standardized == five_qi)?;
+        Some(Self {
+            id,
+            qos_reference: qos_reference.to_string(),
+            five_qi,
+            gbr: *gbr,
+            priority_level: *priority_level,
+            packet_delay_budget: *packet_delay_budget,
+            guaranteed_bit_rate: if *gbr { guaranteed_bit_rate } else { None },
+            maximum_bit_rate,
+            application_ids,
+            budget_violated: false,
+        })
+    }
+
+    /// The best effort flow every PDU session has.
+    pub fn default_flow() -> Self {
+        Self::new(0, &format!("5qi-{}", DEFAULT_5QI), Vec::new(), None, None).unwrap()
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn get_qos_reference(&self) -> &str {
+        &self.qos_reference
+    }
+
+    pub fn get_packet_delay_budget(&self) -> Duration {
+        Duration::from_millis(self.packet_delay_budget as u64)
+    }
+
+    pub fn carries(&self, application_id: u32) -> bool {
+        self.application_ids.is_empty() || self.application_ids.contains(&application_id)
+    }
+
+    /// Lower values are scheduled first, GBR flows before all non-GBR flows.
+    pub fn scheduling_priority(&self) -> (bool, u8) {
+        (!self.gbr, self.priority_level)
+    }
+
+    /// Records a measured delay, returns true if it starts a violation of the packet delay budget.
+    pub fn record_delay(&mut self, delay: Duration) -> bool {
+        let violated = delay > self.get_packet_delay_budget();
+        let started = violated && !self.budget_violated;
+        self.budget_violated = violated;
+        started
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_from_qos_reference() {
+        let flow = QosFlow::new(1, "5qi-82", vec![3], Some(1_000_000), None).unwrap();
+
+        assert_eq!(flow.get_packet_delay_budget(), Duration::from_millis(10));
+        assert_eq!(flow.scheduling_priority(), (false, 19));
+        assert!(flow.carries(3));
+        assert!(!flow.carries(4));
+        assert!(QosFlow::new(1, "5qi-10", vec![], None, None).is_none());
+        assert!(QosFlow::new(1, "gold", vec![], None, None).is_none());
+        assert!(QosFlow::default_flow().carries(4));
+    }
+
+    #[test]
+    fn record_delay() {
+        let mut flow = QosFlow::new(1, "5qi-3", vec![], None, None).unwrap();
+
+        assert!(!flow.record_delay(Duration::from_millis(20)));
+        assert!(flow.record_delay(Duration::from_millis(60)));
+        assert!(!flow.record_delay(Duration::from_millis(60)));
+        assert!(!flow.record_delay(Duration::from_millis(20)));
+        assert!(flow.record_delay(Duration::from_millis(60)));
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use geo::Contains;
 use geo::EuclideanDistance;
 use geo::Point;
@@ -6,11 +8,17 @@ use serde::Serialize;
 
 use crate::{pdu_session::PDUSession, user::User};
 
+/// Number of requests a RAN schedules per tick without queueing.
+const DEFAULT_CAPACITY: usize = 8;
+/// Extra delay for every request queued behind a full RAN.
+const SCHEDULING_DELAY: Duration = Duration::from_millis(5);
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Ran {
     id: u32,
     position: Point,
     radius: f64,
+    capacity: usize,
     connected_users: Vec<PDUSession>,
 }
 
@@ -20,10 +28,12 @@ impl Ran {
             id,
             position,
             radius,
+            capacity: DEFAULT_CAPACITY,
             connected_users: Vec::new(),
         }
     }
 
+    #[allow(dead_code)]
     fn get_connected_users(&mut self) -> Vec<PDUSession> {
         self.connected_users.drain(..).collect()
     }
@@ -57,6 +67,7 @@ impl Ran {
         self.connected_users.push(user);
     }
 
+    #[allow(dead_code)]
     pub fn connect_users(&mut self, mut users: Vec<PDUSession>) {
         self.connected_users.append(&mut users);
     }
@@ -68,6 +79,11 @@ impl Ran {
     pub fn get_position(&self) -> Point {
         self.position
     }
+
+    /// The queueing delay of the request scheduled as number `rank` (starting at 0) in a tick.
+    pub fn scheduling_delay(&self, rank: usize) -> Duration {
+        SCHEDULING_DELAY * (rank + 1).saturating_sub(self.capacity) as u32
+    }
 }
 
 impl Contains<User> for Ran {
@@ -81,11 +97,12 @@ impl Serialize for Ran {
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("Ran", 4)?;
+        let mut state = serializer.serialize_struct("Ran", 5)?;
         state.serialize_field("id", &self.id)?;
         state.serialize_field("x", &self.position.x())?;
         state.serialize_field("y", &self.position.y())?;
         state.serialize_field("radius", &self.radius)?;
+        state.serialize_field("capacity", &self.capacity)?;
         state.end()
     }
 }
@@ -185,4 +202,17 @@ mod tests {
         let all_pdu_sessions = ran.get_current_connected_users();
         assert_eq!(all_pdu_sessions.len(), 32);
     }
+
+    #[test]
+    fn scheduling_delay() {
+        let ran = Ran::new(1, Point::new(0.5, 0.5), 0.5);
+
+        assert_eq!(ran.scheduling_delay(0), Duration::ZERO);
+        assert_eq!(ran.scheduling_delay(DEFAULT_CAPACITY - 1), Duration::ZERO);
+        assert_eq!(ran.scheduling_delay(DEFAULT_CAPACITY), SCHEDULING_DELAY);
+        assert_eq!(
+            ran.scheduling_delay(DEFAULT_CAPACITY + 2),
+            SCHEDULING_DELAY * 3
+        );
+    }
 }
@@ -0,0 +1,100 @@
+use std::{
+    fmt::{Display, Formatter},
+    ops::{Add, Sub},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Compute resources, either offered by an edge data center or needed by an application.
+/// `cpu` is in cores and `memory` in MiB.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Resources {
+    cpu: f64,
+    memory: u64,
+}
+
+impl Resources {
+    pub const fn new(cpu: f64, memory: u64) -> Self {
+        Resources { cpu, memory }
+    }
+
+    pub fn get_cpu(&self) -> f64 {
+        self.cpu
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.cpu.is_finite() && self.cpu >= 0.0) {
+            return Err(format!("cpu {} must be a non-negative number", self.cpu));
+        }
+        Ok(())
+    }
+
+    /// Whether these resources can be taken from `available`.
+    pub fn fits_in(&self, available: &Resources) -> bool {
+        self.cpu <= available.cpu && self.memory <= available.memory
+    }
+
+    /// The share of `capacity` these resources take, per resource.
+    pub fn utilisation(&self, capacity: &Resources) -> Utilisation {
+        let share = |used: f64, capacity: f64| if capacity > 0.0 { used / capacity } else { 0.0 };
+        Utilisation {
+            cpu: share(self.cpu, capacity.cpu),
+            memory: share(self.memory as f64, capacity.memory as f64),
+        }
+    }
+}
+
+impl Add for Resources {
+    type Output = Resources;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Resources::new(self.cpu + rhs.cpu, self.memory + rhs.memory)
+    }
+}
+
+impl Sub for Resources {
+    type Output = Resources;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Resources::new(
+            (self.cpu - rhs.cpu).max(0.0),
+            self.memory.saturating_sub(rhs.memory),
+        )
+    }
+}
+
+impl Display for Resources {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} cpu, {} MiB memory", self.cpu, self.memory)
+    }
+}
+
+/// How much of an edge data center's capacity is in use, as fractions between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Utilisation {
+    cpu: f64,
+    memory: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_in() {
+        let capacity = Resources::new(4.0, 4096);
+        let used = Resources::new(1.0, 1024) + Resources::new(2.0, 1024);
+
+        assert!(Resources::new(1.0, 2048).fits_in(&(capacity - used)));
+        assert!(!Resources::new(1.5, 512).fits_in(&(capacity - used)));
+        assert!(!Resources::new(0.5, 4096).fits_in(&(capacity - used)));
+        assert_eq!(
+            used.utilisation(&capacity),
+            Utilisation {
+                cpu: 0.75,
+                memory: 0.5
+            }
+        );
+        assert!(Resources::new(-1.0, 0).validate().is_err());
+    }
+}
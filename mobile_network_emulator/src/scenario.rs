@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{application_catalogue::ApplicationProfile, network::ReplicaSelection, topology::Link};
+
+/// Settings of an emulation, read from the JSON file given with `--scenario`.
+#[derive(Debug, Default, Deserialize)]
+pub struct Scenario {
+    /// The transport network, every RAN is linked straight to every edge data center without it.
+    #[serde(default)]
+    topology: Option<TopologyDescription>,
+    /// How long users wait for a response before giving up on a request.
+    #[serde(default)]
+    request_timeout_ms: Option<u64>,
+    /// How requests choose among the replicas of an application.
+    #[serde(default)]
+    replica_selection: ReplicaSelection,
+    /// How long deployed applications take to start.
+    #[serde(default)]
+    cold_start_ms: Option<u64>,
+    /// The central cloud serving requests no edge data center can.
+    #[serde(default)]
+    cloud: CloudDescription,
+    /// The application catalogue, each profile with its `id`.
+    #[serde(default)]
+    applications: Option<Vec<ApplicationProfile>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopologyDescription {
+    links: Vec<Link>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CloudDescription {
+    /// Latency of the links from the RANs to the cloud when the scenario does not define the topology.
+    #[serde(default)]
+    latency_ms: Option<u64>,
+    /// The applications the cloud serves, every application without it.
+    #[serde(default)]
+    application_ids: Option<Vec<u32>>,
+}
+
+impl Scenario {
+    pub fn take_links(&mut self) -> Option<Vec<Link>> {
+        self.topology.take().map(|topology| topology.links)
+    }
+
+    pub fn get_request_timeout(&self) -> Option<Duration> {
+        self.request_timeout_ms.map(Duration::from_millis)
+    }
+
+    pub fn get_replica_selection(&self) -> ReplicaSelection {
+        self.replica_selection
+    }
+
+    pub fn get_cold_start(&self) -> Option<Duration> {
+        self.cold_start_ms.map(Duration::from_millis)
+    }
+
+    pub fn get_cloud_latency(&self) -> Option<Duration> {
+        self.cloud.latency_ms.map(Duration::from_millis)
+    }
+
+    pub fn take_cloud_application_ids(&mut self) -> Option<Vec<u32>> {
+        self.cloud.application_ids.take()
+    }
+
+    pub fn take_applications(&mut self) -> Option<Vec<ApplicationProfile>> {
+        self.applications.take()
+    }
+}
@@ -0,0 +1,506 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap},
+    error::Error,
+    fmt::{Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
+
+use geo::{EuclideanDistance, Point};
+use rand::Rng;
+use rand_distr::StandardNormal;
+use serde::{ser::SerializeStruct, Deserialize, Serialize};
+
+/// Latency of the links from the RANs to the central cloud when the scenario gives none.
+pub const DEFAULT_CLOUD_LATENCY: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub struct TopologyError {
+    message: String,
+}
+
+impl TopologyError {
+    pub fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl Display for TopologyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for TopologyError {}
+
+/// A node of the transport network, written as `ran-{id}`, `router-{id}`, `edc-{id}` or `cloud`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Node {
+    Ran(u32),
+    Router(u32),
+    EdgeDataCenter(u32),
+    Cloud,
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Node::Ran(id) => write!(f, "ran-{}", id),
+            Node::Router(id) => write!(f, "router-{}", id),
+            Node::EdgeDataCenter(id) => write!(f, "edc-{}", id),
+            Node::Cloud => write!(f, "cloud"),
+        }
+    }
+}
+
+impl FromStr for Node {
+    type Err = TopologyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "cloud" {
+            return Ok(Node::Cloud);
+        }
+        let invalid = || TopologyError::new(format!("{} is not a node of the topology", s));
+        let (kind, id) = s.split_once('-').ok_or_else(invalid)?;
+        let id = id.parse().map_err(|_| invalid())?;
+        match kind {
+            "ran" => Ok(Node::Ran(id)),
+            "router" => Ok(Node::Router(id)),
+            "edc" => Ok(Node::EdgeDataCenter(id)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for Node {
+    type Error = TopologyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Node> for String {
+    fn from(node: Node) -> Self {
+        node.to_string()
+    }
+}
+
+/// A bidirectional link of the transport network.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Link {
+    from: Node,
+    to: Node,
+    latency_ms: f64,
+    /// In Mbit/s.
+    bandwidth_mbps: f64,
+    /// Probability that a packet is lost on the link.
+    #[serde(default)]
+    loss: f64,
+    /// Scale of the half-normally distributed delay queueing adds to the latency of the link.
+    #[serde(default)]
+    jitter_ms: f64,
+}
+
+impl Link {
+    pub fn new(from: Node, to: Node, latency: Duration, bandwidth_mbps: f64, loss: f64) -> Self {
+        Link {
+            from,
+            to,
+            latency_ms: latency.as_secs_f64() * 1000.0,
+            bandwidth_mbps,
+            loss,
+            jitter_ms: 0.0,
+        }
+    }
+
+    pub fn get_latency(&self) -> Duration {
+        Duration::from_secs_f64(self.latency_ms / 1000.0)
+    }
+
+    fn validate(&self) -> Result<(), TopologyError> {
+        let invalid = |what: &str| {
+            Err(TopologyError::new(format!(
+                "link {} - {} has an invalid {}",
+                self.from, self.to, what
+            )))
+        };
+        if self.from == self.to {
+            return invalid("end, it connects a node to itself");
+        }
+        if !(self.latency_ms.is_finite() && self.latency_ms >= 0.0) {
+            return invalid("latency");
+        }
+        if !(self.bandwidth_mbps.is_finite() && self.bandwidth_mbps > 0.0) {
+            return invalid("bandwidth");
+        }
+        if !(0.0..=1.0).contains(&self.loss) {
+            return invalid("loss");
+        }
+        if !(self.jitter_ms.is_finite() && self.jitter_ms >= 0.0) {
+            return invalid("jitter");
+        }
+        Ok(())
+    }
+}
+
+/// The route between two nodes: its latency is the sum of the links' latencies,
+/// its bandwidth that of the narrowest link and a packet gets through if no link loses it.
+/// The jitter of the links adds up like the deviations of independent normal distributions.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Route {
+    from: Node,
+    to: Node,
+    nodes: Vec<Node>,
+    latency_ms: f64,
+    bandwidth_mbps: f64,
+    loss: f64,
+    jitter_ms: f64,
+}
+
+impl Route {
+    pub fn get_latency(&self) -> Duration {
+        Duration::from_secs_f64(self.latency_ms / 1000.0)
+    }
+
+    /// How long it takes to send `size_mb` MB along the route at the bandwidth of its narrowest link.
+    pub fn transfer_time(&self, size_mb: u64) -> Duration {
+        self.get_latency() + self.transmission_time(size_mb * 1000)
+    }
+
+    /// How long it takes to put `size_kb` kB on the narrowest link of the route.
+    pub fn transmission_time(&self, size_kb: u64) -> Duration {
+        Duration::from_secs_f64(size_kb as f64 * 8.0 / 1000.0 / self.bandwidth_mbps)
+    }
+
+    /// The latency of a single packet: the latency of the route plus a half-normally distributed
+    /// queueing delay with the jitter as scale, packets are never faster than the links allow.
+    pub fn sample_latency<R: Rng>(&self, rng: &mut R) -> Duration {
+        let deviation: f64 = rng.sample(StandardNormal);
+        self.get_latency() + Duration::from_secs_f64(self.jitter_ms * deviation.abs() / 1000.0)
+    }
+
+    /// Whether a packet sent along the route is lost.
+    pub fn sample_loss<R: Rng>(&self, rng: &mut R) -> bool {
+        rng.gen_bool(self.loss)
+    }
+}
+
+/// The transport network between RANs, aggregation routers, edge data centers and the central cloud.
+/// Routes from every RAN and edge data center are computed up front with Dijkstra's algorithm over the link latencies.
+#[derive(Debug, Clone)]
+pub struct Topology {
+    links: Vec<Link>,
+    routes: HashMap<(Node, Node), Route>,
+}
+
+impl Topology {
+    pub fn new(links: Vec<Link>) -> Result<Self, TopologyError> {
+        for link in links.iter() {
+            link.validate()?;
+        }
+        let mut topology = Topology {
+            links,
+            routes: HashMap::new(),
+        };
+        let sources: Vec<Node> = topology
+            .get_nodes()
+            .into_iter()
+            .filter(|node| matches!(node, Node::Ran(_) | Node::EdgeDataCenter(_)))
+            .collect();
+        for source in sources {
+            for route in topology.shortest_routes(source) {
+                topology.routes.insert((route.from, route.to), route);
+            }
+        }
+        Ok(topology)
+    }
+
+    /// Links every RAN directly to every edge data center with the latency growing with their distance,
+    /// and to the cloud with `cloud_latency`, the transport network used when the scenario does not define one.
+    pub fn full_mesh(
+        rans: &[(u32, Point)],
+        edge_data_centers: &[(u32, Point)],
+        latency_per_distance: Duration,
+        cloud_latency: Duration,
+    ) -> Self {
+        let cloud_links = rans.iter().map(|(ran_id, _)| {
+            Link::new(
+                Node::Ran(*ran_id),
+                Node::Cloud,
+                cloud_latency,
+                10_000.0,
+                0.0,
+            )
+        });
+        let links = rans
+            .iter()
+            .flat_map(|(ran_id, ran_position)| {
+                edge_data_centers.iter().map(
+                    move |(edge_data_center_id, edge_data_center_position)| {
+                        let distance = ran_position
+                            .euclidean_distance(edge_data_center_position)
+                            .abs();
+                        Link::new(
+                            Node::Ran(*ran_id),
+                            Node::EdgeDataCenter(*edge_data_center_id),
+                            latency_per_distance.mul_f64(distance),
+                            10_000.0,
+                            0.0,
+                        )
+                    },
+                )
+            })
+            .chain(cloud_links)
+            .collect();
+        Topology::new(links).unwrap()
+    }
+
+    /// Checks that the RANs and edge data centers the links connect exist.
+    pub fn validate(
+        &self,
+        ran_ids: &[u32],
+        edge_data_center_ids: &[u32],
+    ) -> Result<(), TopologyError> {
+        for node in self.get_nodes() {
+            let exists = match node {
+                Node::Ran(id) => ran_ids.contains(&id),
+                Node::EdgeDataCenter(id) => edge_data_center_ids.contains(&id),
+                Node::Router(_) | Node::Cloud => true,
+            };
+            if !exists {
+                return Err(TopologyError::new(format!(
+                    "the topology links {}, which does not exist",
+                    node
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_nodes(&self) -> Vec<Node> {
+        let mut nodes: Vec<Node> = self
+            .links
+            .iter()
+            .flat_map(|link| [link.from, link.to])
+            .collect();
+        nodes.sort();
+        nodes.dedup();
+        nodes
+    }
+
+    /// The shortest route from a RAN to `to`, `None` if they are not connected.
+    pub fn get_route(&self, ran_id: u32, to: Node) -> Option<&Route> {
+        self.routes.get(&(Node::Ran(ran_id), to))
+    }
+
+    /// The shortest route between two edge data centers, the way application state is migrated.
+    pub fn get_route_between_edge_data_centers(&self, from: u32, to: u32) -> Option<&Route> {
+        self.routes
+            .get(&(Node::EdgeDataCenter(from), Node::EdgeDataCenter(to)))
+    }
+
+    /// The shortest routes from every RAN to the edge data centers and the cloud, ordered by their ends.
+    pub fn get_routes(&self) -> Vec<&Route> {
+        let mut routes: Vec<&Route> = self
+            .routes
+            .values()
+            .filter(|route| {
+                matches!(route.from, Node::Ran(_))
+                    && matches!(route.to, Node::EdgeDataCenter(_) | Node::Cloud)
+            })
+            .collect();
+        routes.sort_by_key(|route| (route.from, route.to));
+        routes
+    }
+
+    /// Dijkstra's algorithm from `from` to every node reachable from it.
+    fn shortest_routes(&self, from: Node) -> Vec<Route> {
+        let mut neighbours: HashMap<Node, Vec<&Link>> = HashMap::new();
+        for link in self.links.iter() {
+            neighbours.entry(link.from).or_default().push(link);
+            neighbours.entry(link.to).or_default().push(link);
+        }
+
+        // Latencies are compared in nanoseconds so they can be ordered in the heap.
+        let mut previous: BTreeMap<Node, (Node, &Link)> = BTreeMap::new();
+        let mut latencies: HashMap<Node, u128> = HashMap::from([(from, 0)]);
+        let mut queue = BinaryHeap::from([Reverse((0u128, from))]);
+        while let Some(Reverse((latency, node))) = queue.pop() {
+            if latencies.get(&node).is_some_and(|best| *best < latency) {
+                continue;
+            }
+            for link in neighbours.get(&node).into_iter().flatten() {
+                let next = if link.from == node {
+                    link.to
+                } else {
+                    link.from
+                };
+                let next_latency = latency + link.get_latency().as_nanos();
+                if latencies.get(&next).is_none_or(|best| next_latency < *best) {
+                    latencies.insert(next, next_latency);
+                    previous.insert(next, (node, link));
+                    queue.push(Reverse((next_latency, next)));
+                }
+            }
+        }
+
+        previous
+            .keys()
+            .map(|to| {
+                let mut nodes = vec![*to];
+                let mut links = Vec::new();
+                while let Some((node, link)) = previous.get(nodes.last().unwrap()) {
+                    nodes.push(*node);
+                    links.push(*link);
+                }
+                nodes.reverse();
+                Route {
+                    from,
+                    to: *to,
+                    nodes,
+                    latency_ms: links.iter().map(|link| link.latency_ms).sum(),
+                    bandwidth_mbps: links
+                        .iter()
+                        .map(|link| link.bandwidth_mbps)
+                        .fold(f64::INFINITY, f64::min),
+                    loss: 1.0 - links.iter().map(|link| 1.0 - link.loss).product::<f64>(),
+                    jitter_ms: links
+                        .iter()
+                        .map(|link| link.jitter_ms.powi(2))
+                        .sum::<f64>()
+                        .sqrt(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Serialize for Topology {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut state = serializer.serialize_struct("Topology", 3)?;
+        state.serialize_field("nodes", &self.get_nodes())?;
+        state.serialize_field("links", &self.links)?;
+        state.serialize_field("routes", &self.get_routes())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(from: &str, to: &str, latency_ms: u64, bandwidth_mbps: f64) -> Link {
+        Link::new(
+            from.parse().unwrap(),
+            to.parse().unwrap(),
+            Duration::from_millis(latency_ms),
+            bandwidth_mbps,
+            0.01,
+        )
+    }
+
+    #[test]
+    fn parse_nodes() {
+        assert_eq!("ran-3".parse::<Node>().unwrap(), Node::Ran(3));
+        assert_eq!("edc-0".parse::<Node>().unwrap(), Node::EdgeDataCenter(0));
+        assert_eq!("cloud".parse::<Node>().unwrap(), Node::Cloud);
+        assert!("edc".parse::<Node>().is_err());
+        assert!("switch-1".parse::<Node>().is_err());
+        assert_eq!(
+            serde_json::to_value(Node::Router(2)).unwrap(),
+            serde_json::json!("router-2")
+        );
+    }
+
+    #[test]
+    fn shortest_route() {
+        let topology = Topology::new(vec![
+            link("ran-0", "router-0", 2, 1000.0),
+            link("ran-1", "router-0", 2, 1000.0),
+            link("router-0", "edc-0", 10, 1000.0),
+            link("router-0", "router-1", 3, 100.0),
+            link("router-1", "edc-0", 4, 1000.0),
+            link("router-1", "cloud", 30, 10000.0),
+        ])
+        .unwrap();
+
+        let route = topology.get_route(0, Node::EdgeDataCenter(0)).unwrap();
+        assert_eq!(
+            route.nodes,
+            vec![
+                Node::Ran(0),
+                Node::Router(0),
+                Node::Router(1),
+                Node::EdgeDataCenter(0)
+            ]
+        );
+        assert_eq!(route.get_latency(), Duration::from_millis(9));
+        assert_eq!(route.bandwidth_mbps, 100.0);
+        assert!((route.loss - (1.0 - 0.99f64.powi(3))).abs() < 1e-9);
+
+        let route = topology.get_route(1, Node::Cloud).unwrap();
+        assert_eq!(route.get_latency(), Duration::from_millis(35));
+        assert!(topology.get_route(0, Node::EdgeDataCenter(1)).is_none());
+        assert_eq!(topology.get_routes().len(), 4);
+
+        assert!(topology.validate(&[0, 1], &[0]).is_ok());
+        assert!(topology.validate(&[0], &[0]).is_err());
+    }
+
+    #[test]
+    fn route_between_edge_data_centers() {
+        let topology = Topology::new(vec![
+            link("ran-0", "edc-0", 2, 1000.0),
+            link("edc-0", "router-0", 3, 100.0),
+            link("router-0", "edc-1", 5, 1000.0),
+        ])
+        .unwrap();
+
+        let route = topology.get_route_between_edge_data_centers(1, 0).unwrap();
+        assert_eq!(route.get_latency(), Duration::from_millis(8));
+        // 100 MB over 100 Mbit/s take 8 s on top of the latency.
+        assert_eq!(route.transfer_time(100), Duration::from_millis(8008));
+        assert!(topology.get_route_between_edge_data_centers(0, 2).is_none());
+        assert_eq!(topology.get_routes().len(), 2);
+    }
+
+    #[test]
+    fn invalid_links_are_rejected() {
+        assert!(Topology::new(vec![link("ran-0", "ran-0", 1, 1.0)]).is_err());
+        assert!(Topology::new(vec![link("ran-0", "edc-0", 1, 0.0)]).is_err());
+        let mut jittery = link("ran-0", "edc-0", 1, 1.0);
+        jittery.jitter_ms = -1.0;
+        assert!(Topology::new(vec![jittery]).is_err());
+    }
+
+    #[test]
+    fn jitter_and_loss_are_sampled_per_packet() {
+        let mut links = vec![
+            link("ran-0", "router-0", 2, 1000.0),
+            link("router-0", "edc-0", 8, 1000.0),
+        ];
+        links[0].jitter_ms = 3.0;
+        links[1].jitter_ms = 4.0;
+        let topology = Topology::new(links).unwrap();
+        let route = topology.get_route(0, Node::EdgeDataCenter(0)).unwrap();
+        assert!((route.jitter_ms - 5.0).abs() < 1e-9);
+
+        let mut rng = rand::thread_rng();
+        let latencies: Vec<Duration> = (0..1000).map(|_| route.sample_latency(&mut rng)).collect();
+        assert!(latencies
+            .iter()
+            .all(|latency| *latency >= route.get_latency()));
+        let mean = latencies.iter().sum::<Duration>() / 1000;
+        // The mean of a half-normal distribution is its scale times sqrt(2 / pi), about 4 ms here.
+        assert!(mean > Duration::from_millis(12) && mean < Duration::from_millis(16));
+
+        let lost = (0..1000).filter(|_| route.sample_loss(&mut rng)).count();
+        assert!(lost > 0 && lost < 100);
+    }
+}
@@ -0,0 +1,304 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use mobile_network_core_event::{Event, MobileNetworkCoreEvent};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{northbound::resolve_user_id, pdu_session::PDUSession};
+
+/// The data network access identifier the emulator uses for an edge data center.
+pub fn dnai(edge_data_center_id: u32) -> String {
+    format!("edc-{}", edge_data_center_id)
+}
+
+fn resolve_edge_data_center_id(dnai: &str) -> Option<u32> {
+    dnai.strip_prefix("edc-")?.parse().ok()
+}
+
+/// Routing rule installed in the network on behalf of an AF.
+/// Requests matching the rule are served by the first listed edge data center hosting the application.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrafficInfluenceRule {
+    id: u32,
+    application_id: u32,
+    user_id: Option<u32>,
+    ip_address: Option<IpAddr>,
+    dnn: Option<String>,
+    ran_ids: Option<Vec<u32>>,
+    edge_data_center_ids: Vec<u32>,
+}
+
+impl TrafficInfluenceRule {
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn get_edge_data_center_ids(&self) -> &[u32] {
+        &self.edge_data_center_ids
+    }
+
+    pub fn applies_to(&self, pdu_session: &PDUSession, application_id: u32) -> bool {
+        self.application_id == application_id
+            && self
+                .user_id
+                .is_none_or(|user_id| user_id == pdu_session.user().get_id())
+            && self
+                .ip_address
+                .is_none_or(|ip_address| ip_address == *pdu_session.ip())
+            && self.dnn.as_ref().is_none_or(|dnn| dnn == pdu_session.dnn())
+            && self
+                .ran_ids
+                .as_ref()
+                .is_none_or(|ran_ids| ran_ids.contains(&pdu_session.get_ran().get_id()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SubscribedEvent {
+    UpPathChange,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteToLocation {
+    dnai: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    route_info: Option<serde_json::Value>,
+}
+
+/// The TrafficInfluSub resource of TS 29.522.
+/// The area of validity is given as RAN ids in `validGeoZoneIds`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficInfluenceSubscription {
+    #[serde(rename = "self", skip_serializing_if = "Option::is_none")]
+    self_link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    af_service_id: Option<String>,
+    af_app_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    af_trans_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dnn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    any_ue_ind: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gpsi: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ipv4_addr: Option<Ipv4Addr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    valid_geo_zone_ids: Option<Vec<String>>,
+    traffic_routes: Vec<RouteToLocation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notification_destination: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    subscribed_events: Vec<SubscribedEvent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supp_feat: Option<String>,
+}
+
+impl TrafficInfluenceSubscription {
+    pub fn validate(&self) -> Result<(), String> {
+        self.rule(0).map(|_| ())?;
+        if let Some(notification_destination) = &self.notification_destination {
+            if Url::parse(notification_destination).is_err() {
+                return Err(format!(
+                    "notificationDestination {} is not a valid URL",
+                    notification_destination
+                ));
+            }
+        }
+        if !self.subscribed_events.is_empty() && self.notification_destination.is_none() {
+            return Err("subscribedEvents requires a notificationDestination".to_string());
+        }
+        Ok(())
+    }
+
+    /// The routing rule this subscription asks the network to apply.
+    pub fn rule(&self, id: u32) -> Result<TrafficInfluenceRule, String> {
+        let application_id = self
+            .af_app_id
+            .parse()
+            .map_err(|_| format!("afAppId {} is not a known application", self.af_app_id))?;
+        let user_id = match &self.gpsi {
+            Some(gpsi) => {
+                Some(resolve_user_id(gpsi).ok_or(format!("gpsi {} does not identify a UE", gpsi))?)
+            }
+            None => None,
+        };
+        if user_id.is_none() && self.ipv4_addr.is_none() && self.any_ue_ind != Some(true) {
+            return Err("one of anyUeInd, gpsi or ipv4Addr is required".to_string());
+        }
+        let ran_ids = match &self.valid_geo_zone_ids {
+            Some(zone_ids) => Some(
+                zone_ids
+                    .iter()
+                    .map(|zone_id| {
+                        zone_id
+                            .parse()
+                            .map_err(|_| format!("validGeoZoneId {} is not a RAN id", zone_id))
+                    })
+                    .collect::<Result<Vec<u32>, String>>()?,
+            ),
+            None => None,
+        };
+        let edge_data_center_ids = self
+            .traffic_routes
+            .iter()
+            .map(|route| {
+                resolve_edge_data_center_id(&route.dnai)
+                    .ok_or(format!("dnai {} is not an edge data center", route.dnai))
+            })
+            .collect::<Result<Vec<u32>, String>>()?;
+        if edge_data_center_ids.is_empty() {
+            return Err("trafficRoutes must not be empty".to_string());
+        }
+        Ok(TrafficInfluenceRule {
+            id,
+            application_id,
+            user_id,
+            ip_address: self.ipv4_addr.map(IpAddr::V4),
+            dnn: self.dnn.clone(),
+            ran_ids,
+            edge_data_center_ids,
+        })
+    }
+
+    pub fn set_self_link(&mut self, self_link: String) {
+        self.self_link = Some(self_link);
+    }
+
+    pub fn get_notification_destination(&self) -> Option<Url> {
+        self.notification_destination
+            .as_ref()
+            .map(|notification_destination| Url::parse(notification_destination).unwrap())
+    }
+
+    /// Whether `event` is a user plane path change this subscription asked to be notified about.
+    pub fn matches(&self, event: &MobileNetworkCoreEvent) -> bool {
+        if !self
+            .subscribed_events
+            .contains(&SubscribedEvent::UpPathChange)
+        {
+            return false;
+        }
+        let up_path_change = match event.get_event() {
+            Event::UpPathChange(up_path_change) => up_path_change,
+            _ => return false,
+        };
+        Some(up_path_change.application_id) == self.af_app_id.parse().ok()
+            && self
+                .gpsi
+                .as_deref()
+                .is_none_or(|gpsi| resolve_user_id(gpsi) == Some(event.get_user_id()))
+            && self
+                .ipv4_addr
+                .is_none_or(|ipv4_addr| ipv4_addr == up_path_change.ipv4_addr)
+    }
+
+    pub fn notification(&self, events: &[MobileNetworkCoreEvent]) -> Vec<EventNotification> {
+        events
+            .iter()
+            .filter_map(|event| match event.get_event() {
+                Event::UpPathChange(up_path_change) => Some(EventNotification {
+                    af_trans_id: self.af_trans_id.clone(),
+                    dnai_chg_type: "UE_TRIGGERED".to_string(),
+                    source_dnai: dnai(up_path_change.source_edge_data_center_id),
+                    target_dnai: dnai(up_path_change.target_edge_data_center_id),
+                    ue_ipv4_addr: up_path_change.ipv4_addr,
+                    gpsi: format!("msisdn-{}", event.get_user_id()),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// User plane path change notification of TS 29.522.
+/// The emulator posts all changes found since the last delivery as a JSON array.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventNotification {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    af_trans_id: Option<String>,
+    dnai_chg_type: String,
+    source_dnai: String,
+    target_dnai: String,
+    ue_ipv4_addr: Ipv4Addr,
+    gpsi: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::Point;
+    use mobile_network_core_event::{EventKind, UpPathChangeInformation};
+    use serde_json::json;
+
+    use crate::{ran::Ran, user::User};
+
+    use super::*;
+
+    fn subscription() -> TrafficInfluenceSubscription {
+        serde_json::from_value(json!({
+            "afServiceId": "test",
+            "afAppId": "3",
+            "afTransId": "transaction",
+            "gpsi": "msisdn-1",
+            "validGeoZoneIds": ["0"],
+            "trafficRoutes": [{"dnai": "edc-2"}, {"dnai": "edc-4"}],
+            "notificationDestination": "http://localhost:8789/",
+            "subscribedEvents": ["UP_PATH_CHANGE"]
+        }))
+        .unwrap()
+    }
+
+    fn pdu_session(user_id: u32, ran_id: u32) -> PDUSession {
+        PDUSession::new(
+            User::new(user_id, Point::new(0.0, 0.0), 1.0, &(-1.0..1.0)),
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            &Ran::new(ran_id, Point::new(0.0, 0.0), 10.0),
+        )
+    }
+
+    #[test]
+    fn rule() {
+        let subscription = subscription();
+        assert!(subscription.validate().is_ok());
+
+        let rule = subscription.rule(7).unwrap();
+
+        assert_eq!(rule.get_id(), 7);
+        assert_eq!(rule.get_edge_data_center_ids(), &[2, 4]);
+        assert!(rule.applies_to(&pdu_session(1, 0), 3));
+        assert!(!rule.applies_to(&pdu_session(1, 0), 2));
+        assert!(!rule.applies_to(&pdu_session(2, 0), 3));
+        assert!(!rule.applies_to(&pdu_session(1, 1), 3));
+    }
+
+    #[test]
+    fn invalid_dnai_should_fail() {
+        let mut subscription = subscription();
+        subscription.traffic_routes = vec![RouteToLocation {
+            dnai: "cloud".to_string(),
+            route_info: None,
+        }];
+
+        assert!(subscription.validate().is_err());
+    }
+
+    #[test]
+    fn up_path_change_notification() {
+        let subscription = subscription();
+        let event = MobileNetworkCoreEvent::new(
+            Event::UpPathChange(UpPathChangeInformation::new(3, 2, 4, Ipv4Addr::LOCALHOST)),
+            EventKind::UpPathChange,
+            1,
+        );
+
+        assert!(subscription.matches(&event));
+        let notifications = subscription.notification(&[event]);
+        assert_eq!(notifications[0].source_dnai, "edc-2");
+        assert_eq!(notifications[0].target_dnai, "edc-4");
+    }
+}
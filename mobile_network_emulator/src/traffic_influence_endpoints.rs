@@ -0,0 +1,219 @@
+use actix_web::{
+    delete, get, post, put,
+    web::{Data, Json, Path},
+    HttpRequest, HttpResponse,
+};
+use mongodb::Database;
+
+use crate::{
+    mobile_network_exposure::Subscription,
+    mobile_network_exposure_endpoints::MobileNetworkExposureWrapper,
+    network_endpoints::NetworkWrapper,
+    northbound::{request_url, resource_url, ProblemDetails},
+    traffic_influence::TrafficInfluenceSubscription,
+};
+
+fn traffic_influence_subscription<'a>(
+    subscription: &'a Subscription,
+    af_id: &str,
+) -> Option<&'a TrafficInfluenceSubscription> {
+    match subscription {
+        Subscription::TrafficInfluence {
+            af_id: owner,
+            subscription,
+        } if owner == af_id => Some(subscription),
+        _ => None,
+    }
+}
+
+#[get("/3gpp-traffic-influence/v1/{af_id}/subscriptions")]
+pub async fn get_traffic_influence_subscriptions(
+    af_id: Path<String>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+) -> HttpResponse {
+    let subscriptions: Vec<TrafficInfluenceSubscription> = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .read()
+        .await
+        .get_subscribers()
+        .into_iter()
+        .filter_map(|subscriber| {
+            traffic_influence_subscription(subscriber.get_subscription(), &af_id)
+        })
+        .cloned()
+        .collect();
+    HttpResponse::Ok().json(subscriptions)
+}
+
+/// Creates a traffic influence subscription and installs its routing rule in the network.
+#[post("/3gpp-traffic-influence/v1/{af_id}/subscriptions")]
+pub async fn post_traffic_influence_subscription(
+    request: HttpRequest,
+    af_id: Path<String>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    network_wrapper: Data<NetworkWrapper>,
+    database: Data<Database>,
+    subscription: Json<TrafficInfluenceSubscription>,
+) -> HttpResponse {
+    let mut subscription = subscription.into_inner();
+    if let Err(err) = subscription.validate() {
+        return ProblemDetails::bad_request(&err);
+    }
+
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    let id = mobile_network_exposure.add_subscription(Subscription::TrafficInfluence {
+        af_id: af_id.clone(),
+        subscription: subscription.clone(),
+    });
+    let rule = match subscription.rule(id) {
+        Ok(rule) => rule,
+        Err(err) => {
+            mobile_network_exposure.remove_subscription(id).unwrap();
+            return ProblemDetails::bad_request(&err);
+        }
+    };
+    let location = resource_url(&request, id);
+    subscription.set_self_link(location.clone());
+    mobile_network_exposure
+        .update_subscription(
+            id,
+            Subscription::TrafficInfluence {
+                af_id: af_id.into_inner(),
+                subscription: subscription.clone(),
+            },
+        )
+        .unwrap();
+    network_wrapper
+        .network
+        .write()
+        .await
+        .add_traffic_influence_rule(rule);
+
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::Created()
+        .insert_header(("Location", location))
+        .json(subscription)
+}
+
+#[get("/3gpp-traffic-influence/v1/{af_id}/subscriptions/{subscription_id}")]
+pub async fn get_traffic_influence_subscription(
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+) -> HttpResponse {
+    let (af_id, subscription_id) = path.into_inner();
+    match mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .read()
+        .await
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            traffic_influence_subscription(subscriber.get_subscription(), &af_id)
+        }) {
+        Some(subscription) => HttpResponse::Ok().json(subscription),
+        None => ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        )),
+    }
+}
+
+#[put("/3gpp-traffic-influence/v1/{af_id}/subscriptions/{subscription_id}")]
+pub async fn put_traffic_influence_subscription(
+    request: HttpRequest,
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    network_wrapper: Data<NetworkWrapper>,
+    database: Data<Database>,
+    subscription: Json<TrafficInfluenceSubscription>,
+) -> HttpResponse {
+    let (af_id, subscription_id) = path.into_inner();
+    let mut subscription = subscription.into_inner();
+    if let Err(err) = subscription.validate() {
+        return ProblemDetails::bad_request(&err);
+    }
+
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    if mobile_network_exposure
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            traffic_influence_subscription(subscriber.get_subscription(), &af_id)
+        })
+        .is_none()
+    {
+        return ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        ));
+    }
+    let rule = match subscription.rule(subscription_id) {
+        Ok(rule) => rule,
+        Err(err) => return ProblemDetails::bad_request(&err),
+    };
+    subscription.set_self_link(request_url(&request));
+    mobile_network_exposure
+        .update_subscription(
+            subscription_id,
+            Subscription::TrafficInfluence {
+                af_id,
+                subscription: subscription.clone(),
+            },
+        )
+        .unwrap();
+    network_wrapper
+        .network
+        .write()
+        .await
+        .add_traffic_influence_rule(rule);
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::Ok().json(subscription)
+}
+
+#[delete("/3gpp-traffic-influence/v1/{af_id}/subscriptions/{subscription_id}")]
+pub async fn delete_traffic_influence_subscription(
+    path: Path<(String, u32)>,
+    mobile_network_exposure_wrapper: Data<MobileNetworkExposureWrapper>,
+    network_wrapper: Data<NetworkWrapper>,
+    database: Data<Database>,
+) -> HttpResponse {
+    let (af_id, subscription_id) = path.into_inner();
+    let mut mobile_network_exposure = mobile_network_exposure_wrapper
+        .mobile_network_exposure
+        .write()
+        .await;
+    if mobile_network_exposure
+        .get_subscription(subscription_id)
+        .and_then(|subscriber| {
+            traffic_influence_subscription(subscriber.get_subscription(), &af_id)
+        })
+        .is_none()
+    {
+        return ProblemDetails::not_found(&format!(
+            "Subscription with id {} does not exist",
+            subscription_id
+        ));
+    }
+    mobile_network_exposure
+        .remove_subscription(subscription_id)
+        .unwrap();
+    // The rule is already gone if it could not be installed again after a restart.
+    network_wrapper
+        .network
+        .write()
+        .await
+        .remove_traffic_influence_rule(subscription_id)
+        .ok();
+    mobile_network_exposure
+        .persist_subscriptions(&database)
+        .await;
+    HttpResponse::NoContent().finish()
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    net::IpAddr,
+    time::Duration,
+};
+
+use serde::{ser::SerializeStruct, Serialize};
+
+/// How long the usage history of an application keeps a use.
+pub const RETENTION: Duration = Duration::from_secs(300);
+
+/// The most uses the usage history of an application keeps, older ones are dropped first.
+pub const CAPACITY: usize = 16_384;
+
+/// A request served by an application.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Use {
+    ip_address: IpAddr,
+    /// The RAN the user was connected to.
+    ran_id: u32,
+    /// Since the UNIX epoch, serialized in milliseconds like the `since` of the usage query.
+    #[serde(serialize_with = "serialize_millis")]
+    timestamp: Duration,
+}
+
+fn serialize_millis<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+/// The uses of an application since a point in time, the body of
+/// `GET /network/edge_data_centers/{edc_id}/applications/{application_id}/usage`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Usage {
+    uses: Vec<Use>,
+    per_ip_address: HashMap<IpAddr, u32>,
+    per_ran: HashMap<u32, u32>,
+}
+
+/// The recent uses of an application in a ring buffer bounded by the retention window and its capacity,
+/// with counters per IP address and per RAN rolling over the uses it holds.
+#[derive(Debug, Clone)]
+pub struct UsageHistory {
+    uses: VecDeque<Use>,
+    per_ip_address: HashMap<IpAddr, u32>,
+    per_ran: HashMap<u32, u32>,
+    /// Every use since the application was deployed, including those dropped from the history.
+    total: u32,
+    retention: Duration,
+    capacity: usize,
+}
+
+impl Default for UsageHistory {
+    fn default() -> Self {
+        UsageHistory::new(RETENTION, CAPACITY)
+    }
+}
+
+impl UsageHistory {
+    pub fn new(retention: Duration, capacity: usize) -> Self {
+        UsageHistory {
+            uses: VecDeque::new(),
+            per_ip_address: HashMap::new(),
+            per_ran: HashMap::new(),
+            total: 0,
+            retention,
+            capacity,
+        }
+    }
+
+    /// Records a use at `timestamp`, dropping the oldest one when the history is full.
+    pub fn add(&mut self, ip_address: IpAddr, ran_id: u32, timestamp: Duration) {
+        self.expire(timestamp);
+        if self.uses.len() >= self.capacity {
+            self.drop_oldest();
+        }
+        self.uses.push_back(Use {
+            ip_address,
+            ran_id,
+            timestamp,
+        });
+        *self.per_ip_address.entry(ip_address).or_default() += 1;
+        *self.per_ran.entry(ran_id).or_default() += 1;
+        self.total += 1;
+    }
+
+    /// Drops the uses older than the retention window at `now`.
+    pub fn expire(&mut self, now: Duration) {
+        let oldest = now.saturating_sub(self.retention);
+        while self
+            .uses
+            .front()
+            .is_some_and(|first| first.timestamp < oldest)
+        {
+            self.drop_oldest();
+        }
+    }
+
+    fn drop_oldest(&mut self) {
+        let Some(first) = self.uses.pop_front() else {
+            return;
+        };
+        decrement(&mut self.per_ip_address, first.ip_address);
+        decrement(&mut self.per_ran, first.ran_id);
+    }
+
+    /// The uses by `ip_address` within the retention window.
+    pub fn get_uses_by(&self, ip_address: &IpAddr) -> u32 {
+        self.per_ip_address.get(ip_address).copied().unwrap_or(0)
+    }
+
+    pub fn get_total(&self) -> u32 {
+        self.total
+    }
+
+    /// The uses still held from `since` on.
+    pub fn since(&self, since: Duration) -> Usage {
+        // The uses are ordered by their timestamps.
+        let start = self.uses.partition_point(|used| used.timestamp < since);
+        let mut usage = Usage::default();
+        for used in self.uses.range(start..) {
+            *usage.per_ip_address.entry(used.ip_address).or_default() += 1;
+            *usage.per_ran.entry(used.ran_id).or_default() += 1;
+            usage.uses.push(*used);
+        }
+        usage
+    }
+}
+
+/// Counts one use less for `key`, forgetting it at zero so the counters only hold what the history does.
+fn decrement<K: Eq + Hash>(counts: &mut HashMap<K, u32>, key: K) {
+    if let Some(count) = counts.get_mut(&key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(&key);
+        }
+    }
+}
+
+impl Serialize for UsageHistory {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut state = serializer.serialize_struct("UsageHistory", 4)?;
+        state.serialize_field("total", &self.total)?;
+        state.serialize_field("retention_s", &self.retention.as_secs())?;
+        state.serialize_field("per_ip_address", &self.per_ip_address)?;
+        state.serialize_field("per_ran", &self.per_ran)?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn counters_roll_with_retention_window() {
+        let mut history = UsageHistory::new(Duration::from_secs(10), 100);
+        history.add(ip(1), 0, Duration::from_secs(100));
+        history.add(ip(1), 1, Duration::from_secs(105));
+        history.add(ip(2), 1, Duration::from_secs(112));
+
+        assert_eq!(history.get_uses_by(&ip(1)), 1);
+        assert_eq!(history.get_uses_by(&ip(2)), 1);
+        assert_eq!(history.per_ran.get(&0), None);
+        assert_eq!(history.per_ran.get(&1), Some(&2));
+        assert_eq!(history.get_total(), 3);
+
+        history.expire(Duration::from_secs(120));
+        assert_eq!(history.get_uses_by(&ip(1)), 0);
+        assert_eq!(history.uses.len(), 1);
+        assert_eq!(history.get_total(), 3);
+    }
+
+    #[test]
+    fn drops_oldest_when_full() {
+        let mut history = UsageHistory::new(Duration::from_secs(10), 2);
+        for second in 0..3 {
+            history.add(ip(second), 0, Duration::from_secs(second as u64));
+        }
+
+        assert_eq!(history.uses.len(), 2);
+        assert_eq!(history.get_uses_by(&ip(0)), 0);
+        assert_eq!(history.per_ran.get(&0), Some(&2));
+    }
+
+    #[test]
+    fn since() {
+        let mut history = UsageHistory::new(Duration::from_secs(10), 100);
+        history.add(ip(1), 0, Duration::from_secs(1));
+        history.add(ip(1), 0, Duration::from_secs(2));
+        history.add(ip(2), 3, Duration::from_secs(3));
+
+        let usage = history.since(Duration::from_secs(2));
+
+        assert_eq!(usage.uses.len(), 2);
+        assert_eq!(usage.per_ip_address.get(&ip(1)), Some(&1));
+        assert_eq!(usage.per_ran.get(&3), Some(&1));
+        assert!(history.since(Duration::from_secs(4)).uses.is_empty());
+    }
+
+    #[test]
+    fn serialize_use_timestamp_in_millis() {
+        let mut history = UsageHistory::new(Duration::from_secs(10), 100);
+        history.add(ip(1), 2, Duration::from_millis(1_700_000_000_123));
+
+        let json = serde_json::to_value(history.since(Duration::ZERO)).unwrap();
+
+        assert_eq!(json["uses"][0]["timestamp"], 1_700_000_000_123u64);
+    }
+
+    #[test]
+    fn serialize_summary() {
+        let mut history = UsageHistory::new(Duration::from_secs(10), 100);
+        history.add(ip(1), 2, Duration::from_secs(1));
+
+        let json = serde_json::to_value(&history).unwrap();
+
+        assert_eq!(json["total"], 1);
+        assert_eq!(json["per_ip_address"]["10.0.0.1"], 1);
+        assert_eq!(json["per_ran"]["2"], 1);
+        assert!(json.get("uses").is_none());
+    }
+}
@@ -61,14 +61,15 @@ impl User {
         self.posititon
     }
 
-    pub fn choose_application(&mut self, applications: &Vec<usize>) -> usize {
+    /// Mostly keeps using the last application while it is still available, `None` if no application is.
+    pub fn choose_application(&mut self, applications: &[usize]) -> Option<usize> {
         let mut rng = rand::thread_rng();
-        if rng.gen_range(0.0..1.0) > 0.02 {
-            return self.last_application;
+        if applications.contains(&self.last_application) && rng.gen_range(0.0..1.0) > 0.02 {
+            return Some(self.last_application);
         }
-        let result = *applications.choose(&mut rng).unwrap();
+        let result = *applications.choose(&mut rng)?;
         self.last_application = result;
-        result
+        Some(result)
     }
 
     pub fn next_pos(&mut self) -> Point {
@@ -103,6 +104,16 @@ impl User {
 mod tests {
     use super::*;
 
+    #[test]
+    fn choose_application_among_available() {
+        let mut user = User::new(0, Point::new(0.0, 0.1), 1., &(-500.0..500.));
+
+        assert_eq!(user.choose_application(&[]), None);
+        for _ in 0..16 {
+            assert_eq!(user.choose_application(&[usize::MAX]), Some(usize::MAX));
+        }
+    }
+
     #[test]
     fn serialize() {
         let user = User::new(0, Point::new(0.0, 0.1), 1., &(-500.0..500.));
@@ -44,6 +44,7 @@ impl Display for OrchestratorError {
 
 impl Error for OrchestratorError {}
 
+#[allow(dead_code)]
 #[derive(Deserialize)]
 pub struct Ran {
     id: u32,
@@ -72,38 +73,32 @@ impl Display for EdgeDataCenter {
 #[derive(Deserialize, Debug, Clone)]
 pub struct Application {
     id: u32,
-    accesses: HashMap<String, Vec<Duration>>,
 }
 
-impl std::ops::Sub for Application {
-    type Output = Application;
+/// A request an application served, as the emulator reports it.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Use {
+    ip_address: String,
+    /// In milliseconds since the UNIX epoch.
+    timestamp: u64,
+}
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        let mut hash_map = HashMap::new();
-        for (ip, durations) in self.accesses {
-            let rhs_durations = {
-                match rhs.accesses.get(&ip) {
-                    Some(durations) => durations.clone(),
-                    None => Vec::new(),
-                }
-            };
-            let result = durations
-                .into_par_iter()
-                .filter(|duration| {
-                    rhs_durations
-                        .iter()
-                        .filter(|rhs_duration| *rhs_duration == duration)
-                        .count()
-                        == 0
-                })
-                .collect();
+#[derive(Deserialize, Debug, Clone)]
+pub struct Usage {
+    uses: Vec<Use>,
+}
 
-            hash_map.insert(ip.clone(), result);
-        }
-        Application {
-            id: self.id,
-            accesses: hash_map,
+impl Usage {
+    /// The times each IP address used the application.
+    fn accesses(&self) -> HashMap<String, Vec<Duration>> {
+        let mut accesses: HashMap<String, Vec<Duration>> = HashMap::new();
+        for used in self.uses.iter() {
+            accesses
+                .entry(used.ip_address.clone())
+                .or_default()
+                .push(Duration::from_millis(used.timestamp));
         }
+        accesses
     }
 }
 
@@ -126,6 +121,7 @@ async fn fetch_edge_data_centers(
     }
 }
 
+#[allow(dead_code)]
 async fn fetch_rans(client: Client, url: &str) -> Result<Vec<Ran>, OrchestratorError> {
     match client.get(url).send().await {
         Ok(response) => match response.json().await {
@@ -168,41 +164,58 @@ async fn fetch_all_applications(
     applications
 }
 
-async fn remove_application(application_id: u32, edc_id: usize, base_url: &str, client: Client) {
+/// The uses of the application in the edge data center since `since`, in milliseconds since the UNIX epoch.
+async fn fetch_usage(
+    client: Client,
+    url: &str,
+    edc_id: u32,
+    application_id: u32,
+    since: u128,
+) -> Result<Usage, OrchestratorError> {
     match client
-        .delete(format!(
-            "{}/edge_data_centers/{}/applications/{}",
-            base_url, edc_id, application_id
+        .get(format!(
+            "{}/{}/applications/{}/usage?since={}",
+            url, edc_id, application_id, since
         ))
         .send()
         .await
     {
-        Ok(result) => println!(
-            "application with id {} deleting to edc {} with response: {}",
-            application_id,
-            edc_id,
-            result.text().await.unwrap()
-        ),
-        Err(e) => println!("got error deleted application: {}, {}", application_id, e),
+        Ok(response) => match response.json().await {
+            Ok(res) => Ok(res),
+            Err(e) => Err(OrchestratorError::new(&e.to_string())),
+        },
+        Err(e) => Err(OrchestratorError::new(&e.to_string())),
     }
 }
 
-async fn add_application(application_id: u32, edc_id: usize, base_url: &str, client: Client) {
+/// Migrates the application, edge data centers refuse applications they have no capacity for.
+async fn migrate_application(
+    application_id: u32,
+    source_edc_id: usize,
+    target_edc_id: usize,
+    base_url: &str,
+    client: Client,
+) {
     match client
         .post(format!(
-            "{}/edge_data_centers/{}/applications/{}",
-            base_url, edc_id, application_id
+            "{}/applications/{}/migrate",
+            base_url, application_id
         ))
+        .json(&HashMap::from([
+            ("source_edge_data_center_id", source_edc_id),
+            ("target_edge_data_center_id", target_edc_id),
+        ]))
         .send()
         .await
     {
         Ok(result) => println!(
-            "application with id {} added to edc {} with response: {}",
+            "application with id {} migrating from edc {} to edc {} with response: {}",
             application_id,
-            edc_id,
+            source_edc_id,
+            target_edc_id,
             result.text().await.unwrap()
         ),
-        Err(e) => println!("got error adding application: {}, {}", application_id, e),
+        Err(e) => println!("got error migrating application: {}, {}", application_id, e),
     }
 }
 
@@ -223,7 +236,7 @@ fn find_user_id(
                     None
                 }
             }
-            mobile_network_core_event::Event::LocationReporting(_) => None,
+            _ => None,
         })
         .filter(|(_id, timestamp)| timestamp < timestamp_last_connected)
         .min_by(|(_id_a, timestamp_a), (_id_b, timestamp_b)| {
@@ -232,21 +245,18 @@ fn find_user_id(
         })
 }
 
+#[allow(dead_code)]
 fn find_ran(
     ip_addr: &str,
     timestamp_last_connected: &Duration,
     events: &[MobileNetworkCoreEvent],
 ) -> Option<Vec<(String, u32)>> {
-    let id = match find_user_id(ip_addr, timestamp_last_connected, events) {
-        Some(id) => id,
-        None => return None,
-    };
+    let id = find_user_id(ip_addr, timestamp_last_connected, events)?;
     let mut res = Vec::new();
     dbg!(id, *timestamp_last_connected - id.1);
     let position = events
         .into_par_iter()
         .filter_map(|event| match event.get_event() {
-            mobile_network_core_event::Event::PdnConnectionEvent(_) => None,
             mobile_network_core_event::Event::LocationReporting(location_event) => {
                 if event.get_user_id() == id.0 {
                     Some((
@@ -258,6 +268,7 @@ fn find_ran(
                     None
                 }
             }
+            _ => None,
         })
         .max_by(|(_, t1, _), (_, t2, _)| t1.cmp(t2))
         .unwrap();
@@ -274,16 +285,12 @@ fn find_location(
     timestamp_last_connected: &Duration,
     events: &[MobileNetworkCoreEvent],
 ) -> Option<Vec<(Point, u32)>> {
-    let id = match find_user_id(ip_addr, timestamp_last_connected, events) {
-        Some(id) => id,
-        None => return None,
-    };
+    let id = find_user_id(ip_addr, timestamp_last_connected, events)?;
     let mut res = Vec::new();
     dbg!(id, *timestamp_last_connected - id.1);
     let position = events
         .into_par_iter()
         .filter_map(|event| match event.get_event() {
-            mobile_network_core_event::Event::PdnConnectionEvent(_) => None,
             mobile_network_core_event::Event::LocationReporting(location_event) => {
                 if event.get_user_id() == id.0 {
                     match location_event.geographic_area {
@@ -296,6 +303,7 @@ fn find_location(
                     None
                 }
             }
+            _ => None,
         })
         .max_by(|(_, t1, _), (_, t2, _)| t1.cmp(t2))
         .unwrap();
@@ -362,6 +370,7 @@ fn calculate_suggested_edc_weighted_avg(
     find_edc(&avg, edcs)
 }
 
+#[allow(dead_code)]
 fn calculate_suggested_position_avg(
     points: &[(Point, Vec<Duration>)],
     edcs: &[EdgeDataCenter],
@@ -377,14 +386,16 @@ fn calculate_suggested_position_avg(
     find_edc(&avg, edcs)
 }
 
+#[allow(dead_code)]
 fn distance_cost(ran: &Ran, edc: &EdgeDataCenter) -> f64 {
     Point::new(ran.x, ran.y)
         .euclidean_distance(&Point::new(edc.x, edc.y))
         .abs()
 }
 
-fn min_edc<'a, 'b, F>(
-    application_usage: &'a [(Ran, usize)],
+#[allow(dead_code)]
+fn min_edc<'b, F>(
+    application_usage: &[(Ran, usize)],
     edcs: &'b [EdgeDataCenter],
     cost_function: F,
 ) -> Option<&'b EdgeDataCenter>
@@ -429,19 +440,11 @@ async fn main() {
     .await
     .unwrap();
 
-    let mut applications: Vec<(usize, Application)> = fetch_all_applications(
-        client.clone(),
-        &format!("{}{}", base_url, edge_data_center_url),
-        &edge_data_centers,
-    )
-    .await;
+    let now = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let mut last_poll = now();
 
     loop {
-        let time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-            - 300;
+        let time = now().as_secs() - 300;
 
         let events = fetch_events(&collection, time).await;
 
@@ -451,19 +454,34 @@ async fn main() {
             &edge_data_centers,
         )
         .await;
+        let since = last_poll;
+        last_poll = now();
+
+        for (j, application) in new_applications.iter() {
+            // Only the uses since the last poll, the emulator keeps a bounded history.
+            let usage = match fetch_usage(
+                client.clone(),
+                &format!("{}{}", base_url, edge_data_center_url),
+                edge_data_centers[*j].id,
+                application.id,
+                since.as_millis(),
+            )
+            .await
+            {
+                Ok(usage) => usage,
+                Err(e) => {
+                    println!(
+                        "failed to fetch usage of application {} in edc {}: {}",
+                        application.id, j, e
+                    );
+                    continue;
+                }
+            };
 
-        for (i, current_application) in applications.iter() {
-            let (j, new_application) = new_applications
-                .iter()
-                .find(|(_edc_id, application)| application.id == current_application.id)
-                .unwrap();
-
-            dbg!(i, j, current_application.id, new_application.id);
-
-            let diff = new_application.clone() - current_application.clone();
+            dbg!(j, application.id);
 
             let mut user_positions = Vec::new();
-            for (ip, value) in diff.accesses.iter() {
+            for (ip, value) in usage.accesses().iter() {
                 if !value.is_empty() {
                     dbg!(value.iter().max().unwrap(), events.len());
                     let points = match find_location(ip, value.iter().max().unwrap(), &events) {
@@ -489,15 +507,17 @@ async fn main() {
                 let edc_index =
                     calculate_suggested_edc_weighted_avg(&user_positions, &edge_data_centers)
                         .unwrap();
-                if *j != edc_index {
+                let has_replica = new_applications
+                    .iter()
+                    .any(|(edc_id, replica)| *edc_id == edc_index && replica.id == application.id);
+                if *j != edc_index && !has_replica {
                     println!("Moving application from {} to {}", j, edc_index);
-                    remove_application(diff.id, *j, base_url, client.clone()).await;
-                    add_application(diff.id, edc_index, base_url, client.clone()).await
+                    migrate_application(application.id, *j, edc_index, base_url, client.clone())
+                        .await;
                 }
             }
         }
 
-        applications = new_applications;
         tokio::time::sleep(Duration::from_secs(5)).await
     }
 }
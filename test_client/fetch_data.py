@@ -12,6 +12,7 @@ if __name__ == '__main__':
 
     time_to_run = time.time() + 300
 
+    start = time.time()
     result = []
     while time.time() < time_to_run:
         timestamp = time.time()
@@ -20,7 +21,7 @@ if __name__ == '__main__':
         for a in range(8):
             for i in range(128):
                 xs = [datetime.datetime.fromtimestamp(d['timestamp']) for d in data if d['user_id'] == i and d['application_id'] == a]
-                ys = [d['time_used'] for d in data if d['user_id'] == i and d['application_id'] == a]
+                ys = [d['time_used_us'] / 1000 for d in data if d['user_id'] == i and d['application_id'] == a and d['outcome'] == 'success']
                 for y in ys:
                     avg.append(y)
 
@@ -30,3 +31,8 @@ if __name__ == '__main__':
     with open('data.json', 'w') as f:
         json.dump(result, f)
 
+    requests = [{'application_id': d['application_id'], 'time_used_us': d['time_used_us'], 'outcome': d['outcome']}
+                for d in collection.find({'timestamp': {'$gt': start}})]
+    with open('requests.json', 'w') as f:
+        json.dump(requests, f)
+
@@ -1,5 +1,6 @@
 import matplotlib.pyplot as plt
 import json
+import numpy as np
 from datetime import datetime
 
 def convert_timeseries_to_smaller(xs: [float]) -> [float]:
@@ -35,13 +36,30 @@ if __name__ == '__main__':
         yerrs = [d['std'] for d in data if d['application_id'] == id]
         plt.errorbar(xs, ys, yerr=yerrs)
         plt.xlabel("Time (s)")
-        plt.ylabel("Mean time used to access last 15s")
+        plt.ylabel("Mean latency last 15s (ms)")
         plt.title(f"Application id {id}")
         plt.savefig(f"plot_mean_app_id_{id}.png", dpi=750)
         plt.clf()
 
 
 
+    with open('requests.json', 'r') as f:
+        requests = json.load(f)
+
+    for id in range(8):
+        outcomes = [r['outcome'] for r in requests if r['application_id'] == id]
+        latencies = np.sort([r['time_used_us'] / 1000 for r in requests if r['application_id'] == id and r['outcome'] == 'success'])
+        if len(latencies) == 0:
+            continue
+        print(id, {outcome: outcomes.count(outcome) / len(outcomes) for outcome in set(outcomes)})
+        plt.plot(latencies, np.arange(1, len(latencies) + 1) / len(latencies), label=f"Application id {id}")
+    plt.xlabel("Latency (ms)")
+    plt.ylabel("CDF")
+    plt.title("Latency of successful requests")
+    plt.legend()
+    plt.savefig("plot_latency_cdf.png", dpi=750)
+    plt.clf()
+
     # for id in range(8):
     #     ax = fig.add_subplot()
     #     plt.show()